@@ -31,6 +31,14 @@ resource "aws_cloudwatch_log_group" "lfn_check" {
   tags              = local.default_tags
 }
 
+resource "aws_cloudwatch_log_group" "lfn_merge" {
+  provider          = aws.ekg_api
+  name              = "/aws/lambda/${local.lambda_merge_name}"
+  skip_destroy      = true
+  retention_in_days = 3
+  tags              = local.default_tags
+}
+
 resource "aws_cloudwatch_log_group" "sfn" {
   provider          = aws.ekg_api
   name              = local.sfn_role_name
@@ -6,9 +6,32 @@ data "aws_iam_policy_document" "lfn_invoke" {
   statement {
     effect    = "Allow"
     actions   = ["states:StartExecution"]
-    resources = [
+    resources = concat([
       "arn:aws:states:${var.aws_region}:${var.aws_account_id}:stateMachine:${local.full_name}"
-    ]
+    ], var.routed_state_machine_arns)
+  }
+
+  // HeadObject, to check whether a deleted object still has a current version
+  statement {
+    effect    = "Allow"
+    actions   = ["s3:GetObject"]
+    resources = ["${aws_s3_bucket.source_data.arn}/*"]
+  }
+
+  // Without it, HeadObject answers 403 rather than 404 for a missing object
+  statement {
+    effect    = "Allow"
+    actions   = ["s3:ListBucket"]
+    resources = [aws_s3_bucket.source_data.arn]
+  }
+
+  dynamic "statement" {
+    for_each = var.routing_table_s3_uri == "" ? [] : [var.routing_table_s3_uri]
+    content {
+      effect    = "Allow"
+      actions   = ["s3:GetObject"]
+      resources = ["arn:aws:s3:::${trimprefix(statement.value, "s3://")}"]
+    }
   }
 
   statement {
@@ -4,7 +4,24 @@
 data "aws_iam_policy_document" "lfn_load" {
 
   // TODO: Move the Neptune specific stuff here
-  
+
+  // Read the source files to rewrite their placeholders
+  statement {
+    effect    = "Allow"
+    actions   = ["s3:GetObject"]
+    resources = ["${aws_s3_bucket.source_data.arn}/*"]
+  }
+
+  // Write the rewritten copies under the staging prefix
+  statement {
+    effect  = "Allow"
+    actions = [
+      "s3:PutObject",
+      "s3:AbortMultipartUpload"
+    ]
+    resources = ["${aws_s3_bucket.source_data.arn}/${var.staging_prefix}*"]
+  }
+
   statement {
     effect  = "Allow"
     actions = [
@@ -0,0 +1,26 @@
+#
+# Policy for the Lambda Function that merges the named graph of a loaded file into the target graph
+#
+data "aws_iam_policy_document" "lfn_merge" {
+
+  // TODO: Move the Neptune specific stuff here
+
+  statement {
+    effect  = "Allow"
+    actions = [
+      "logs:CreateLogDelivery",
+      "logs:CreateLogStream",
+      "logs:GetLogDelivery",
+      "logs:UpdateLogDelivery",
+      "logs:DeleteLogDelivery",
+      "logs:ListLogDeliveries",
+      "logs:PutLogEvents",
+      "logs:PutResourcePolicy",
+      "logs:DescribeResourcePolicies",
+      "logs:DescribeLogGroups"
+    ]
+    resources = ["*"] // TODO: restrict to the log group
+  }
+}
+
+
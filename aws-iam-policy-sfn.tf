@@ -10,7 +10,9 @@ data "aws_iam_policy_document" "sfn" {
       "arn:aws:lambda:${var.aws_region}:${var.aws_account_id}:function:${local.lambda_load_name}",
       "arn:aws:lambda:${var.aws_region}:${var.aws_account_id}:function:${local.lambda_load_name}/*",
       "arn:aws:lambda:${var.aws_region}:${var.aws_account_id}:function:${local.lambda_check_name}",
-      "arn:aws:lambda:${var.aws_region}:${var.aws_account_id}:function:${local.lambda_check_name}/*"
+      "arn:aws:lambda:${var.aws_region}:${var.aws_account_id}:function:${local.lambda_check_name}/*",
+      "arn:aws:lambda:${var.aws_region}:${var.aws_account_id}:function:${local.lambda_merge_name}",
+      "arn:aws:lambda:${var.aws_region}:${var.aws_account_id}:function:${local.lambda_merge_name}/*"
     ]
   }
 
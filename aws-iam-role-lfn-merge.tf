@@ -0,0 +1,20 @@
+# Create the IAM role that the merge lambda function will use
+resource "aws_iam_role" "lfn_merge" {
+  provider             = aws.ekg_api
+  name                 = local.lfn_role_merge
+  path                 = local.path
+  assume_role_policy   = data.aws_iam_policy_document.assume_role_policy_for_service.json
+  permissions_boundary = local.permissions_boundary
+  tags                 = local.default_tags
+  managed_policy_arns  = [
+    "arn:aws:iam::aws:policy/service-role/AWSLambdaBasicExecutionRole",
+    "arn:aws:iam::aws:policy/NeptuneFullAccess", # TODO: trim this down
+    "arn:aws:iam::aws:policy/service-role/AWSLambdaVPCAccessExecutionRole"
+  ]
+}
+
+resource "aws_iam_role_policy" "lfn_merge" {
+  name   = local.lfn_role_merge
+  role   = aws_iam_role.lfn_merge.id
+  policy = data.aws_iam_policy_document.lfn_merge.json
+}
\ No newline at end of file
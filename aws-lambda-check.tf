@@ -13,26 +13,33 @@ resource "aws_lambda_function" "check" {
   environment {
     variables = {
       //
-      EKG_BASE_INTERNAL          = var.ekg_base_internal
-      EKG_ID_BASE_INTERNAL       = var.ekg_id_base_internal
-      EKG_GRAPH_BASE_INTERNAL    = var.ekg_graph_base_internal
-      EKG_ONTOLOGY_BASE_INTERNAL = var.ekg_ontology_base_internal
+      EKG_BASE_INTERNAL            = var.ekg_base_internal
+      EKG_ID_BASE_INTERNAL         = var.ekg_id_base_internal
+      EKG_GRAPH_BASE_INTERNAL      = var.ekg_graph_base_internal
+      EKG_ONTOLOGY_BASE_INTERNAL   = var.ekg_ontology_base_internal
       //
-      EKG_BASE_EXTERNAL          = var.ekg_base_external
-      EKG_ID_BASE_EXTERNAL       = var.ekg_id_base_external
-      EKG_GRAPH_BASE_EXTERNAL    = var.ekg_graph_base_external
-      EKG_ONTOLOGY_BASE_EXTERNAL = var.ekg_ontology_base_external
+      EKG_BASE_EXTERNAL            = var.ekg_base_external
+      EKG_ID_BASE_EXTERNAL         = var.ekg_id_base_external
+      EKG_GRAPH_BASE_EXTERNAL      = var.ekg_graph_base_external
+      EKG_ONTOLOGY_BASE_EXTERNAL   = var.ekg_ontology_base_external
       //
-      EKG_API_BASE               = var.ekg_api_base
+      EKG_API_BASE                 = var.ekg_api_base
       //
-      EKG_SPARQL_LOADER_ENDPOINT = var.ekg_sparql_loader_endpoint
-      EKG_SPARQL_HEALTH_ENDPOINT = var.ekg_sparql_health_endpoint
-      EKG_SPARQL_QUERY_ENDPOINT  = var.ekg_sparql_query_endpoint
-      EKG_SPARQL_UPDATE_ENDPOINT = var.ekg_sparql_update_endpoint
+      EKG_SPARQL_LOADER_ENDPOINT   = var.ekg_sparql_loader_endpoint
+      EKG_SPARQL_HEALTH_ENDPOINT   = var.ekg_sparql_health_endpoint
+      EKG_SPARQL_QUERY_ENDPOINT    = var.ekg_sparql_query_endpoint
+      EKG_SPARQL_UPDATE_ENDPOINT   = var.ekg_sparql_update_endpoint
       //
-      EKG_PIPELINE_ID            = var.name
-      neptune_s3_iam_role_arn    = var.neptune_s3_iam_role_arn
-      neptune_s3_bucket_region   = var.aws_region
+      EKG_PIPELINE_ID              = var.name
+      //
+      EKG_RETRY_BASE_DELAY_SECONDS = var.retry_policy.base_delay_seconds
+      EKG_RETRY_MULTIPLIER         = var.retry_policy.multiplier
+      EKG_RETRY_MAX_DELAY_SECONDS  = var.retry_policy.max_delay_seconds
+      EKG_RETRY_JITTER             = var.retry_policy.jitter
+      EKG_RETRY_MAX_ATTEMPTS       = var.retry_policy.max_attempts
+      //
+      neptune_s3_iam_role_arn      = var.neptune_s3_iam_role_arn
+      neptune_s3_bucket_region     = var.aws_region
     }
   }
 
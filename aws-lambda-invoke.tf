@@ -28,6 +28,17 @@ resource "aws_lambda_function" "invoke" {
       EKG_API_BASE                  = var.ekg_api_base
       //
       EKG_PIPELINE_ID               = var.name
+      EKG_LOAD_STRATEGY             = var.load_strategy
+      EKG_GRAPH_NAMING              = var.graph_naming
+      EKG_ROUTING_TABLE             = var.routing_table
+      EKG_ROUTING_TABLE_S3_URI      = var.routing_table_s3_uri
+      EKG_STAGING_PREFIX            = var.staging_prefix
+      EKG_VERIFY_SNS_SIGNATURES     = tostring(var.verify_sns_signatures)
+      //
+      EKG_SPARQL_LOADER_ENDPOINT    = var.ekg_sparql_loader_endpoint
+      EKG_SPARQL_HEALTH_ENDPOINT    = var.ekg_sparql_health_endpoint
+      EKG_SPARQL_QUERY_ENDPOINT     = var.ekg_sparql_query_endpoint
+      EKG_SPARQL_UPDATE_ENDPOINT    = var.ekg_sparql_update_endpoint
       //
       neptune_s3_iam_role_arn       = var.neptune_s3_iam_role_arn
       neptune_s3_bucket_region      = var.aws_region
@@ -0,0 +1,26 @@
+resource "null_resource" "merge" {
+  triggers = {
+    always_run = timestamp()
+  }
+
+  provisioner "local-exec" {
+    command     = "cargo lambda build --release --bin ${local.lambda_merge_crate} --arm64 --output-format binary"
+    working_dir = local.lambda_merge_crate_path
+  }
+}
+
+data "archive_file" "merge" {
+  depends_on       = [null_resource.merge]
+  type             = "zip"
+  source_dir       = local.lambda_merge_package_path
+  output_path      = local.lambda_merge_zip
+  output_file_mode = "0666"
+  excludes         = setunion(
+    fileset(local.lambda_merge_package_path, "**/*.zip"),
+    [local.lambda_merge_zip]
+  )
+}
+
+output "lambda_merge_zip" {
+  value = data.archive_file.merge.output_path
+}
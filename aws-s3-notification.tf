@@ -8,6 +8,7 @@ resource "aws_s3_bucket_notification" "s3_notif" {
     # See https://docs.aws.amazon.com/AmazonS3/latest/userguide/notification-how-to-event-types-and-destinations.html
     events = [
       "s3:ObjectCreated:*",
+      "s3:ObjectRemoved:*",
       "s3:ObjectTagging:*"
     ]
   }
@@ -5,3 +5,26 @@ resource "aws_s3_bucket" "source_data" {
   force_destroy       = true
   tags                = local.default_tags
 }
+
+# The copies of the source files with their placeholders rewritten are only needed until Neptune has loaded them
+resource "aws_s3_bucket_lifecycle_configuration" "staging" {
+  provider = aws.ekg_api
+  bucket   = aws_s3_bucket.source_data.id
+
+  rule {
+    id     = "expire-staged-copies"
+    status = "Enabled"
+
+    filter {
+      prefix = var.staging_prefix
+    }
+
+    expiration {
+      days = 7
+    }
+
+    abort_incomplete_multipart_upload {
+      days_after_initiation = 1
+    }
+  }
+}
@@ -0,0 +1,197 @@
+use {
+    crate::{
+        s3::{encode_key, S3},
+        OwnerIdentity,
+        RequestParameters,
+        ResponseElements,
+        S3Bucket,
+        S3EventName,
+        S3EventRecord,
+        S3Object,
+        UserId,
+    },
+    ekg_error::Error,
+    serde::Deserialize,
+    serde_json::Value,
+};
+
+/// An event delivered by Amazon EventBridge.
+/// For example:
+/// {
+///     "version": "0",
+///     "id": "17793124-05d4-b198-2fde-7ededc63b103",
+///     "detail-type": "Object Created",
+///     "source": "aws.s3",
+///     "account": "123456789012",
+///     "time": "2023-09-18T10:03:15Z",
+///     "region": "antartica-01",
+///     "resources": ["arn:aws:s3:::ekgf-dt-dev-metadata"],
+///     "detail": {
+///         "version": "0",
+///         "bucket": { "name": "ekgf-dt-dev-metadata" },
+///         "object": {
+///             "key": "ontology/cdmc-data-use.ttl",
+///             "size": 1206,
+///             "etag": "455c556f7d1b7f8587ecabe2dd8184af",
+///             "version-id": "LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2",
+///             "sequencer": "0065082063F0F5766D"
+///         },
+///         "request-id": "JJ807NMA5B2VMJ0D",
+///         "requester": "123456789012",
+///         "source-ip-address": "193.237.90.75",
+///         "reason": "PutObject"
+///     }
+/// }
+///
+/// See https://docs.aws.amazon.com/AmazonS3/latest/userguide/ev-events.html
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct EventBridgeEvent {
+    pub version:     String,
+    pub id:          String,
+    pub detail_type: String,
+    pub source:      String,
+    pub account:     String,
+    pub time:        String,
+    pub region:      String,
+    #[serde(default)]
+    pub resources:   Vec<String>,
+    /// The detail of the event, its structure depends on the `source` and the
+    /// `detail_type`, see [`EventBridgeEvent::s3_event_record`]
+    pub detail:      Value,
+}
+
+/// The detail of an S3 event in EventBridge
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct EventBridgeS3Detail {
+    pub version:           String,
+    pub bucket:            EventBridgeS3Bucket,
+    pub object:            EventBridgeS3Object,
+    #[serde(default)]
+    pub request_id:        String,
+    #[serde(default)]
+    pub requester:         String,
+    #[serde(default)]
+    pub source_ip_address: String,
+    /// The API call that created the object, such as `PutObject` or
+    /// `CompleteMultipartUpload`
+    #[serde(default)]
+    pub reason:            Option<String>,
+    /// `Permanently Deleted` or `Delete Marker Created`
+    #[serde(default)]
+    pub deletion_type:     Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventBridgeS3Bucket {
+    pub name: String,
+}
+
+/// The object of an S3 event in EventBridge, the `Object Deleted` events do
+/// not have a size and eTag
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct EventBridgeS3Object {
+    pub key:        String,
+    #[serde(default)]
+    pub size:       Option<u64>,
+    #[serde(default)]
+    pub etag:       Option<String>,
+    #[serde(default)]
+    pub version_id: Option<String>,
+    #[serde(default)]
+    pub sequencer:  Option<String>,
+}
+
+impl EventBridgeEvent {
+    pub fn is_s3_event(&self) -> bool { self.source == "aws.s3" }
+
+    /// The S3 event notification record equivalent to this S3 event, so that
+    /// EventBridge events are processed exactly like the S3 event
+    /// notifications that SNS (or SQS) delivers.
+    pub fn s3_event_record(&self) -> Result<S3EventRecord, Error> {
+        if !self.is_s3_event() {
+            return Err(Error::ServiceError(format!(
+                "Not an S3 event: {} from {}",
+                self.detail_type, self.source
+            )));
+        }
+        let detail = serde_json::from_value::<EventBridgeS3Detail>(self.detail.clone())?;
+        let event_name = self.s3_event_name(&detail);
+        let bucket_arn = self
+            .resources
+            .iter()
+            .find(|resource| resource.starts_with("arn:aws:s3:::"))
+            .cloned()
+            .unwrap_or_else(|| format!("arn:aws:s3:::{}", detail.bucket.name));
+        Ok(S3EventRecord {
+            event_source: "aws:s3".to_string(),
+            event_version: detail.version,
+            aws_region: self.region.clone(),
+            event_time: self.time.clone(),
+            event_name,
+            user_identity: UserId { principal_id: detail.requester },
+            request_parameters: RequestParameters { source_ip_address: detail.source_ip_address },
+            response_elements: ResponseElements {
+                x_amz_request_id: detail.request_id,
+                x_amz_id_2:       String::new(),
+            },
+            s3: S3 {
+                s3_schema_version: "1.0".to_string(),
+                configuration_id:  self.id.clone(),
+                bucket:            S3Bucket {
+                    name:           detail.bucket.name,
+                    owner_identity: OwnerIdentity { principal_id: self.account.clone() },
+                    arn:            bucket_arn,
+                },
+                object:            S3Object {
+                    // Unlike S3 event notifications, EventBridge events carry the key
+                    // as it is in the bucket
+                    key:        encode_key(detail.object.key.as_str()),
+                    size:       detail.object.size,
+                    e_tag:      detail.object.etag,
+                    version_id: detail.object.version_id,
+                    sequencer:  detail.object.sequencer,
+                },
+            },
+            glacier_event_data: None,
+            lifecycle_event_data: None,
+            intelligent_tiering_event_data: None,
+            replication_event_data: None,
+        })
+    }
+
+    /// The name that the S3 event notification of the same event would have
+    fn s3_event_name(&self, detail: &EventBridgeS3Detail) -> S3EventName {
+        match self.detail_type.as_str() {
+            "Object Created" => {
+                match detail.reason.as_deref() {
+                    Some("POST Object") => S3EventName::ObjectCreatedPost,
+                    Some("CopyObject") => S3EventName::ObjectCreatedCopy,
+                    Some("CompleteMultipartUpload") => {
+                        S3EventName::ObjectCreatedCompleteMultipartUpload
+                    },
+                    // PutObject, or a reason that S3 might add later, the object has been
+                    // created either way
+                    _ => S3EventName::ObjectCreatedPut,
+                }
+            },
+            "Object Deleted" => {
+                match detail.deletion_type.as_deref() {
+                    Some("Delete Marker Created") => S3EventName::ObjectRemovedDeleteMarkerCreated,
+                    _ => S3EventName::ObjectRemovedDelete,
+                }
+            },
+            "Object Restore Initiated" => S3EventName::ObjectRestorePost,
+            "Object Restore Completed" => S3EventName::ObjectRestoreCompleted,
+            "Object Restore Expired" => S3EventName::ObjectRestoreDelete,
+            "Object Storage Class Changed" => S3EventName::LifecycleTransition,
+            "Object Access Tier Changed" => S3EventName::IntelligentTiering,
+            "Object ACL Updated" => S3EventName::ObjectAclPut,
+            "Object Tags Added" => S3EventName::ObjectTaggingPut,
+            "Object Tags Deleted" => S3EventName::ObjectTaggingDelete,
+            other => S3EventName::Other(other.to_string()),
+        }
+    }
+}
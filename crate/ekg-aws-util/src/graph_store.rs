@@ -0,0 +1,257 @@
+//! The SPARQL side of the lambda functions: they execute SPARQL updates, ASK
+//! and SELECT queries, against Neptune via the [`ekg_sparql::SPARQLClient`] or,
+//! in unit tests, against an `InMemoryGraphStore` (with the `fake` feature)
+//! that only records them or an `OxigraphStore` (with the `oxigraph` feature)
+//! that actually executes them.
+#[cfg(feature = "fake")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "oxigraph")]
+use oxigraph::{model::Term, sparql::QueryResults, store::Store};
+use {
+    ekg_error::Error,
+    ekg_sparql::Statement,
+    std::{collections::HashMap, future::Future},
+};
+
+/// The solutions of a SELECT query, each of them maps the names of its bound
+/// variables to their value: the IRI of an IRI, the lexical form of a literal
+pub type Solutions = Vec<HashMap<String, String>>;
+
+pub trait GraphStore: Clone + Send + Sync + 'static {
+    /// Execute the given SPARQL update statement
+    fn execute(&self, statement: &Statement) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Execute the given SPARQL ASK query
+    fn ask(&self, statement: &Statement) -> impl Future<Output = Result<bool, Error>> + Send;
+
+    /// Execute the given SPARQL SELECT query
+    fn select(
+        &self,
+        statement: &Statement,
+    ) -> impl Future<Output = Result<Solutions, Error>> + Send;
+}
+
+impl GraphStore for ekg_sparql::SPARQLClient {
+    fn execute(&self, statement: &Statement) -> impl Future<Output = Result<(), Error>> + Send {
+        async move {
+            ekg_sparql::SPARQLClient::execute(self, statement)
+                .await
+                .map(|_| ())
+        }
+    }
+
+    fn ask(&self, statement: &Statement) -> impl Future<Output = Result<bool, Error>> + Send {
+        async move {
+            let result = ekg_sparql::SPARQLClient::execute(self, statement).await?;
+            result
+                .get("boolean")
+                .and_then(serde_json::Value::as_bool)
+                .ok_or_else(|| Error::ServiceError(format!("Not an ASK query result: {}", result)))
+        }
+    }
+
+    fn select(
+        &self,
+        statement: &Statement,
+    ) -> impl Future<Output = Result<Solutions, Error>> + Send {
+        async move {
+            let result = ekg_sparql::SPARQLClient::execute(self, statement).await?;
+            let bindings = result
+                .pointer("/results/bindings")
+                .and_then(serde_json::Value::as_array)
+                .ok_or_else(|| {
+                    Error::ServiceError(format!("Not a SELECT query result: {}", result))
+                })?;
+            Ok(bindings
+                .iter()
+                .filter_map(serde_json::Value::as_object)
+                .map(|binding| {
+                    binding
+                        .iter()
+                        .filter_map(|(variable, term)| {
+                            Some((
+                                variable.clone(),
+                                term["value"].as_str()?.to_string(),
+                            ))
+                        })
+                        .collect()
+                })
+                .collect())
+        }
+    }
+}
+
+/// A [`GraphStore`] that only records the statements that it was given, in
+/// order, answers every ASK query with the same answer (`false` unless set
+/// otherwise) and every SELECT query without any solutions. Clones share their
+/// statements.
+#[cfg(feature = "fake")]
+#[derive(Clone, Default)]
+pub struct InMemoryGraphStore {
+    statements: Arc<Mutex<Vec<String>>>,
+    answer:     bool,
+}
+
+#[cfg(feature = "fake")]
+impl InMemoryGraphStore {
+    pub fn new() -> Self { Self::default() }
+
+    /// Answer every ASK query with the given answer
+    pub fn with_answer(self, answer: bool) -> Self { Self { answer, ..self } }
+
+    /// All executed statements, including their prefixes
+    pub fn statements(&self) -> Vec<String> { self.statements.lock().unwrap().clone() }
+}
+
+#[cfg(feature = "fake")]
+impl GraphStore for InMemoryGraphStore {
+    fn execute(&self, statement: &Statement) -> impl Future<Output = Result<(), Error>> + Send {
+        self.statements.lock().unwrap().push(statement.to_string());
+        std::future::ready(Ok(()))
+    }
+
+    fn ask(&self, statement: &Statement) -> impl Future<Output = Result<bool, Error>> + Send {
+        self.statements.lock().unwrap().push(statement.to_string());
+        std::future::ready(Ok(self.answer))
+    }
+
+    fn select(
+        &self,
+        statement: &Statement,
+    ) -> impl Future<Output = Result<Solutions, Error>> + Send {
+        self.statements.lock().unwrap().push(statement.to_string());
+        std::future::ready(Ok(Vec::new()))
+    }
+}
+
+/// A [`GraphStore`] backed by an embedded, in-memory Oxigraph store, so that
+/// tests can check the outcome of the SPARQL updates with SPARQL queries.
+/// Clones share the same store.
+#[cfg(feature = "oxigraph")]
+#[derive(Clone)]
+pub struct OxigraphStore {
+    store: Store,
+}
+
+#[cfg(feature = "oxigraph")]
+impl OxigraphStore {
+    pub fn new() -> Result<Self, Error> {
+        let store = Store::new().map_err(|error| {
+            Error::ServiceError(format!(
+                "Could not create the Oxigraph store: {}",
+                error
+            ))
+        })?;
+        Ok(Self { store })
+    }
+
+    /// Execute the given SPARQL update
+    pub fn update(&self, sparql: &str) -> Result<(), Error> {
+        self.store.update(sparql).map_err(|error| {
+            Error::ServiceError(format!(
+                "SPARQL update failed: {}\n{}",
+                error, sparql
+            ))
+        })
+    }
+
+    /// Execute the given SELECT query, each solution maps the names of its
+    /// bound variables to their value: the IRI of an IRI, the lexical form of
+    /// a literal or the N-Triples form of anything else.
+    pub fn select(&self, sparql: &str) -> Result<Solutions, Error> {
+        match self.query(sparql)? {
+            QueryResults::Solutions(solutions) => {
+                solutions
+                    .map(|solution| {
+                        let solution = solution.map_err(|error| query_error(error, sparql))?;
+                        Ok(solution
+                            .iter()
+                            .map(|(variable, term)| {
+                                (variable.as_str().to_string(), term_value(term))
+                            })
+                            .collect())
+                    })
+                    .collect()
+            },
+            _ => {
+                Err(Error::ServiceError(format!(
+                    "Not a SELECT query: {}",
+                    sparql
+                )))
+            },
+        }
+    }
+
+    /// Execute the given ASK query
+    pub fn ask(&self, sparql: &str) -> Result<bool, Error> {
+        match self.query(sparql)? {
+            QueryResults::Boolean(answer) => Ok(answer),
+            _ => {
+                Err(Error::ServiceError(format!(
+                    "Not an ASK query: {}",
+                    sparql
+                )))
+            },
+        }
+    }
+
+    /// The number of quads in the store, in all graphs
+    pub fn len(&self) -> Result<usize, Error> {
+        self.store.len().map_err(|error| {
+            Error::ServiceError(format!(
+                "Could not count the quads in the Oxigraph store: {}",
+                error
+            ))
+        })
+    }
+
+    pub fn is_empty(&self) -> Result<bool, Error> { Ok(self.len()? == 0) }
+
+    fn query(&self, sparql: &str) -> Result<QueryResults, Error> {
+        self.store
+            .query(sparql)
+            .map_err(|error| query_error(error, sparql))
+    }
+}
+
+#[cfg(feature = "oxigraph")]
+impl GraphStore for OxigraphStore {
+    fn execute(&self, statement: &Statement) -> impl Future<Output = Result<(), Error>> + Send {
+        std::future::ready(self.update(statement.to_string().as_str()))
+    }
+
+    fn ask(&self, statement: &Statement) -> impl Future<Output = Result<bool, Error>> + Send {
+        std::future::ready(OxigraphStore::ask(
+            self,
+            statement.to_string().as_str(),
+        ))
+    }
+
+    fn select(
+        &self,
+        statement: &Statement,
+    ) -> impl Future<Output = Result<Solutions, Error>> + Send {
+        std::future::ready(OxigraphStore::select(
+            self,
+            statement.to_string().as_str(),
+        ))
+    }
+}
+
+#[cfg(feature = "oxigraph")]
+fn query_error(error: impl std::fmt::Display, sparql: &str) -> Error {
+    Error::ServiceError(format!(
+        "SPARQL query failed: {}\n{}",
+        error, sparql
+    ))
+}
+
+#[cfg(feature = "oxigraph")]
+fn term_value(term: &Term) -> String {
+    match term {
+        Term::NamedNode(iri) => iri.as_str().to_string(),
+        Term::Literal(literal) => literal.value().to_string(),
+        term => term.to_string(),
+    }
+}
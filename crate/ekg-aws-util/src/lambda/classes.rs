@@ -5,6 +5,8 @@ pub const LN_QUEUED_LOAD_REQUEST: &str = "QueuedLoadRequest";
 pub const LN_LOADING_LOAD_REQUEST: &str = "LoadingLoadRequest";
 pub const LN_FINISHED_LOAD_REQUEST: &str = "FinishedLoadRequest";
 pub const LN_FAILED_LOAD_REQUEST: &str = "FailedLoadRequest";
+pub const LN_LOAD_ERROR: &str = "LoadError";
+pub const LN_LOAD_REQUEST_STATUS_CHANGE: &str = "LoadRequestStatusChange";
 
 #[rustfmt::skip]
 lazy_static! {
@@ -13,6 +15,8 @@ lazy_static! {
     pub static ref CLASS_DATAOPS_LOADING_LOAD_REQUEST: Class = Class::declare(NS_DATAOPS.clone(), LN_LOADING_LOAD_REQUEST);
     pub static ref CLASS_DATAOPS_FINISHED_LOAD_REQUEST: Class = Class::declare(NS_DATAOPS.clone(), LN_FINISHED_LOAD_REQUEST);
     pub static ref CLASS_DATAOPS_FAILED_LOAD_REQUEST: Class = Class::declare(NS_DATAOPS.clone(), LN_FAILED_LOAD_REQUEST);
+    pub static ref CLASS_DATAOPS_LOAD_ERROR: Class = Class::declare(NS_DATAOPS.clone(), LN_LOAD_ERROR);
+    pub static ref CLASS_DATAOPS_LOAD_REQUEST_STATUS_CHANGE: Class = Class::declare(NS_DATAOPS.clone(), LN_LOAD_REQUEST_STATUS_CHANGE);
 }
 
 pub fn default_load_request_label(
@@ -0,0 +1,94 @@
+use {crate::sparql::Iri, ekg_identifier::EkgIdentifierContexts, indoc::formatdoc};
+
+/// The PROV-O namespace, see https://www.w3.org/TR/prov-o/
+pub const NS_PROV: &str = "http://www.w3.org/ns/prov#";
+
+/// Return the IRI of the named graph in which the load requests (and the
+/// datasets that they load) of the given pipeline are registered.
+///
+/// TODO: the string "load-requests" should be based on the name of the
+///       terraform module
+pub fn load_requests_graph_iri(
+    identifier_contexts: &EkgIdentifierContexts,
+    pipeline_id: &str,
+) -> String {
+    format!(
+        "{}{}-{}",
+        identifier_contexts.internal.ekg_graph_base.as_base_iri(),
+        "load-requests",
+        pipeline_id
+    )
+}
+
+/// Return the IRI of the named graph into which the merge lambda function
+/// merges the named graphs of the individual files of the given pipeline,
+/// unless another target graph has been configured.
+pub fn default_merge_target_graph_iri(
+    identifier_contexts: &EkgIdentifierContexts,
+    pipeline_id: &str,
+) -> String {
+    format!(
+        "{}{}-{}",
+        identifier_contexts.internal.ekg_graph_base.as_base_iri(),
+        "merged",
+        pipeline_id
+    )
+}
+
+/// Return the SPARQL update operation that removes every triple from the target
+/// graphs that the given named graph has been merged into (as recorded with
+/// `prov:wasDerivedFrom` in the given load-requests graph) that none of the
+/// named graphs that these target graphs were derived from contains.
+///
+/// Execute it after the named graph of a file has been replaced by a new
+/// version or has been dropped: the triples that the file no longer
+/// contributes then disappear from the target graphs as well, while the
+/// triples that another file contributes too remain.
+pub fn prune_merge_targets(source_graph: &Iri, graph_load_requests: &Iri) -> String {
+    formatdoc! {
+        r#"
+            DELETE {{
+                GRAPH ?target {{ ?s ?p ?o }}
+            }}
+            WHERE {{
+                GRAPH {graph_load_requests} {{
+                    ?target <{prov}wasDerivedFrom> {source_graph} .
+                }}
+                GRAPH ?target {{ ?s ?p ?o }}
+                FILTER NOT EXISTS {{
+                    GRAPH {graph_load_requests} {{
+                        ?target <{prov}wasDerivedFrom> ?graph .
+                    }}
+                    GRAPH ?graph {{ ?s ?p ?o }}
+                }}
+            }}
+        "#,
+        prov = NS_PROV,
+    }
+}
+
+/// Return the IRI of the `dataops:LoadRequest` with the given identifier
+pub fn load_request_iri(
+    identifier_contexts: &EkgIdentifierContexts,
+    load_request_id: &str,
+) -> String {
+    format!(
+        "{}uuid:{}",
+        identifier_contexts.internal.ekg_id_base.as_base_iri(),
+        load_request_id
+    )
+}
+
+/// Return the IRI of the `dataops:LoadError` for the given (1-based) index in
+/// the error logs of the loader job of the given load request.
+pub fn load_error_iri(
+    identifier_contexts: &EkgIdentifierContexts,
+    load_request_id: &str,
+    index: u64,
+) -> String {
+    format!(
+        "{}-error-{}",
+        load_request_iri(identifier_contexts, load_request_id),
+        index
+    )
+}
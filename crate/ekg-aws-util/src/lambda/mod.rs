@@ -1,5 +1,20 @@
-pub use {classes::*, response::LambdaResponse, status::LambdaDetailStatus};
+pub use {
+    classes::*,
+    graphs::{
+        default_merge_target_graph_iri,
+        load_error_iri,
+        load_request_iri,
+        load_requests_graph_iri,
+        prune_merge_targets,
+        NS_PROV,
+    },
+    response::LambdaResponse,
+    retry_policy::{Jitter, RetryPolicy},
+    status::{LambdaDetailStatus, Retryability},
+};
 
 mod classes;
+mod graphs;
 mod response;
+mod retry_policy;
 mod status;
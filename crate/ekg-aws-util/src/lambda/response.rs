@@ -1,23 +1,18 @@
 use {
-    crate::lambda::LambdaDetailStatus,
+    crate::lambda::{LambdaDetailStatus, RetryPolicy, Retryability},
     aws_sdk_neptunedata::{
-        error::SdkError,
-        operation::{
-            get_loader_job_status::GetLoaderJobStatusError,
-            start_loader_job::{StartLoaderJobError, StartLoaderJobOutput},
-        },
+        error::{DisplayErrorContext, ProvideErrorMetadata, SdkError},
+        operation::start_loader_job::StartLoaderJobOutput,
         types::error::BadRequestException,
     },
-    aws_smithy_runtime_api::{
-        client::result::{
-            ConstructionFailure,
-            DispatchFailure,
-            ResponseError,
-            ServiceError,
-            TimeoutError,
-        },
-        http::Response,
+    aws_smithy_runtime_api::client::result::{
+        ConstructionFailure,
+        DispatchFailure,
+        ResponseError,
+        ServiceError,
+        TimeoutError,
     },
+    aws_smithy_types::retry::ProvideErrorKind,
     rand::Rng,
     serde::{Deserialize, Serialize},
     std::error::Error,
@@ -32,11 +27,20 @@ pub struct LambdaResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub detailed_message:        Option<String>,
     pub detail_status:           LambdaDetailStatus,
+    /// Tells the Choice states of the state machine whether to try again,
+    /// see [`LambdaDetailStatus::retryability`]
+    #[serde(default)]
+    pub retryability:            Retryability,
     /// A generic slot that can be used to pass back a result identifier
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result_identifier:       Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub suggested_retry_seconds: Option<u16>,
+    /// The number of times in a row (starting at 1) that the state machine
+    /// executed the step that produced this response, see
+    /// [`LambdaResponse::with_retry_policy`]
+    #[serde(default)]
+    pub attempt:                 u16,
 }
 
 const MIN_RETRY_WAIT_SECONDS: u16 = 10;
@@ -78,6 +82,52 @@ impl LambdaResponse {
         }
     }
 
+    /// Count this response as the next attempt after the `previous` response
+    /// of the same step of the state machine (if any) and, if it asks to be
+    /// retried, suggest the delay according to the given policy. Once the
+    /// policy's budget is spent the response turns into a permanent
+    /// [`LambdaDetailStatus::RetryBudgetExhausted`] so the state machine stops
+    /// looping.
+    pub fn with_retry_policy(self, policy: &RetryPolicy, previous: Option<&Self>) -> Self {
+        let attempt = previous
+            .map(|previous| previous.attempt)
+            .unwrap_or_default()
+            .saturating_add(1);
+        match self.retryability {
+            Retryability::Pending | Retryability::Transient if policy.is_exhausted(attempt) => {
+                let detail_status = LambdaDetailStatus::RetryBudgetExhausted;
+                let msg = format!(
+                    "{} after {} attempts: {}",
+                    detail_status.message(),
+                    attempt,
+                    self.message
+                );
+                tracing::error!(msg);
+                Self {
+                    status_code: detail_status.http_status_code(),
+                    message: msg,
+                    detailed_message: self.detailed_message,
+                    retryability: detail_status.retryability(),
+                    detail_status,
+                    result_identifier: self.result_identifier,
+                    suggested_retry_seconds: None,
+                    attempt,
+                }
+            },
+            Retryability::Pending | Retryability::Transient => {
+                Self {
+                    suggested_retry_seconds: Some(policy.delay_seconds(
+                        attempt,
+                        previous.and_then(|previous| previous.suggested_retry_seconds),
+                    )),
+                    attempt,
+                    ..self
+                }
+            },
+            _ => Self { suggested_retry_seconds: None, attempt, ..self },
+        }
+    }
+
     pub fn pipeline_id_not_matching(
         received_pipeline_id: &str,
         required_pipeline_id: &str,
@@ -89,6 +139,42 @@ impl LambdaResponse {
                 received_pipeline_id, required_pipeline_id
             ),
             detail_status: LambdaDetailStatus::PipelineIdNotMatching,
+            retryability: Retryability::Permanent,
+            ..Default::default()
+        }
+    }
+
+    pub fn unsupported_rdf_format(source: &str) -> Self {
+        Self {
+            status_code: 400,
+            message: format!(
+                "Unsupported RDF file format: {} (supported are .ttl, .nt, .nq and .rdf, \
+                 optionally compressed as .gz or .bz2)",
+                source
+            ),
+            detail_status: LambdaDetailStatus::UnsupportedRdfFormat,
+            retryability: Retryability::Permanent,
+            ..Default::default()
+        }
+    }
+
+    /// The placeholder IRIs in the source could not be rewritten, see
+    /// [`crate::placeholder::stage`]. Only a source that cannot be rewritten
+    /// at all is a permanent failure, reading from or writing to S3 can be
+    /// tried again.
+    pub fn staging_failed(source: &str, error: &crate::placeholder::StagingError) -> Self {
+        let detail_status = if error.is_transient() {
+            LambdaDetailStatus::IOError
+        } else {
+            LambdaDetailStatus::S3Error
+        };
+        tracing::error!("Could not stage {}: {}", source, error);
+        Self {
+            status_code: detail_status.http_status_code(),
+            message: format!("Could not rewrite the placeholders in {}", source),
+            detailed_message: Some(error.to_string()),
+            retryability: detail_status.retryability(),
+            detail_status,
             ..Default::default()
         }
     }
@@ -107,6 +193,7 @@ impl LambdaResponse {
             status_code: 200,
             message: detail_status.message().to_string(),
             detailed_message: detailed_message.map(|s| s.to_string()),
+            retryability: detail_status.retryability(),
             detail_status,
             ..Default::default()
         };
@@ -120,27 +207,23 @@ impl LambdaResponse {
 
 impl From<&BadRequestException> for LambdaResponse {
     fn from(error: &BadRequestException) -> Self {
-        Self {
+        let detail_status = LambdaDetailStatus::from_bad_request_exception(error)
+            .unwrap_or(LambdaDetailStatus::BadRequest);
+        let response = Self {
             status_code: 400,
             message: error
                 .message
                 .clone()
                 .unwrap_or("unknown message".to_string()),
             detailed_message: Some(error.detailed_message.clone()),
-            detail_status: LambdaDetailStatus::from_bad_request_exception(error)
-                .unwrap_or(LambdaDetailStatus::UserError),
-            ..Default::default()
-        }
-    }
-}
-
-impl From<&StartLoaderJobError> for LambdaResponse {
-    fn from(error: &StartLoaderJobError) -> Self {
-        tracing::error!("Unknown service error: {:?}", error);
-        Self {
-            status_code: 500,
-            message: format!("Service Error: {:?}", error),
+            retryability: detail_status.retryability(),
+            detail_status,
             ..Default::default()
+        };
+        if response.retryability == Retryability::Transient {
+            response.retryable()
+        } else {
+            response
         }
     }
 }
@@ -150,8 +233,10 @@ impl From<ConstructionFailure> for LambdaResponse {
         let msg = format!("Construction failure: {:?}", error);
         tracing::error!(msg);
         Self {
-            status_code: 504,
+            status_code: 500,
             message: msg,
+            detail_status: LambdaDetailStatus::UserError,
+            retryability: Retryability::Permanent,
             ..Default::default()
         }
     }
@@ -165,6 +250,7 @@ impl From<TimeoutError> for LambdaResponse {
             status_code: 504,
             message: msg,
             detail_status: LambdaDetailStatus::Timedout,
+            retryability: Retryability::Transient,
             ..Default::default()
         }
         .retryable()
@@ -173,100 +259,139 @@ impl From<TimeoutError> for LambdaResponse {
 
 impl From<DispatchFailure> for LambdaResponse {
     fn from(error: DispatchFailure) -> Self {
-        let cause = error.as_connector_error().unwrap();
-        let msg = format!("Dispatch failure: {cause}");
+        let msg = match error.as_connector_error() {
+            Some(cause) => format!("Dispatch failure: {}", DisplayErrorContext(cause)),
+            None => format!("Dispatch failure: {:?}", error),
+        };
         tracing::error!(msg);
-        let detail_status = if cause.is_timeout() {
+        let detail_status = if error.is_timeout() {
             LambdaDetailStatus::Timedout
-        } else if cause.is_io() {
+        } else if error.is_io() {
             LambdaDetailStatus::IOError
-        } else if cause.is_user() {
+        } else if error.is_user() {
             LambdaDetailStatus::UserError
         } else {
             LambdaDetailStatus::LoaderJobUnexpectedError
         };
-        Self {
-            status_code: 500,
+        let response = Self {
+            status_code: if error.is_timeout() { 504 } else { 500 },
             message: msg,
-            detailed_message: Some(format!("{}", cause.source().unwrap())),
+            detailed_message: error
+                .as_connector_error()
+                .and_then(|cause| cause.source())
+                .map(|source| source.to_string()),
+            retryability: detail_status.retryability(),
             detail_status,
             ..Default::default()
+        };
+        if error.is_timeout() || error.is_io() {
+            response.retryable()
+        } else {
+            response
         }
     }
 }
 
-// impl<R> From<ResponseError<R>> for LambdaResponse {
-//     fn from(error: ResponseError<R>) -> Self {
-//         let msg = format!("Response error");
-//         tracing::error!(msg);
-//         Self {
-//             status_code: 500,
-//             message: msg,
-//             ..Default::default()
-//         }
-//     }
-// }
-
-impl From<ResponseError<Response>> for LambdaResponse {
-    fn from(error: ResponseError<Response>) -> Self {
+impl<R: std::fmt::Debug> From<ResponseError<R>> for LambdaResponse {
+    fn from(error: ResponseError<R>) -> Self {
         let msg = format!("Response error: {:?}", error);
         tracing::error!(msg);
         Self {
-            status_code: 500,
+            status_code: 502,
             message: msg,
+            detail_status: LambdaDetailStatus::LoaderJobUnexpectedError,
+            retryability: Retryability::Permanent,
             ..Default::default()
         }
     }
 }
 
-impl<E: std::fmt::Debug, R> From<ServiceError<E, R>> for LambdaResponse {
-    fn from(error: ServiceError<E, R>) -> Self {
-        let service_error = error.into_err();
-        tracing::error!("Known service error: {:?}", service_error);
-        Self {
-            status_code: 500,
-            message: format!("Service Error: {:?}", service_error),
-            ..Default::default()
-        }
-    }
+impl<E, R> From<ServiceError<E, R>> for LambdaResponse
+where E: ProvideErrorMetadata + ProvideErrorKind + Error + 'static
+{
+    fn from(error: ServiceError<E, R>) -> Self { Self::from_service_error(error.err()) }
 }
 
-impl From<SdkError<StartLoaderJobError, Response>> for LambdaResponse {
-    fn from(error: SdkError<StartLoaderJobError, Response>) -> Self {
+/// Convert the error of any neptunedata operation (such as
+/// `SdkError<StartLoaderJobError, Response>`) into a response, this never
+/// panics, whatever variant the SDK comes up with.
+impl<E, R> From<SdkError<E, R>> for LambdaResponse
+where
+    E: ProvideErrorMetadata + ProvideErrorKind + Error + 'static,
+    R: std::fmt::Debug,
+{
+    fn from(error: SdkError<E, R>) -> Self {
         match error {
-            SdkError::ServiceError(service_error) => {
-                let service_error = service_error.into_err();
-                match service_error {
-                    StartLoaderJobError::BadRequestException(exc) => return exc.into(),
-                    _ => (),
-                }
-                service_error.into()
-            },
+            SdkError::ServiceError(service_error) => service_error.into(),
             SdkError::TimeoutError(timeout_error) => timeout_error.into(),
             SdkError::DispatchFailure(dispatch_failure) => dispatch_failure.into(),
             SdkError::ResponseError(response_error) => response_error.into(),
             SdkError::ConstructionFailure(construction_failure) => construction_failure.into(),
-            _ => todo!(),
+            error => {
+                let msg = format!(
+                    "Unknown SDK error: {}",
+                    DisplayErrorContext(&error)
+                );
+                tracing::error!(msg);
+                Self {
+                    status_code: 500,
+                    message: msg,
+                    detail_status: LambdaDetailStatus::LoaderJobUnexpectedError,
+                    retryability: Retryability::Permanent,
+                    ..Default::default()
+                }
+            },
         }
     }
 }
 
-impl From<BadRequestException> for LambdaResponse {
-    fn from(error: BadRequestException) -> Self {
-        Self {
-            status_code: 400,
-            message: error
-                .message
-                .clone()
-                .unwrap_or("unknown message".to_string()),
-            detailed_message: Some(error.detailed_message.clone()),
-            detail_status: LambdaDetailStatus::from_bad_request_exception(&error)
-                .unwrap_or(LambdaDetailStatus::UserError),
+impl LambdaResponse {
+    /// Convert the service error of any neptunedata operation (such as
+    /// `StartLoaderJobError` or `GetLoaderJobStatusError`) into a response.
+    ///
+    /// All these error enums wrap the exception as their source, so we can
+    /// get to the details of a `BadRequestException`, all other exceptions
+    /// are mapped based on their error code to their own detail status and
+    /// retryability.
+    pub fn from_service_error<E>(error: &E) -> Self
+    where E: ProvideErrorMetadata + ProvideErrorKind + Error + 'static {
+        if let Some(exc) = error
+            .source()
+            .and_then(|source| source.downcast_ref::<BadRequestException>())
+        {
+            return exc.into();
+        }
+        let detail_status = LambdaDetailStatus::from_error_code(ProvideErrorMetadata::code(error));
+        let msg = format!(
+            "Service error {}: {}",
+            ProvideErrorMetadata::code(error).unwrap_or("unknown"),
+            error.message().unwrap_or("unknown message")
+        );
+        tracing::error!(msg);
+        let retryability = match error.retryable_error_kind() {
+            Some(_) => Retryability::Transient,
+            None => detail_status.retryability(),
+        };
+        let response = Self {
+            status_code: detail_status.http_status_code(),
+            message: msg,
+            detailed_message: Some(DisplayErrorContext(error).to_string()),
+            detail_status,
+            retryability,
             ..Default::default()
+        };
+        if retryability == Retryability::Transient {
+            response.retryable()
+        } else {
+            response
         }
     }
 }
 
+impl From<BadRequestException> for LambdaResponse {
+    fn from(error: BadRequestException) -> Self { (&error).into() }
+}
+
 impl From<&StartLoaderJobOutput> for LambdaResponse {
     fn from(loader_job: &StartLoaderJobOutput) -> Self {
         let load_id = loader_job.payload.get("loadId").cloned();
@@ -275,61 +400,11 @@ impl From<&StartLoaderJobOutput> for LambdaResponse {
             message: "Loader job started successfully".to_string(),
             result_identifier: load_id,
             detail_status: LambdaDetailStatus::LoaderJobInQueue,
+            // The job itself is pending but instructing the loader succeeded,
+            // there is nothing to retry for the load step
+            retryability: Retryability::Succeeded,
             suggested_retry_seconds: None,
             ..Default::default()
         }
     }
 }
-
-impl From<SdkError<GetLoaderJobStatusError, Response>> for LambdaResponse {
-    fn from(error: SdkError<GetLoaderJobStatusError, Response>) -> Self {
-        match error {
-            SdkError::ServiceError(service_error) => service_error.into(),
-            SdkError::TimeoutError(timeout_error) => timeout_error.into(),
-            SdkError::DispatchFailure(dispatch_failure) => dispatch_failure.into(),
-            SdkError::ResponseError(response_error) => response_error.into(),
-            SdkError::ConstructionFailure(construction_failure) => construction_failure.into(),
-            _ => todo!(),
-        }
-    }
-}
-
-impl From<&GetLoaderJobStatusError> for LambdaResponse {
-    fn from(error: &GetLoaderJobStatusError) -> Self {
-        match error {
-            GetLoaderJobStatusError::BadRequestException(exc) => exc.into(),
-            source @ _ => {
-                tracing::error!(
-                    "Unknown service error checking the status of a loader job: {:}",
-                    source
-                );
-                Self {
-                    status_code: 500,
-                    message: format!("{:}", source),
-                    ..Default::default()
-                }
-            },
-        }
-    }
-}
-
-impl From<StartLoaderJobError> for LambdaResponse {
-    fn from(error: StartLoaderJobError) -> Self {
-        match error {
-            StartLoaderJobError::BadRequestException(exc) => exc.into(),
-            ref source @ _ => {
-                let meta = error.meta();
-                let msg = format!(
-                    "Unknown error starting a loader job: {} {:?}",
-                    source, meta
-                );
-                tracing::error!(msg);
-                Self {
-                    status_code: 500,
-                    message: msg,
-                    ..Default::default()
-                }
-            },
-        }
-    }
-}
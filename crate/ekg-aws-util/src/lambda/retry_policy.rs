@@ -0,0 +1,167 @@
+use {
+    ekg_error::Error,
+    rand::Rng,
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
+
+pub const ENV_RETRY_BASE_DELAY_SECONDS: &str = "EKG_RETRY_BASE_DELAY_SECONDS";
+pub const ENV_RETRY_MULTIPLIER: &str = "EKG_RETRY_MULTIPLIER";
+pub const ENV_RETRY_MAX_DELAY_SECONDS: &str = "EKG_RETRY_MAX_DELAY_SECONDS";
+pub const ENV_RETRY_JITTER: &str = "EKG_RETRY_JITTER";
+pub const ENV_RETRY_MAX_ATTEMPTS: &str = "EKG_RETRY_MAX_ATTEMPTS";
+
+/// How much randomness is added to the exponential backoff so that the
+/// hundreds of load requests that hit a full loader queue at the same time
+/// don't all come back at the same time, see
+/// https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Jitter {
+    /// Wait exactly the exponential backoff delay
+    None,
+    /// Wait a random number of seconds between one and the exponential
+    /// backoff delay
+    #[default]
+    Full,
+    /// Wait a random number of seconds between the base delay and three times
+    /// the previous delay
+    Decorrelated,
+}
+
+impl Jitter {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Full => "full",
+            Self::Decorrelated => "decorrelated",
+        }
+    }
+}
+
+impl FromStr for Jitter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "full" => Ok(Self::Full),
+            "decorrelated" => Ok(Self::Decorrelated),
+            other => {
+                Err(Error::ServiceError(format!(
+                    "Unknown jitter \"{}\" in {}, expected \"none\", \"full\" or \"decorrelated\"",
+                    other, ENV_RETRY_JITTER
+                )))
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Jitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// How long the state machine waits before it retries a step (such as
+/// instructing the loader or checking the status of a loader job) and how
+/// many times it tries that step before it gives up.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// The delay before the first retry
+    pub base_delay_seconds: u16,
+    /// The factor by which the delay grows with every attempt
+    pub multiplier:         f64,
+    /// The delay never exceeds this number of seconds
+    pub max_delay_seconds:  u16,
+    pub jitter:             Jitter,
+    /// The number of attempts after which we give up, including the first one
+    pub max_attempts:       u16,
+}
+
+impl Default for RetryPolicy {
+    /// With the defaults a loader job is checked for roughly four hours at
+    /// most before the state machine gives up on it.
+    fn default() -> Self {
+        Self {
+            base_delay_seconds: 10,
+            multiplier:         2.0,
+            max_delay_seconds:  300,
+            jitter:             Jitter::Full,
+            max_attempts:       100,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Get the retry policy from the `EKG_RETRY_*` environment variables, each
+    /// of them defaults to the value of [`RetryPolicy::default`] when not set.
+    pub fn from_env() -> Result<Self, Error> {
+        let default = Self::default();
+        Ok(Self {
+            base_delay_seconds: optional_env_var(ENV_RETRY_BASE_DELAY_SECONDS)?
+                .unwrap_or(default.base_delay_seconds),
+            multiplier:         optional_env_var(ENV_RETRY_MULTIPLIER)?
+                .unwrap_or(default.multiplier),
+            max_delay_seconds:  optional_env_var(ENV_RETRY_MAX_DELAY_SECONDS)?
+                .unwrap_or(default.max_delay_seconds),
+            jitter:             optional_env_var(ENV_RETRY_JITTER)?.unwrap_or(default.jitter),
+            max_attempts:       optional_env_var(ENV_RETRY_MAX_ATTEMPTS)?
+                .unwrap_or(default.max_attempts),
+        })
+    }
+
+    /// The longest that the state machine keeps working on a load request:
+    /// every attempt to instruct the loader and every attempt to check its job
+    /// after the maximum delay. A load request that has not finished by then
+    /// has been given up on, or its execution died.
+    pub fn max_duration_seconds(&self) -> u64 {
+        2 * u64::from(self.max_attempts) * u64::from(self.max_delay_seconds)
+    }
+
+    /// Return true if the given (1-based) attempt was the last one we are
+    /// allowed to make.
+    pub fn is_exhausted(&self, attempt: u16) -> bool { attempt >= self.max_attempts }
+
+    /// The exponential backoff delay after the given (1-based) attempt,
+    /// without any jitter.
+    pub fn backoff_seconds(&self, attempt: u16) -> u16 {
+        let exponent = i32::from(attempt.max(1) - 1);
+        let delay = f64::from(self.base_delay_seconds) * self.multiplier.powi(exponent);
+        delay.min(f64::from(self.max_delay_seconds)) as u16
+    }
+
+    /// The number of seconds to wait after the given (1-based) attempt, the
+    /// previous delay is only used by [`Jitter::Decorrelated`].
+    pub fn delay_seconds(&self, attempt: u16, previous_delay_seconds: Option<u16>) -> u16 {
+        let mut rng = rand::thread_rng();
+        match self.jitter {
+            Jitter::None => self.backoff_seconds(attempt),
+            Jitter::Full => rng.gen_range(1..=self.backoff_seconds(attempt).max(1)),
+            Jitter::Decorrelated => {
+                let base = self.base_delay_seconds.max(1);
+                let upper = previous_delay_seconds
+                    .unwrap_or(base)
+                    .saturating_mul(3)
+                    .max(base);
+                rng.gen_range(base..=upper).min(self.max_delay_seconds)
+            },
+        }
+    }
+}
+
+fn optional_env_var<T: FromStr>(name: &str) -> Result<Option<T>, Error>
+where T::Err: std::fmt::Display {
+    match std::env::var(name) {
+        Ok(value) if !value.trim().is_empty() => {
+            value.trim().parse().map(Some).map_err(|error| {
+                Error::ServiceError(format!(
+                    "Invalid value \"{}\" in {}: {}",
+                    value, name, error
+                ))
+            })
+        },
+        _ => Ok(None),
+    }
+}
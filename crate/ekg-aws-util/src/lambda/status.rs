@@ -4,32 +4,98 @@ use {
     std::ops::Deref,
 };
 
-// noinspection SpellCheckingInspection
-#[derive(Clone, Debug, Deserialize, Serialize, Default)]
-pub enum LambdaDetailStatus {
-    PipelineIdNotMatching,
-    Timedout,
-    IOError,
-    MaxLoadTaskQueueSizeLimitBreached,
-    MaxConcurrentLoadLimitBreached,
-    LoaderJobInQueue,
-    LoaderJobNotStarted,
-    LoaderJobInProgress,
-    LoaderJobCompleted,
-    LoaderJobCancelledByUser,
-    LoaderJobCancelledDueToErrors,
-    LoaderJobUnexpectedError,
-    LoaderJobFailed,
-    LoaderJobS3ReadError,
-    LoaderJobS3AccessDeniedError,
-    LoaderJobCommittedWithWriteConflicts,
-    LoaderJobDataDeadlock,
-    LoaderJobDataFailedDueToFeedModifiedOrDeleted,
-    LoaderJobFailedBecauseDependencyNotSatisfied,
-    LoaderJobFailedInvalidRequest,
+/// Declare [`LambdaDetailStatus`] together with [`LambdaDetailStatus::ALL`],
+/// from the same list of variants so that `ALL` cannot miss one
+macro_rules! lambda_detail_status {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident,)*
+        }
+    ) => {
+        $(#[$meta])*
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+        }
+
+        impl $name {
+            /// The number of statuses
+            pub const COUNT: usize = [$(stringify!($variant)),*].len();
+            /// Every status, in the order in which they are declared, so that
+            /// the state machine definition can route each one of them
+            pub const ALL: [Self; Self::COUNT] = [$(Self::$variant),*];
+        }
+    };
+}
+
+lambda_detail_status! {
+    // noinspection SpellCheckingInspection
+    #[derive(Clone, Debug, Deserialize, Serialize, Default, PartialEq, Eq)]
+    pub enum LambdaDetailStatus {
+        PipelineIdNotMatching,
+        Timedout,
+        IOError,
+        MaxLoadTaskQueueSizeLimitBreached,
+        MaxConcurrentLoadLimitBreached,
+        LoaderJobInQueue,
+        LoaderJobNotStarted,
+        LoaderJobInProgress,
+        LoaderJobCompleted,
+        LoaderJobCancelledByUser,
+        LoaderJobCancelledDueToErrors,
+        LoaderJobUnexpectedError,
+        LoaderJobFailed,
+        LoaderJobS3ReadError,
+        LoaderJobS3AccessDeniedError,
+        LoaderJobCommittedWithWriteConflicts,
+        LoaderJobDataDeadlock,
+        LoaderJobDataFailedDueToFeedModifiedOrDeleted,
+        LoaderJobFailedBecauseDependencyNotSatisfied,
+        LoaderJobFailedInvalidRequest,
+        UnsupportedRdfFormat,
+        GraphMerged,
+        #[default]
+        LoaderJobStatusUnknown,
+        UserError,
+        BadRequest,
+        BulkLoadInProgress,
+        BulkLoadIdNotFound,
+        ClientTimeout,
+        ConstraintViolation,
+        IllegalArgument,
+        InternalFailure,
+        InvalidArgument,
+        InvalidParameter,
+        LoadUrlAccessDenied,
+        MissingParameter,
+        PreconditionsFailed,
+        ReadOnlyViolation,
+        S3Error,
+        Throttled,
+        TimeLimitExceeded,
+        TooManyRequests,
+        UnsupportedOperation,
+        RetryBudgetExhausted,
+        /// The same version of the same S3 object was loaded (or is being loaded)
+        /// already, by an earlier delivery of the same event
+        AlreadyLoaded,
+    }
+}
+
+/// Whether it makes sense to try again after a given [`LambdaDetailStatus`],
+/// so that the Choice states of the state machine can tell transient failures
+/// from permanent ones.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Default, PartialEq, Eq)]
+pub enum Retryability {
+    /// The loader job is still going, check its status again later
+    Pending,
+    /// A transient failure, the same request may succeed if we try again later
+    Transient,
+    /// A permanent failure, trying again will fail in the same way
     #[default]
-    LoaderJobStatusUnknown,
-    UserError,
+    Permanent,
+    /// Finished successfully, there is nothing to try again
+    Succeeded,
 }
 
 impl LambdaDetailStatus {
@@ -40,12 +106,55 @@ impl LambdaDetailStatus {
                     return Some(Self::MaxLoadTaskQueueSizeLimitBreached);
                 } else if message.contains("Max concurrent load limit breached") {
                     return Some(Self::MaxConcurrentLoadLimitBreached);
+                } else if message.contains("already in progress") {
+                    return Some(Self::BulkLoadInProgress);
                 }
             }
         }
         None
     }
 
+    /// Map the error code of a neptunedata service error (the name of the
+    /// exception such as `S3Exception`) to a status, see
+    /// https://docs.aws.amazon.com/neptune/latest/userguide/data-api-dp-errors.html
+    pub fn from_error_code(code: Option<&str>) -> Self {
+        match code.unwrap_or_default() {
+            "BadRequestException" => Self::BadRequest,
+            "BulkLoadIdNotFoundException" => Self::BulkLoadIdNotFound,
+            "ClientTimeoutException" => Self::ClientTimeout,
+            "ConstraintViolationException" => Self::ConstraintViolation,
+            "IllegalArgumentException" => Self::IllegalArgument,
+            "InternalFailureException" => Self::InternalFailure,
+            "InvalidArgumentException" => Self::InvalidArgument,
+            "InvalidParameterException" => Self::InvalidParameter,
+            "LoadUrlAccessDeniedException" => Self::LoadUrlAccessDenied,
+            "MissingParameterException" => Self::MissingParameter,
+            "PreconditionsFailedException" => Self::PreconditionsFailed,
+            "ReadOnlyViolationException" => Self::ReadOnlyViolation,
+            "S3Exception" => Self::S3Error,
+            "ThrottlingException" => Self::Throttled,
+            "TimeLimitExceededException" => Self::TimeLimitExceeded,
+            "TooManyRequestsException" => Self::TooManyRequests,
+            "UnsupportedOperationException" => Self::UnsupportedOperation,
+            _ => Self::LoaderJobUnexpectedError,
+        }
+    }
+
+    /// The HTTP status code that goes with this status when it is the result
+    /// of a failed request
+    pub const fn http_status_code(&self) -> u16 {
+        match self {
+            Self::BulkLoadIdNotFound => 404,
+            Self::Throttled | Self::TooManyRequests => 429,
+            Self::InternalFailure | Self::LoaderJobUnexpectedError => 500,
+            Self::Timedout |
+            Self::ClientTimeout |
+            Self::TimeLimitExceeded |
+            Self::RetryBudgetExhausted => 504,
+            _ => 400,
+        }
+    }
+
     pub fn from_loader_job_status(status: &str) -> Self {
         match status {
             "LOAD_IN_QUEUE" => Self::LoaderJobInQueue,
@@ -102,8 +211,30 @@ impl LambdaDetailStatus {
                 "Loader job failed because dependency was not satisfied."
             },
             Self::LoaderJobFailedInvalidRequest => "Loader job failed due to invalid request",
+            Self::UnsupportedRdfFormat => "Unsupported RDF file format",
+            Self::GraphMerged => "Graph merged into the target graph",
             Self::LoaderJobStatusUnknown => "Loader job status unknown",
             Self::UserError => "User error",
+            Self::BadRequest => "Bad request",
+            Self::BulkLoadInProgress => "Another bulk load is in progress",
+            Self::BulkLoadIdNotFound => "Loader job not found",
+            Self::ClientTimeout => "Client timed out",
+            Self::ConstraintViolation => "Constraint violation",
+            Self::IllegalArgument => "Illegal argument",
+            Self::InternalFailure => "Internal failure in Neptune",
+            Self::InvalidArgument => "Invalid argument",
+            Self::InvalidParameter => "Invalid parameter",
+            Self::LoadUrlAccessDenied => "Access denied to the source URL",
+            Self::MissingParameter => "Missing parameter",
+            Self::PreconditionsFailed => "Preconditions failed",
+            Self::ReadOnlyViolation => "Neptune is read-only",
+            Self::S3Error => "Could not read from S3",
+            Self::Throttled => "Request throttled",
+            Self::TimeLimitExceeded => "Time limit exceeded",
+            Self::TooManyRequests => "Too many requests",
+            Self::UnsupportedOperation => "Unsupported operation",
+            Self::RetryBudgetExhausted => "Gave up after too many attempts",
+            Self::AlreadyLoaded => "This version of the file has been loaded already",
         }
     }
 
@@ -116,10 +247,30 @@ impl LambdaDetailStatus {
 
     /// Return true if the caller should check the status of the load again
     /// later
-    pub fn is_retryable(&self) -> bool {
+    pub fn is_retryable(&self) -> bool { self.retryability() == Retryability::Pending }
+
+    /// Classify this status, note that the final statuses of a loader job
+    /// (such as [`Self::LoaderJobDataDeadlock`]) are permanent since checking
+    /// the status of that job again will not change anything.
+    pub const fn retryability(&self) -> Retryability {
         match self {
-            Self::LoaderJobInQueue | Self::LoaderJobNotStarted | Self::LoaderJobInProgress => true,
-            _ => false,
+            Self::LoaderJobInQueue | Self::LoaderJobNotStarted | Self::LoaderJobInProgress => {
+                Retryability::Pending
+            },
+            Self::Timedout |
+            Self::IOError |
+            Self::MaxLoadTaskQueueSizeLimitBreached |
+            Self::MaxConcurrentLoadLimitBreached |
+            Self::BulkLoadInProgress |
+            Self::ClientTimeout |
+            Self::InternalFailure |
+            Self::Throttled |
+            Self::TimeLimitExceeded |
+            Self::TooManyRequests => Retryability::Transient,
+            Self::LoaderJobCompleted | Self::GraphMerged | Self::AlreadyLoaded => {
+                Retryability::Succeeded
+            },
+            _ => Retryability::Permanent,
         }
     }
 
@@ -141,7 +292,9 @@ impl LambdaDetailStatus {
             LambdaDetailStatus::LoaderJobInProgress => {
                 crate::lambda::CLASS_DATAOPS_LOADING_LOAD_REQUEST.deref()
             },
-            LambdaDetailStatus::LoaderJobCompleted => {
+            LambdaDetailStatus::LoaderJobCompleted |
+            LambdaDetailStatus::GraphMerged |
+            LambdaDetailStatus::AlreadyLoaded => {
                 crate::lambda::CLASS_DATAOPS_FINISHED_LOAD_REQUEST.deref()
             },
             _ => crate::lambda::CLASS_DATAOPS_FAILED_LOAD_REQUEST.deref(),
@@ -2,15 +2,24 @@
 
 use serde::Deserialize;
 pub use {
-    s3::{S3Bucket, S3EventRecord, S3EventRecords, S3Object},
+    event_bridge::EventBridgeEvent,
+    s3::{S3Bucket, S3EventName, S3EventRecord, S3EventRecords, S3Object},
     sns::{SnsEventRecord, SnsRecord},
+    sqs::{SqsBatchItemFailure, SqsEventRecord},
 };
 
+pub mod event_bridge;
+pub mod graph_store;
 pub mod lambda;
 pub mod neptune;
+pub mod object_store;
+pub mod placeholder;
+pub mod routing;
 pub mod s3;
 pub mod sdk_config;
 pub mod sns;
+pub mod sparql;
+pub mod sqs;
 pub mod tls_connector;
 
 mod http;
@@ -0,0 +1,93 @@
+use {
+    crate::neptune::LoadRequest,
+    aws_sdk_neptunedata::{
+        error::SdkError,
+        operation::{
+            cancel_loader_job::{CancelLoaderJobError, CancelLoaderJobOutput},
+            get_loader_job_status::{GetLoaderJobStatusError, GetLoaderJobStatusOutput},
+            start_loader_job::{StartLoaderJobError, StartLoaderJobOutput},
+        },
+    },
+    aws_smithy_runtime_api::http::Response as HttpResponse,
+    std::future::Future,
+};
+
+pub type StartLoaderJobSdkError = SdkError<StartLoaderJobError, HttpResponse>;
+pub type GetLoaderJobStatusSdkError = SdkError<GetLoaderJobStatusError, HttpResponse>;
+pub type CancelLoaderJobSdkError = SdkError<CancelLoaderJobError, HttpResponse>;
+
+/// The calls to the Neptune bulk loader that the load and check lambda
+/// functions make, implemented for the real [`aws_sdk_neptunedata::Client`]
+/// and (with the `fake` feature) for the
+/// [`InMemoryBulkLoader`](crate::neptune::InMemoryBulkLoader).
+///
+/// The outputs and errors are those of the AWS SDK so that the conversions to
+/// a [`LambdaResponse`](crate::lambda::LambdaResponse) are the same for both.
+pub trait BulkLoader: Clone + Send + Sync + 'static {
+    /// Ask the bulk loader to start a loader job for the given load request
+    fn start_load(
+        &self,
+        load_request: &LoadRequest,
+    ) -> impl Future<Output = Result<StartLoaderJobOutput, StartLoaderJobSdkError>> + Send;
+
+    /// Get the status of the given loader job including the given page (which
+    /// starts at 1) of its errors
+    fn load_status(
+        &self,
+        load_id: &str,
+        errors_per_page: i32,
+        page: i32,
+    ) -> impl Future<Output = Result<GetLoaderJobStatusOutput, GetLoaderJobStatusSdkError>> + Send;
+
+    /// Cancel the given loader job, such as one that the check lambda function
+    /// gave up on
+    fn cancel_load(
+        &self,
+        load_id: &str,
+    ) -> impl Future<Output = Result<CancelLoaderJobOutput, CancelLoaderJobSdkError>> + Send;
+}
+
+impl BulkLoader for aws_sdk_neptunedata::Client {
+    fn start_load(
+        &self,
+        load_request: &LoadRequest,
+    ) -> impl Future<Output = Result<StartLoaderJobOutput, StartLoaderJobSdkError>> + Send {
+        self.start_loader_job()
+            .source(load_request.loader_source())
+            .format(load_request.format.as_str().into())
+            .iam_role_arn(&load_request.iam_role_arn)
+            .mode(load_request.mode.clone().into())
+            .s3_bucket_region(load_request.region.as_str().into())
+            .fail_on_error(load_request.fail_on_error)
+            .parallelism(load_request.parallelism.as_str().into())
+            .set_parser_configuration(Some(
+                load_request.parser_configuration.as_hash_map(),
+            ))
+            .update_single_cardinality_properties(load_request.update_single_cardinality_properties)
+            .queue_request(load_request.queue_request)
+            .set_dependencies(Some(load_request.dependencies.clone()))
+            .send()
+    }
+
+    fn load_status(
+        &self,
+        load_id: &str,
+        errors_per_page: i32,
+        page: i32,
+    ) -> impl Future<Output = Result<GetLoaderJobStatusOutput, GetLoaderJobStatusSdkError>> + Send
+    {
+        self.get_loader_job_status()
+            .load_id(load_id)
+            .errors(true)
+            .errors_per_page(errors_per_page)
+            .page(page)
+            .send()
+    }
+
+    fn cancel_load(
+        &self,
+        load_id: &str,
+    ) -> impl Future<Output = Result<CancelLoaderJobOutput, CancelLoaderJobSdkError>> + Send {
+        self.cancel_loader_job().load_id(load_id).send()
+    }
+}
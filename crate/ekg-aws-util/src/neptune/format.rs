@@ -0,0 +1,107 @@
+use aws_sdk_neptunedata::types::Format;
+
+/// The compression formats that the Neptune bulk loader can decompress by
+/// itself, see https://docs.aws.amazon.com/neptune/latest/userguide/bulk-load-tutorial-format.html#bulk-load-tutorial-format-compression
+const COMPRESSION_EXTENSIONS: [&str; 2] = ["gz", "bz2"];
+
+/// Content types that S3 assigns by default (or that tools tend to use) which
+/// say nothing about the RDF format so we have to look at the extension
+/// instead.
+const GENERIC_CONTENT_TYPES: [&str; 6] = [
+    "application/octet-stream",
+    "binary/octet-stream",
+    "text/plain",
+    "application/gzip",
+    "application/x-gzip",
+    "application/x-bzip2",
+];
+
+/// Resolve the Neptune bulk loader format for the given S3 object.
+///
+/// A specific `content_type` (as stored in the S3 object metadata) takes
+/// precedence over the extension of the key, generic content types such as
+/// `application/octet-stream` are ignored. Returns `None` if the format is not
+/// one of the RDF formats that the Neptune bulk loader supports (which
+/// excludes TriG for instance).
+pub fn resolve_format(key: &str, content_type: Option<&str>) -> Option<Format> {
+    content_type
+        .and_then(format_from_content_type)
+        .or_else(|| format_from_s3_key(key))
+}
+
+/// Derive the format from the extension of the given S3 object key, where a
+/// compression extension is skipped so that `.nt.gz` is seen as N-Triples.
+pub fn format_from_s3_key(key: &str) -> Option<Format> {
+    let file_name = key.rsplit('/').next().unwrap_or(key).to_ascii_lowercase();
+    let mut extensions: Vec<&str> = file_name.split('.').skip(1).collect();
+    let mut extension = extensions.pop()?;
+    if COMPRESSION_EXTENSIONS.contains(&extension) {
+        extension = extensions.pop()?;
+    }
+    format_from_extension(extension)
+}
+
+/// The format of the given (lowercase) file name extension
+fn format_from_extension(extension: &str) -> Option<Format> {
+    match extension {
+        "ttl" | "turtle" => Some(Format::Turtle),
+        "nt" | "ntriples" => Some(Format::Ntriples),
+        "nq" | "nquads" => Some(Format::Nquads),
+        "rdf" | "rdfxml" | "owl" => Some(Format::Rdfxml),
+        _ => None,
+    }
+}
+
+/// The given file name without its compression extension and RDF extension,
+/// if it has them, so that `data.v2.nt.gz` becomes `data.v2`. Other dots in the
+/// file name are kept.
+pub fn rdf_file_stem(file_name: &str) -> &str {
+    let stem = strip_extension(file_name, |extension| {
+        COMPRESSION_EXTENSIONS.contains(&extension)
+    });
+    strip_extension(stem, |extension| {
+        format_from_extension(extension).is_some()
+    })
+}
+
+fn strip_extension(file_name: &str, is_known: impl Fn(&str) -> bool) -> &str {
+    match file_name.rsplit_once('.') {
+        Some((stem, extension))
+            if !stem.is_empty() && is_known(extension.to_ascii_lowercase().as_str()) =>
+        {
+            stem
+        },
+        _ => file_name,
+    }
+}
+
+/// Derive the format from the given content type (MIME type), ignoring any
+/// parameters such as `charset`.
+pub fn format_from_content_type(content_type: &str) -> Option<Format> {
+    let mime_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if GENERIC_CONTENT_TYPES.contains(&mime_type.as_str()) {
+        return None;
+    }
+    match mime_type.as_str() {
+        "text/turtle" | "application/x-turtle" => Some(Format::Turtle),
+        "application/n-triples" => Some(Format::Ntriples),
+        "application/n-quads" | "text/x-nquads" => Some(Format::Nquads),
+        "application/rdf+xml" => Some(Format::Rdfxml),
+        _ => None,
+    }
+}
+
+/// Return true if the given format is one of the RDF formats, as opposed to
+/// the property graph formats (CSV, openCypher) that the Neptune bulk loader
+/// also supports.
+pub fn is_rdf_format(format: &Format) -> bool {
+    matches!(
+        format,
+        Format::Ntriples | Format::Nquads | Format::Rdfxml | Format::Turtle
+    )
+}
@@ -0,0 +1,209 @@
+use {
+    crate::{
+        neptune::rdf_file_stem,
+        routing::RoutingRule,
+        s3::s3_uri_to_iri,
+        sparql::Iri,
+        S3EventRecord,
+    },
+    ekg_error::Error,
+    ekg_identifier::EkgIdentifierContexts,
+    sha2::{Digest, Sha256},
+    std::str::FromStr,
+};
+
+/// The environment variable that holds the graph naming strategy of a
+/// pipeline: `s3-uri` (the default), `hash` or a graph IRI template such as
+/// `{graph_base}{prefix}/{stem}`.
+pub const ENV_GRAPH_NAMING: &str = "EKG_GRAPH_NAMING";
+
+/// The placeholders that a graph IRI template can use, see
+/// [`GraphNamingStrategy::Template`]
+const GRAPH_TEMPLATE_PLACEHOLDERS: [&str; 6] =
+    ["graph_base", "bucket", "key", "prefix", "stem", "hash"];
+
+/// The keys that a graph IRI template is rendered for when it is parsed, one
+/// in a folder and one at the root of the bucket, the result has to be an
+/// absolute IRI
+const SAMPLE_KEYS: [&str; 2] = ["dataset/data.v1.ttl.gz", "data.ttl"];
+const SAMPLE_GRAPH_BASE: &str = "https://kg.example.com/graph/";
+const SAMPLE_BUCKET: &str = "bucket";
+
+/// How the named graph of an S3 object is named, i.e. the `namedGraphUri` of
+/// its load request.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum GraphNamingStrategy {
+    /// The S3 URI of the object, with the characters that an IRI cannot
+    /// contain percent-encoded. Simple, but it leaks the name of the bucket
+    /// into the graph IRI and changes when the files move to another bucket.
+    #[default]
+    S3Uri,
+    /// The internal `ekg_graph_base` followed by a SHA-256 hash of the key of
+    /// the object, which does not depend on the bucket, the same as the
+    /// template `{graph_base}{hash}`.
+    Hash,
+    /// A template for the graph IRI that can use these placeholders:
+    ///
+    /// - `{graph_base}`: the internal `ekg_graph_base`
+    /// - `{bucket}`: the name of the bucket
+    /// - `{key}`: the key of the object
+    /// - `{prefix}`: the key up to its last `/` (without that `/`), for a key
+    ///   at the root of the bucket a `/` right after it is left out as well
+    /// - `{stem}`: the file name of the key without its RDF extension and
+    ///   compression extension, see [`crate::neptune::rdf_file_stem`]
+    /// - `{hash}`: the SHA-256 hash of the key, in hex
+    Template(String),
+}
+
+impl GraphNamingStrategy {
+    /// Get the graph naming strategy from the `EKG_GRAPH_NAMING` environment
+    /// variable, defaults to [`GraphNamingStrategy::S3Uri`] when not set.
+    pub fn from_env() -> Result<Self, Error> {
+        match std::env::var(ENV_GRAPH_NAMING) {
+            Ok(value) if !value.trim().is_empty() => value.parse(),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// The graph naming strategy for an S3 object that the given routing rule
+    /// (if any) applies to: the graph template of that rule if it has one and
+    /// otherwise the one of the pipeline, see
+    /// [`GraphNamingStrategy::from_env`].
+    pub fn for_routing_rule(routing_rule: Option<&RoutingRule>) -> Result<Self, Error> {
+        match routing_rule.and_then(RoutingRule::graph_naming) {
+            Some(graph_naming) => Ok(graph_naming.clone()),
+            None => Self::from_env(),
+        }
+    }
+
+    /// The IRI of the named graph of the object of the given S3 event record
+    pub fn graph_iri(
+        &self,
+        s3_event_record: &S3EventRecord,
+        identifier_contexts: &EkgIdentifierContexts,
+    ) -> Result<String, Error> {
+        let template = match self {
+            Self::S3Uri => return Ok(s3_event_record.s3_iri()),
+            Self::Hash => "{graph_base}{hash}",
+            Self::Template(template) => template.as_str(),
+        };
+        render_graph_iri(
+            template,
+            identifier_contexts
+                .internal
+                .ekg_graph_base
+                .as_base_iri()
+                .as_str(),
+            s3_event_record.s3.bucket.name.as_str(),
+            s3_event_record.s3.object.decoded_key().as_str(),
+        )
+    }
+}
+
+/// Render the given graph IRI template for the given (decoded) key
+fn render_graph_iri(
+    template: &str,
+    graph_base: &str,
+    bucket: &str,
+    key: &str,
+) -> Result<String, Error> {
+    let (prefix, file_name) = key.rsplit_once('/').unwrap_or(("", key));
+    let template = if prefix.is_empty() {
+        template.replace("{prefix}/", "{prefix}")
+    } else {
+        template.to_string()
+    };
+    let hash: String = Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    render_template(template.as_str(), &[
+        ("graph_base", graph_base),
+        ("bucket", bucket),
+        ("key", s3_uri_to_iri(key).as_str()),
+        ("prefix", s3_uri_to_iri(prefix).as_str()),
+        (
+            "stem",
+            s3_uri_to_iri(rdf_file_stem(file_name)).as_str(),
+        ),
+        ("hash", hash.as_str()),
+    ])
+}
+
+impl FromStr for GraphNamingStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains('{') {
+            // Check the placeholders of the template and that it renders an IRI
+            render_template(s, &[])?;
+            for key in SAMPLE_KEYS {
+                let graph_iri = render_graph_iri(s, SAMPLE_GRAPH_BASE, SAMPLE_BUCKET, key)?;
+                Iri::new(graph_iri.as_str()).map_err(|error| {
+                    Error::ServiceError(format!(
+                        "The graph template {} does not render an IRI for the key {}: {}",
+                        s, key, error
+                    ))
+                })?;
+                // A named graph is a document, not a part of one
+                if graph_iri.contains('#') {
+                    return Err(Error::ServiceError(format!(
+                        "The graph template {} renders an IRI with a fragment: {}",
+                        s, graph_iri
+                    )));
+                }
+            }
+            return Ok(Self::Template(s.to_string()));
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "s3-uri" => Ok(Self::S3Uri),
+            "hash" => Ok(Self::Hash),
+            other => {
+                Err(Error::ServiceError(format!(
+                    "Unknown graph naming strategy \"{}\" in {}, expected \"s3-uri\", \"hash\" or \
+                     a template",
+                    other, ENV_GRAPH_NAMING
+                )))
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for GraphNamingStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::S3Uri => write!(f, "s3-uri"),
+            Self::Hash => write!(f, "hash"),
+            Self::Template(template) => write!(f, "{}", template),
+        }
+    }
+}
+
+/// Replace the `{placeholder}`s in the given template with the given values,
+/// given no values at all it only checks the placeholders of the template
+fn render_template(template: &str, values: &[(&str, &str)]) -> Result<String, Error> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::ServiceError(format!("Unclosed placeholder in {}", template)))?;
+        let placeholder = &rest[start + 1..start + end];
+        if !GRAPH_TEMPLATE_PLACEHOLDERS.contains(&placeholder) {
+            return Err(Error::ServiceError(format!(
+                "Unknown placeholder {{{}}} in {}, expected one of {}",
+                placeholder,
+                template,
+                GRAPH_TEMPLATE_PLACEHOLDERS.join(", ")
+            )));
+        }
+        if let Some((_, value)) = values.iter().find(|(name, _)| *name == placeholder) {
+            rendered.push_str(value);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
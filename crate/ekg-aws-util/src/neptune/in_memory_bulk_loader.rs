@@ -0,0 +1,230 @@
+use {
+    crate::neptune::{
+        json_to_document,
+        BulkLoader,
+        CancelLoaderJobSdkError,
+        GetLoaderJobStatusSdkError,
+        LoadRequest,
+        LoaderJobFeedStatus,
+        LoaderJobStatus,
+        StartLoaderJobSdkError,
+    },
+    aws_sdk_neptunedata::{
+        error::ErrorMetadata,
+        operation::{
+            cancel_loader_job::{CancelLoaderJobError, CancelLoaderJobOutput},
+            get_loader_job_status::{GetLoaderJobStatusError, GetLoaderJobStatusOutput},
+            start_loader_job::{StartLoaderJobError, StartLoaderJobOutput},
+        },
+    },
+    aws_smithy_runtime_api::http::{Response as HttpResponse, StatusCode},
+    aws_smithy_types::body::SdkBody,
+    serde_json::Value,
+    std::{
+        collections::VecDeque,
+        future::Future,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// A loader job that the [`InMemoryBulkLoader`] accepted
+#[derive(Clone, Debug)]
+pub struct InMemoryLoaderJob {
+    pub load_id:   String,
+    /// The S3 URI that the job loads, see [`LoadRequest::loader_source`]
+    pub source:    String,
+    /// The load request as it was given to [`BulkLoader::start_load`]
+    pub request:   Value,
+    /// The statuses that the job goes through, one per status check, the last
+    /// one is repeated. Cancelling the job ends it with
+    /// `LOAD_CANCELLED_BY_USER`.
+    pub lifecycle: Vec<LoaderJobStatus>,
+    /// The number of times the status of the job was checked
+    pub checks:    usize,
+}
+
+impl InMemoryLoaderJob {
+    /// The status that the job currently has
+    pub fn status(&self) -> &LoaderJobStatus {
+        &self.lifecycle[self.checks.min(self.lifecycle.len() - 1)]
+    }
+}
+
+#[derive(Default)]
+struct State {
+    lifecycle:    Vec<LoaderJobStatus>,
+    start_errors: VecDeque<StartLoaderJobError>,
+    jobs:         Vec<InMemoryLoaderJob>,
+}
+
+/// A [`BulkLoader`] that keeps its loader jobs in memory, for unit tests of the
+/// lambda functions that need no network at all.
+///
+/// Every job goes through the same lifecycle (by default `LOAD_IN_QUEUE`,
+/// `LOAD_IN_PROGRESS`, `LOAD_COMPLETED`), advancing one status per status
+/// check. Clones share their jobs.
+#[derive(Clone)]
+pub struct InMemoryBulkLoader {
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for InMemoryBulkLoader {
+    fn default() -> Self { Self::new() }
+}
+
+impl InMemoryBulkLoader {
+    pub fn new() -> Self {
+        Self { state: Arc::new(Mutex::new(State::default())) }.with_statuses([
+            "LOAD_IN_QUEUE",
+            "LOAD_IN_PROGRESS",
+            "LOAD_COMPLETED",
+        ])
+    }
+
+    /// The lifecycle of all jobs started from now on
+    pub fn with_lifecycle(self, lifecycle: impl IntoIterator<Item = LoaderJobStatus>) -> Self {
+        self.state.lock().unwrap().lifecycle = lifecycle.into_iter().collect();
+        self
+    }
+
+    /// The lifecycle of all jobs started from now on, as plain statuses such
+    /// as `LOAD_FAILED`
+    pub fn with_statuses<'a>(self, statuses: impl IntoIterator<Item = &'a str>) -> Self {
+        self.with_lifecycle(statuses.into_iter().map(plain_status))
+    }
+
+    /// Fail the next attempt to start a loader job with the given error, such
+    /// as a `BadRequestException` saying that the load queue is full
+    pub fn push_start_error(&self, error: StartLoaderJobError) {
+        self.state.lock().unwrap().start_errors.push_back(error);
+    }
+
+    /// All accepted jobs, in the order in which they were started
+    pub fn jobs(&self) -> Vec<InMemoryLoaderJob> { self.state.lock().unwrap().jobs.clone() }
+
+    fn start(
+        &self,
+        load_request: &LoadRequest,
+    ) -> Result<StartLoaderJobOutput, StartLoaderJobSdkError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(error) = state.start_errors.pop_front() {
+            return Err(StartLoaderJobSdkError::service_error(
+                error,
+                raw_response(400),
+            ));
+        }
+        if state.lifecycle.is_empty() {
+            return Err(StartLoaderJobSdkError::construction_failure(
+                "InMemoryBulkLoader has an empty lifecycle",
+            ));
+        }
+        let request = serde_json::to_value(load_request)
+            .map_err(StartLoaderJobSdkError::construction_failure)?;
+        let load_id = format!("in-memory-{}", state.jobs.len() + 1);
+        let lifecycle = state.lifecycle.clone();
+        state.jobs.push(InMemoryLoaderJob {
+            load_id: load_id.clone(),
+            source: load_request.loader_source().to_string(),
+            request,
+            lifecycle,
+            checks: 0,
+        });
+        StartLoaderJobOutput::builder()
+            .status("200 OK")
+            .payload("loadId", load_id)
+            .build()
+            .map_err(StartLoaderJobSdkError::construction_failure)
+    }
+
+    fn status(
+        &self,
+        load_id: &str,
+        page: i32,
+    ) -> Result<GetLoaderJobStatusOutput, GetLoaderJobStatusSdkError> {
+        let mut state = self.state.lock().unwrap();
+        let Some(job) = state.jobs.iter_mut().find(|job| job.load_id == load_id) else {
+            return Err(GetLoaderJobStatusSdkError::service_error(
+                GetLoaderJobStatusError::generic(
+                    ErrorMetadata::builder()
+                        .code("BulkLoadIdNotFoundException")
+                        .message(format!("Load id {} not found", load_id))
+                        .build(),
+                ),
+                raw_response(404),
+            ));
+        };
+        let mut status = job.status().clone();
+        // All errors are on the first page
+        if page > 1 {
+            status.errors = None;
+        }
+        job.checks += 1;
+        let payload = serde_json::to_value(&status)
+            .map_err(GetLoaderJobStatusSdkError::construction_failure)?;
+        GetLoaderJobStatusOutput::builder()
+            .status("200 OK")
+            .payload(json_to_document(&payload))
+            .build()
+            .map_err(GetLoaderJobStatusSdkError::construction_failure)
+    }
+
+    fn cancel(&self, load_id: &str) -> Result<CancelLoaderJobOutput, CancelLoaderJobSdkError> {
+        let mut state = self.state.lock().unwrap();
+        let Some(job) = state.jobs.iter_mut().find(|job| job.load_id == load_id) else {
+            return Err(CancelLoaderJobSdkError::service_error(
+                CancelLoaderJobError::generic(
+                    ErrorMetadata::builder()
+                        .code("BulkLoadIdNotFoundException")
+                        .message(format!("Load id {} not found", load_id))
+                        .build(),
+                ),
+                raw_response(404),
+            ));
+        };
+        // The next status check returns the cancelled status
+        let checks = job.checks;
+        job.lifecycle.truncate(checks);
+        job.lifecycle.push(plain_status("LOAD_CANCELLED_BY_USER"));
+        Ok(CancelLoaderJobOutput::builder().status("200 OK").build())
+    }
+}
+
+impl BulkLoader for InMemoryBulkLoader {
+    fn start_load(
+        &self,
+        load_request: &LoadRequest,
+    ) -> impl Future<Output = Result<StartLoaderJobOutput, StartLoaderJobSdkError>> + Send {
+        std::future::ready(self.start(load_request))
+    }
+
+    fn load_status(
+        &self,
+        load_id: &str,
+        _errors_per_page: i32,
+        page: i32,
+    ) -> impl Future<Output = Result<GetLoaderJobStatusOutput, GetLoaderJobStatusSdkError>> + Send
+    {
+        std::future::ready(self.status(load_id, page))
+    }
+
+    fn cancel_load(
+        &self,
+        load_id: &str,
+    ) -> impl Future<Output = Result<CancelLoaderJobOutput, CancelLoaderJobSdkError>> + Send {
+        std::future::ready(self.cancel(load_id))
+    }
+}
+
+fn plain_status(status: &str) -> LoaderJobStatus {
+    LoaderJobStatus {
+        overall_status: LoaderJobFeedStatus { status: status.to_string(), ..Default::default() },
+        ..Default::default()
+    }
+}
+
+fn raw_response(status: u16) -> HttpResponse {
+    HttpResponse::new(
+        StatusCode::try_from(status).unwrap(),
+        SdkBody::empty(),
+    )
+}
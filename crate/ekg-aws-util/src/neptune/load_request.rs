@@ -1,5 +1,7 @@
 use {
     crate::{
+        neptune::{GraphNamingStrategy, LoadStrategy},
+        routing::RoutingRule,
         serde_util::{deserialize_format_from_str, serialize_format},
         Region,
         S3EventRecord,
@@ -13,6 +15,7 @@ use {
         serde_util::{deserialize_bool_as_uppercase, serialize_bool_as_uppercase},
     },
     serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
 };
 
 /// AWS Neptune Load Request
@@ -20,10 +23,11 @@ use {
 ///
 /// We do not support a source string that is a list of S3 objects because we
 /// want to load each individual file into its own named graph initially, where
-/// the IRI of the named graph is equal to the given S3 URL. Then after the load
-/// we can merge the named graphs into a single graph and record the original S3
-/// URL as the source of the triples for proper lineage/provenance purposes.
-#[derive(Deserialize, Serialize, Debug)]
+/// the IRI of the named graph follows from the [`GraphNamingStrategy`]. Then
+/// after the load we can merge the named graphs into a single graph and record
+/// the original S3 URL as the source of the triples for proper
+/// lineage/provenance purposes.
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LoadRequest {
     pub source:                               S3URI,
@@ -45,39 +49,141 @@ pub struct LoadRequest {
     #[serde(deserialize_with = "deserialize_bool_as_uppercase")]
     pub queue_request:                        bool,
     pub dependencies:                         Vec<String>,
+    /// Not part of the Neptune load request, see [`LoadStrategy`]
+    #[serde(default)]
+    pub load_strategy:                        LoadStrategy,
+    /// The named graph that should end up holding the triples of the source.
+    /// Only set when the load goes via a staging graph (see
+    /// [`LoadStrategy::Replace`]), in which case the `namedGraphUri` of the
+    /// parser configuration is that staging graph.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_graph_uri:                     Option<String>,
+    /// Not part of the Neptune load request: the version ID (or, if the bucket
+    /// isn't versioning-enabled, the eTag) of the source, so that the load
+    /// lambda function can skip a version that has been loaded already.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_version:                       Option<String>,
+    /// Not part of the Neptune load request: the copy of the source with its
+    /// placeholder IRIs rewritten that the Neptune bulk loader loads instead of
+    /// the source itself, see [`crate::placeholder::stage`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staged_source:                        Option<S3URI>,
 }
 
 impl LoadRequest {
+    /// Create a load request for the S3 object in the given S3 event record,
+    /// where the `format` is normally the result of
+    /// [`crate::neptune::resolve_format`] (unless the routing rule overrides
+    /// it). The routing rule, if any, decides the named graph (otherwise the
+    /// [`GraphNamingStrategy`] of the pipeline does) and the base URI, see
+    /// [`crate::routing::RoutingTable::route`].
     pub fn from_s3_event_record(
         s3_event_record: &S3EventRecord,
+        format: aws_sdk_neptunedata::types::Format,
         identifier_contexts: &EkgIdentifierContexts,
+        routing_rule: Option<&RoutingRule>,
     ) -> Result<Self, Error> {
-        let s3_uri = format!(
-            "s3://{}/{}",
-            s3_event_record.s3.bucket.name,
-            s3_event_record.s3.object.key.clone()
-        );
+        // The key as it is in the bucket for the loader, and an IRI for the graph
+        let s3_uri = s3_event_record.s3_uri();
+        let graph_iri = GraphNamingStrategy::for_routing_rule(routing_rule)?
+            .graph_iri(s3_event_record, identifier_contexts)?;
+        let base_uri = routing_rule
+            .and_then(|routing_rule| routing_rule.base_uri.clone())
+            .unwrap_or_else(|| identifier_contexts.internal.ekg_id_base.clone());
+        let load_strategy = LoadStrategy::from_env()?;
+        let (named_graph_uri, target_graph_uri) = match load_strategy {
+            LoadStrategy::Append => (graph_iri, None),
+            LoadStrategy::Replace => {
+                (
+                    staging_graph_uri(
+                        graph_iri.as_str(),
+                        s3_event_record,
+                        identifier_contexts,
+                    ),
+                    Some(graph_iri),
+                )
+            },
+        };
         Ok(Self {
-            source:                               s3_uri.clone(),
-            format:                               aws_sdk_neptunedata::types::Format::Turtle,
-            iam_role_arn:                         mandatory_env_var(
-                "AWS_NEPTUNE_LOAD_IAM_ROLE_ARN",
-                None,
-            )?,
-            mode:                                 Mode::NEW,
-            region:                               mandatory_env_var("AWS_REGION", None)?,
-            fail_on_error:                        true,
-            parallelism:                          "OVERSUBSCRIBE".to_string(),
-            parser_configuration:                 ParserConfiguration {
-                base_uri:            identifier_contexts.internal.ekg_id_base.clone(),
-                named_graph_uri:     s3_uri,
+            source: s3_uri,
+            format,
+            iam_role_arn: mandatory_env_var("AWS_NEPTUNE_LOAD_IAM_ROLE_ARN", None)?,
+            mode: Mode::NEW,
+            region: mandatory_env_var("AWS_REGION", None)?,
+            fail_on_error: true,
+            parallelism: "OVERSUBSCRIBE".to_string(),
+            parser_configuration: ParserConfiguration {
+                base_uri,
+                named_graph_uri,
                 allow_empty_strings: false,
             },
             update_single_cardinality_properties: false,
-            queue_request:                        true,
-            dependencies:                         vec![],
+            queue_request: true,
+            dependencies: vec![],
+            load_strategy,
+            target_graph_uri,
+            source_version: Some(s3_event_record.s3.object.version())
+                .filter(|version| !version.is_empty())
+                .map(str::to_string),
+            staged_source: None,
         })
     }
+
+    /// The [`LoadRequest::source`] as an IRI, the IRI of its `dataops:Dataset`
+    pub fn source_iri(&self) -> String { crate::s3::s3_uri_to_iri(self.source.as_str()) }
+
+    /// The S3 URI that the Neptune bulk loader reads: the staged copy of the
+    /// source if there is one, otherwise the source itself
+    pub fn loader_source(&self) -> &str {
+        self.staged_source
+            .as_deref()
+            .unwrap_or(self.source.as_str())
+    }
+
+    /// The named graph that holds the triples of the source once the load
+    /// request has been processed completely.
+    pub fn target_graph_uri(&self) -> &str {
+        self.target_graph_uri
+            .as_deref()
+            .unwrap_or(self.parser_configuration.named_graph_uri.as_str())
+    }
+
+    /// The staging graph that the Neptune bulk loader loads into, if any.
+    pub fn staging_graph_uri(&self) -> Option<&str> {
+        self.target_graph_uri
+            .as_ref()
+            .map(|_| self.parser_configuration.named_graph_uri.as_str())
+    }
+}
+
+/// The staging graph of a given version of an S3 object: a graph under the
+/// internal `ekg_graph_base` named after a hash of its graph IRI (so that it
+/// does not matter what that IRI looks like, or whether it has a fragment
+/// already) and the sequencer of the S3 event, which is unique for every PUT
+/// (and DELETE) of a given key, see
+/// https://docs.aws.amazon.com/AmazonS3/latest/userguide/notification-content-structure.html
+fn staging_graph_uri(
+    graph_iri: &str,
+    s3_event_record: &S3EventRecord,
+    identifier_contexts: &EkgIdentifierContexts,
+) -> String {
+    let hash: String = Sha256::digest(graph_iri.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!(
+        "{}staging/{}/{}",
+        identifier_contexts.internal.ekg_graph_base.as_base_iri(),
+        hash,
+        crate::s3::s3_uri_to_iri(
+            s3_event_record
+                .s3
+                .object
+                .sequencer
+                .as_deref()
+                .unwrap_or(s3_event_record.s3.object.version())
+        )
+    )
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -99,11 +205,12 @@ impl Into<aws_sdk_neptunedata::types::Mode> for Mode {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ParserConfiguration {
     pub base_uri:            ABoxNamespaceIRI,
     pub named_graph_uri:     String,
+    #[serde(default)]
     #[serde(serialize_with = "serialize_bool_as_uppercase")]
     #[serde(deserialize_with = "deserialize_bool_as_uppercase")]
     pub allow_empty_strings: bool,
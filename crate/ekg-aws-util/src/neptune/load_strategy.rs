@@ -0,0 +1,65 @@
+use {
+    ekg_error::Error,
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
+
+/// The environment variable that holds the load strategy of a pipeline,
+/// either `append` (the default) or `replace`.
+pub const ENV_LOAD_STRATEGY: &str = "EKG_LOAD_STRATEGY";
+
+/// What happens with the triples that are already in the named graph of an S3
+/// object when a new version of that object is uploaded.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadStrategy {
+    /// Load the new version into the named graph as is, so the named graph
+    /// becomes the union of every version of the file that was ever uploaded.
+    #[default]
+    Append,
+    /// Load the new version into a staging graph first and, once the load
+    /// job completed, swap it into the named graph in a single SPARQL update
+    /// so that readers never see a half-empty graph.
+    Replace,
+}
+
+impl LoadStrategy {
+    /// Get the load strategy from the `EKG_LOAD_STRATEGY` environment
+    /// variable, defaults to [`LoadStrategy::Append`] when not set.
+    pub fn from_env() -> Result<Self, Error> {
+        match std::env::var(ENV_LOAD_STRATEGY) {
+            Ok(value) if !value.trim().is_empty() => value.parse(),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Append => "append",
+            Self::Replace => "replace",
+        }
+    }
+}
+
+impl FromStr for LoadStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "append" => Ok(Self::Append),
+            "replace" => Ok(Self::Replace),
+            other => {
+                Err(Error::ServiceError(format!(
+                    "Unknown load strategy \"{}\" in {}, expected \"append\" or \"replace\"",
+                    other, ENV_LOAD_STRATEGY
+                )))
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for LoadStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -0,0 +1,196 @@
+use {
+    crate::lambda::LambdaDetailStatus,
+    aws_smithy_types::{Document, Number},
+    chrono::{DateTime, SecondsFormat, Utc},
+    ekg_error::Error,
+    serde::{Deserialize, Serialize},
+    serde_json::Value,
+    std::collections::HashMap,
+};
+
+/// The payload of the response of the Neptune loader's Get-Status API.
+/// See https://docs.aws.amazon.com/neptune/latest/userguide/load-api-reference-status-response.html
+///
+/// The AWS SDK only gives us this payload as an untyped [`Document`], use
+/// `LoaderJobStatus::try_from(output.payload())` to convert it.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoaderJobStatus {
+    /// The number of feeds (files) per status, for instance
+    /// `[{"LOAD_COMPLETED": 1}]`
+    #[serde(default)]
+    pub feed_count:     Vec<HashMap<String, u64>>,
+    pub overall_status: LoaderJobFeedStatus,
+    /// Only present if one or more feeds failed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_feeds:   Vec<LoaderJobFeedStatus>,
+    /// Only present if the status was requested with `errors=true` and there
+    /// were errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors:         Option<LoaderJobErrors>,
+}
+
+/// The `overallStatus` or an entry of the `failedFeeds` of a loader job,
+/// Neptune leaves out the counters that do not apply (yet), so they default to
+/// zero.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LoaderJobFeedStatus {
+    pub full_uri:                 String,
+    pub run_number:               u64,
+    pub retry_number:             u64,
+    /// The loader job status, such as `LOAD_IN_PROGRESS` or `LOAD_COMPLETED`
+    pub status:                   String,
+    /// Seconds spent on the load so far
+    pub total_time_spent:         u64,
+    /// Epoch seconds at which the load started
+    pub start_time:               u64,
+    pub total_records:            u64,
+    pub total_duplicates:         u64,
+    pub parsing_errors:           u64,
+    pub datatype_mismatch_errors: u64,
+    pub insert_errors:            u64,
+}
+
+/// One page of the errors of a loader job, see the `page` and `errorsPerPage`
+/// parameters of the Get-Status API.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LoaderJobErrors {
+    pub start_index: u64,
+    pub end_index:   u64,
+    pub load_id:     String,
+    pub error_logs:  Vec<LoaderJobErrorLog>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LoaderJobErrorLog {
+    pub error_code:    String,
+    pub error_message: String,
+    pub file_name:     String,
+    pub record_num:    u64,
+}
+
+impl LoaderJobStatus {
+    /// The status of the load as a whole
+    pub fn detail_status(&self) -> LambdaDetailStatus { self.overall_status.detail_status() }
+}
+
+impl LoaderJobFeedStatus {
+    /// Return true if the feed has finished, successfully or not. Only the
+    /// terminal statuses count, a status that Neptune may add later does not:
+    /// its load might still be going.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status.as_str(),
+            "LOAD_COMPLETED" |
+                "LOAD_CANCELLED_BY_USER" |
+                "LOAD_CANCELLED_DUE_TO_ERRORS" |
+                "LOAD_UNEXPECTED_ERROR" |
+                "LOAD_FAILED" |
+                "LOAD_S3_READ_ERROR" |
+                "LOAD_S3_ACCESS_DENIED_ERROR" |
+                "LOAD_COMMITTED_W_WRITE_CONFLICTS" |
+                "LOAD_DATA_DEADLOCK" |
+                "LOAD_DATA_FAILED_DUE_TO_FEED_MODIFIED_OR_DELETED" |
+                "LOAD_FAILED_BECAUSE_DEPENDENCY_NOT_SATISFIED" |
+                "LOAD_FAILED_INVALID_REQUEST"
+        )
+    }
+
+    pub fn detail_status(&self) -> LambdaDetailStatus {
+        LambdaDetailStatus::from_loader_job_status(self.status.as_str())
+    }
+
+    /// The start time, if the load has started at all
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        if self.start_time == 0 {
+            return None;
+        }
+        DateTime::from_timestamp(self.start_time as i64, 0)
+    }
+
+    /// Neptune does not report when the load finished, but it does report how
+    /// many seconds were spent on it since it started.
+    pub fn finished_at(&self) -> Option<DateTime<Utc>> {
+        self.started_at()?;
+        DateTime::from_timestamp(
+            (self.start_time + self.total_time_spent) as i64,
+            0,
+        )
+    }
+
+    /// The start time as an `xsd:dateTime` lexical value
+    pub fn started_at_xsd(&self) -> Option<String> {
+        self.started_at()
+            .map(|started_at| started_at.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    /// The finish time as an `xsd:dateTime` lexical value
+    pub fn finished_at_xsd(&self) -> Option<String> {
+        self.finished_at()
+            .map(|finished_at| finished_at.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    /// The duration as an `xsd:duration` lexical value such as `PT42S`
+    pub fn duration_xsd(&self) -> String { format!("PT{}S", self.total_time_spent) }
+}
+
+impl TryFrom<&Document> for LoaderJobStatus {
+    type Error = Error;
+
+    fn try_from(document: &Document) -> Result<Self, Self::Error> {
+        Ok(serde_json::from_value(document_to_json(
+            document,
+        ))?)
+    }
+}
+
+/// Convert an AWS SDK [`Document`] into the equivalent JSON value, numbers
+/// keep their integer-ness so that they deserialize into integer fields.
+pub fn document_to_json(document: &Document) -> Value {
+    match document {
+        Document::Object(map) => {
+            Value::Object(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), document_to_json(value)))
+                    .collect(),
+            )
+        },
+        Document::Array(values) => Value::Array(values.iter().map(document_to_json).collect()),
+        Document::Number(Number::PosInt(number)) => Value::from(*number),
+        Document::Number(Number::NegInt(number)) => Value::from(*number),
+        Document::Number(Number::Float(number)) => Value::from(*number),
+        Document::String(string) => Value::String(string.clone()),
+        Document::Bool(boolean) => Value::Bool(*boolean),
+        Document::Null => Value::Null,
+    }
+}
+
+/// Convert a JSON value into the equivalent AWS SDK [`Document`], the inverse
+/// of [`document_to_json`].
+pub fn json_to_document(value: &Value) -> Document {
+    match value {
+        Value::Object(map) => {
+            Document::Object(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), json_to_document(value)))
+                    .collect(),
+            )
+        },
+        Value::Array(values) => Document::Array(values.iter().map(json_to_document).collect()),
+        Value::Number(number) => {
+            if let Some(number) = number.as_u64() {
+                Document::Number(Number::PosInt(number))
+            } else if let Some(number) = number.as_i64() {
+                Document::Number(Number::NegInt(number))
+            } else {
+                Document::Number(Number::Float(number.as_f64().unwrap_or_default()))
+            }
+        },
+        Value::String(string) => Document::String(string.clone()),
+        Value::Bool(boolean) => Document::Bool(*boolean),
+        Value::Null => Document::Null,
+    }
+}
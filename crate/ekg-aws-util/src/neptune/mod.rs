@@ -1,7 +1,39 @@
+#[cfg(feature = "fake")]
+pub use in_memory_bulk_loader::{InMemoryBulkLoader, InMemoryLoaderJob};
 pub use {
+    bulk_loader::{
+        BulkLoader,
+        CancelLoaderJobSdkError,
+        GetLoaderJobStatusSdkError,
+        StartLoaderJobSdkError,
+    },
+    format::{
+        format_from_content_type,
+        format_from_s3_key,
+        is_rdf_format,
+        rdf_file_stem,
+        resolve_format,
+    },
+    graph_naming::{GraphNamingStrategy, ENV_GRAPH_NAMING},
     load_request::LoadRequest,
+    load_strategy::{LoadStrategy, ENV_LOAD_STRATEGY},
+    loader_job_status::{
+        document_to_json,
+        json_to_document,
+        LoaderJobErrorLog,
+        LoaderJobErrors,
+        LoaderJobFeedStatus,
+        LoaderJobStatus,
+    },
     neptune_data_config::{get_neptunedata_client, get_neptunedata_client_config},
 };
 
+mod bulk_loader;
+mod format;
+mod graph_naming;
+#[cfg(feature = "fake")]
+mod in_memory_bulk_loader;
 mod load_request;
+mod load_strategy;
+mod loader_job_status;
 mod neptune_data_config;
@@ -0,0 +1,458 @@
+//! The S3 side of the lambda functions: they read objects from and write
+//! objects to S3 via the [`aws_sdk_s3::Client`] or, in unit tests, via an
+//! `InMemoryObjectStore` (with the `fake` feature).
+#[cfg(feature = "fake")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use {
+    aws_sdk_s3::{
+        operation::head_object::HeadObjectOutput,
+        primitives::ByteStream,
+        types::{CompletedMultipartUpload, CompletedPart},
+    },
+    ekg_error::Error,
+    std::future::Future,
+};
+
+pub trait ObjectStore: Clone + Send + Sync + 'static {
+    /// Get the content of the given object, as a stream of chunks
+    fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<ByteStream, Error>> + Send;
+
+    /// Whether the given object exists, i.e. it has a current version
+    fn object_exists(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<bool, Error>> + Send;
+
+    /// The content type of the given object, if it exists and has one
+    fn content_type(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<String>, Error>> + Send;
+
+    /// Put the given content into the given object in one go
+    fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Start a multipart upload of the given object, returns its upload ID
+    fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<String, Error>> + Send;
+
+    /// Upload the given part (numbered from 1) of a multipart upload, returns
+    /// the eTag of the part
+    fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<String, Error>> + Send;
+
+    /// Complete a multipart upload with the given parts (their number and
+    /// eTag), in order
+    fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, String)>,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Abort a multipart upload, dropping the parts that were uploaded
+    fn abort_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+fn s3_error(action: &str, bucket: &str, key: &str, error: impl std::fmt::Display) -> Error {
+    Error::ServiceError(format!(
+        "Could not {} s3://{}/{}: {}",
+        action, bucket, key, error
+    ))
+}
+
+/// The metadata of the given object, `None` if it does not exist
+async fn head_object(
+    client: &aws_sdk_s3::Client,
+    bucket: &str,
+    key: &str,
+) -> Result<Option<HeadObjectOutput>, Error> {
+    match client.head_object().bucket(bucket).key(key).send().await {
+        Ok(output) => Ok(Some(output)),
+        Err(error)
+            if error
+                .as_service_error()
+                .is_some_and(|error| error.is_not_found()) =>
+        {
+            Ok(None)
+        },
+        Err(error) => {
+            Err(s3_error(
+                "get the metadata of",
+                bucket,
+                key,
+                error,
+            ))
+        },
+    }
+}
+
+impl ObjectStore for aws_sdk_s3::Client {
+    fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<ByteStream, Error>> + Send {
+        async move {
+            self.get_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await
+                .map(|output| output.body)
+                .map_err(|error| s3_error("get", bucket, key, error))
+        }
+    }
+
+    fn object_exists(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<bool, Error>> + Send {
+        async move {
+            head_object(self, bucket, key)
+                .await
+                .map(|output| output.is_some())
+        }
+    }
+
+    fn content_type(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<String>, Error>> + Send {
+        async move {
+            head_object(self, bucket, key)
+                .await
+                .map(|output| output.and_then(|output| output.content_type))
+        }
+    }
+
+    fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        async move {
+            self.put_object()
+                .bucket(bucket)
+                .key(key)
+                .body(ByteStream::from(body))
+                .send()
+                .await
+                .map(|_| ())
+                .map_err(|error| s3_error("put", bucket, key, error))
+        }
+    }
+
+    fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<String, Error>> + Send {
+        async move {
+            let output = self
+                .create_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await
+                .map_err(|error| s3_error("start uploading", bucket, key, error))?;
+            output
+                .upload_id()
+                .map(str::to_string)
+                .ok_or_else(|| s3_error("start uploading", bucket, key, "no upload ID"))
+        }
+    }
+
+    fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<String, Error>> + Send {
+        async move {
+            let output = self
+                .upload_part()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(body))
+                .send()
+                .await
+                .map_err(|error| s3_error("upload a part of", bucket, key, error))?;
+            output
+                .e_tag()
+                .map(str::to_string)
+                .ok_or_else(|| s3_error("upload a part of", bucket, key, "no eTag"))
+        }
+    }
+
+    fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, String)>,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        async move {
+            let parts = parts
+                .into_iter()
+                .map(|(part_number, e_tag)| {
+                    CompletedPart::builder()
+                        .part_number(part_number)
+                        .e_tag(e_tag)
+                        .build()
+                })
+                .collect();
+            self.complete_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(parts))
+                        .build(),
+                )
+                .send()
+                .await
+                .map(|_| ())
+                .map_err(|error| s3_error("complete the upload of", bucket, key, error))
+        }
+    }
+
+    fn abort_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        async move {
+            self.abort_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+                .await
+                .map(|_| ())
+                .map_err(|error| s3_error("abort the upload of", bucket, key, error))
+        }
+    }
+}
+
+#[cfg(feature = "fake")]
+#[derive(Default)]
+struct State {
+    objects:       HashMap<(String, String), Vec<u8>>,
+    content_types: HashMap<(String, String), String>,
+    uploads:       HashMap<String, Vec<(i32, Vec<u8>)>>,
+}
+
+/// An [`ObjectStore`] that keeps its objects in memory, for unit tests of the
+/// lambda functions that need no network at all. Clones share their objects.
+#[cfg(feature = "fake")]
+#[derive(Clone, Default)]
+pub struct InMemoryObjectStore {
+    state: Arc<Mutex<State>>,
+}
+
+#[cfg(feature = "fake")]
+impl InMemoryObjectStore {
+    pub fn new() -> Self { Self::default() }
+
+    /// Add the given object
+    pub fn with_object(self, bucket: &str, key: &str, content: impl Into<Vec<u8>>) -> Self {
+        self.state.lock().unwrap().objects.insert(
+            (bucket.to_string(), key.to_string()),
+            content.into(),
+        );
+        self
+    }
+
+    /// Set the content type of the given object
+    pub fn with_content_type(self, bucket: &str, key: &str, content_type: &str) -> Self {
+        self.state.lock().unwrap().content_types.insert(
+            (bucket.to_string(), key.to_string()),
+            content_type.to_string(),
+        );
+        self
+    }
+
+    /// The content of the given object, if it exists
+    pub fn object(&self, bucket: &str, key: &str) -> Option<Vec<u8>> {
+        self.state
+            .lock()
+            .unwrap()
+            .objects
+            .get(&(bucket.to_string(), key.to_string()))
+            .cloned()
+    }
+
+    /// The number of multipart uploads that have been started but neither
+    /// completed nor aborted
+    pub fn pending_uploads(&self) -> usize { self.state.lock().unwrap().uploads.len() }
+
+    fn upload(&self, upload_id: &str, bucket: &str, key: &str) -> Result<(), Error> {
+        if self.state.lock().unwrap().uploads.contains_key(upload_id) {
+            Ok(())
+        } else {
+            Err(s3_error(
+                "find the upload of",
+                bucket,
+                key,
+                upload_id,
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "fake")]
+impl ObjectStore for InMemoryObjectStore {
+    fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<ByteStream, Error>> + Send {
+        std::future::ready(
+            self.object(bucket, key)
+                .map(ByteStream::from)
+                .ok_or_else(|| s3_error("get", bucket, key, "no such key")),
+        )
+    }
+
+    fn object_exists(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<bool, Error>> + Send {
+        std::future::ready(Ok(self.object(bucket, key).is_some()))
+    }
+
+    fn content_type(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<String>, Error>> + Send {
+        let object = (bucket.to_string(), key.to_string());
+        let state = self.state.lock().unwrap();
+        std::future::ready(Ok(state
+            .objects
+            .contains_key(&object)
+            .then(|| state.content_types.get(&object).cloned())
+            .flatten()))
+    }
+
+    fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        self.state
+            .lock()
+            .unwrap()
+            .objects
+            .insert((bucket.to_string(), key.to_string()), body);
+        std::future::ready(Ok(()))
+    }
+
+    fn create_multipart_upload(
+        &self,
+        _bucket: &str,
+        _key: &str,
+    ) -> impl Future<Output = Result<String, Error>> + Send {
+        let mut state = self.state.lock().unwrap();
+        let upload_id = format!("in-memory-upload-{}", state.uploads.len() + 1);
+        state.uploads.insert(upload_id.clone(), Vec::new());
+        std::future::ready(Ok(upload_id))
+    }
+
+    fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<String, Error>> + Send {
+        std::future::ready(self.upload(upload_id, bucket, key).map(|_| {
+            let mut state = self.state.lock().unwrap();
+            state
+                .uploads
+                .get_mut(upload_id)
+                .unwrap()
+                .push((part_number, body));
+            format!("{}-{}", upload_id, part_number)
+        }))
+    }
+
+    fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, String)>,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        std::future::ready(self.upload(upload_id, bucket, key).map(|_| {
+            let mut state = self.state.lock().unwrap();
+            let uploaded = state.uploads.remove(upload_id).unwrap();
+            let content = parts
+                .iter()
+                .filter_map(|(part_number, _)| {
+                    uploaded
+                        .iter()
+                        .find(|(uploaded_part_number, _)| uploaded_part_number == part_number)
+                })
+                .flat_map(|(_, body)| body.iter().copied())
+                .collect();
+            state
+                .objects
+                .insert((bucket.to_string(), key.to_string()), content);
+        }))
+    }
+
+    fn abort_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        std::future::ready(self.upload(upload_id, bucket, key).map(|_| {
+            self.state.lock().unwrap().uploads.remove(upload_id);
+        }))
+    }
+}
@@ -0,0 +1,465 @@
+//! Rewrite the placeholder IRIs in RDF files before they are loaded.
+//!
+//! Authors write their RDF files against placeholder bases (by default
+//! `https://placeholder.kg`, `https://placeholder.kg/id` and
+//! `https://placeholder.kg/graph`) so that the same files can be loaded into
+//! every environment. Before the Neptune bulk loader gets such a file, the load
+//! lambda function streams it from S3, replaces the placeholders with the
+//! internal bases of the environment (`EKG_BASE_INTERNAL`,
+//! `EKG_ID_BASE_INTERNAL` and `EKG_GRAPH_BASE_INTERNAL`) and writes the result
+//! to the staging prefix of the bucket, see [`stage`]. The rewriting is purely
+//! textual, so a placeholder in a literal is replaced as well.
+use {
+    crate::{object_store::ObjectStore, s3::parse_s3_uri},
+    ekg_error::Error,
+    std::{
+        fmt::{Display, Formatter},
+        io::Write,
+    },
+};
+
+/// The environment variable that holds the placeholder of `EKG_BASE_INTERNAL`
+pub const ENV_BASE_PLACEHOLDER: &str = "EKG_BASE_PLACEHOLDER";
+/// The environment variable that holds the placeholder of
+/// `EKG_ID_BASE_INTERNAL`
+pub const ENV_ID_BASE_PLACEHOLDER: &str = "EKG_ID_BASE_PLACEHOLDER";
+/// The environment variable that holds the placeholder of
+/// `EKG_GRAPH_BASE_INTERNAL`
+pub const ENV_GRAPH_BASE_PLACEHOLDER: &str = "EKG_GRAPH_BASE_PLACEHOLDER";
+/// The environment variable that holds the key prefix that the rewritten
+/// files are written to, in the same bucket as the originals
+pub const ENV_STAGING_PREFIX: &str = "EKG_STAGING_PREFIX";
+/// The staging prefix when `EKG_STAGING_PREFIX` is not set
+pub const DEFAULT_STAGING_PREFIX: &str = "ekg-staging/";
+
+/// The rewritten file is uploaded in parts of this size (S3 requires at least
+/// 5 MiB for every part but the last)
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Replaces placeholder bases with the bases of the environment
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlaceholderRewriter {
+    /// The placeholders and their replacements, longest placeholder first so
+    /// that `https://placeholder.kg/id/` wins from `https://placeholder.kg/`
+    replacements: Vec<(String, String)>,
+}
+
+impl PlaceholderRewriter {
+    /// A rewriter for the given placeholders and their replacements. Both are
+    /// base IRIs, so they get a trailing `/` if they do not end with a `/` or a
+    /// `#` already. Placeholders that are empty are left out, placeholders
+    /// that are the same as their replacement are kept: they still win from a
+    /// shorter placeholder that they start with.
+    pub fn new<P: AsRef<str>, R: AsRef<str>>(
+        replacements: impl IntoIterator<Item = (P, R)>,
+    ) -> Self {
+        let mut replacements = replacements
+            .into_iter()
+            .filter(|(placeholder, _)| !placeholder.as_ref().trim().is_empty())
+            .map(|(placeholder, replacement)| {
+                (
+                    as_base_iri(placeholder.as_ref()),
+                    as_base_iri(replacement.as_ref()),
+                )
+            })
+            .collect::<Vec<_>>();
+        replacements.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+        replacements.dedup_by(|(a, _), (b, _)| a == b);
+        Self { replacements }
+    }
+
+    /// The rewriter for the placeholders in the `EKG_BASE_PLACEHOLDER`,
+    /// `EKG_ID_BASE_PLACEHOLDER` and `EKG_GRAPH_BASE_PLACEHOLDER` environment
+    /// variables, replaced with `EKG_BASE_INTERNAL`, `EKG_ID_BASE_INTERNAL`
+    /// and `EKG_GRAPH_BASE_INTERNAL` respectively. A pair of which either one
+    /// is not set is left out.
+    pub fn from_env() -> Self {
+        Self::new(
+            [
+                (ENV_BASE_PLACEHOLDER, "EKG_BASE_INTERNAL"),
+                (ENV_ID_BASE_PLACEHOLDER, "EKG_ID_BASE_INTERNAL"),
+                (
+                    ENV_GRAPH_BASE_PLACEHOLDER,
+                    "EKG_GRAPH_BASE_INTERNAL",
+                ),
+            ]
+            .into_iter()
+            .filter_map(|(placeholder, replacement)| {
+                Some((
+                    std::env::var(placeholder).ok()?,
+                    std::env::var(replacement).ok()?,
+                ))
+            }),
+        )
+    }
+
+    /// True if there is nothing to rewrite, typically because the placeholders
+    /// are the bases of the environment itself
+    pub fn is_empty(&self) -> bool {
+        self.replacements
+            .iter()
+            .all(|(placeholder, replacement)| placeholder == replacement)
+    }
+
+    /// A writer that writes everything that is written to it to the given
+    /// writer, with the placeholders replaced
+    pub fn writer<W: Write>(&self, inner: W) -> PlaceholderWriter<W> {
+        PlaceholderWriter { rewriter: self.clone(), pending: Vec::new(), inner }
+    }
+
+    /// The given content with the placeholders replaced
+    pub fn rewrite(&self, content: &[u8]) -> Vec<u8> {
+        let mut writer = self.writer(Vec::with_capacity(content.len()));
+        writer
+            .write_all(content)
+            .and_then(|_| writer.finish())
+            .expect("writing to a Vec cannot fail")
+    }
+
+    fn max_placeholder_len(&self) -> usize {
+        self.replacements
+            .first()
+            .map_or(0, |(placeholder, _)| placeholder.len())
+    }
+}
+
+fn as_base_iri(iri: &str) -> String {
+    let iri = iri.trim();
+    if iri.ends_with('/') || iri.ends_with('#') {
+        iri.to_string()
+    } else {
+        format!("{}/", iri)
+    }
+}
+
+/// See [`PlaceholderRewriter::writer`]. Since a placeholder can be split over
+/// two writes, it holds back the end of every write that could be the start of
+/// a placeholder, call [`PlaceholderWriter::finish`] to write that as well.
+pub struct PlaceholderWriter<W: Write> {
+    rewriter: PlaceholderRewriter,
+    pending:  Vec<u8>,
+    inner:    W,
+}
+
+impl<W: Write> PlaceholderWriter<W> {
+    /// Write what has been held back and return the inner writer
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.rewrite_pending(true)?;
+        Ok(self.inner)
+    }
+
+    pub fn get_mut(&mut self) -> &mut W { &mut self.inner }
+
+    /// Replace the placeholders in the pending bytes and write them, except
+    /// (unless this is the last call) for the bytes at the end that could be
+    /// the start of a placeholder
+    fn rewrite_pending(&mut self, last: bool) -> std::io::Result<()> {
+        let max_placeholder_len = self.rewriter.max_placeholder_len();
+        let mut rewritten = Vec::with_capacity(self.pending.len());
+        let mut copied = 0;
+        let mut index = 0;
+        while index < self.pending.len() {
+            if !last && self.pending.len() - index < max_placeholder_len {
+                break;
+            }
+            let rest = &self.pending[index..];
+            match self
+                .rewriter
+                .replacements
+                .iter()
+                .find(|(placeholder, _)| rest.starts_with(placeholder.as_bytes()))
+            {
+                Some((placeholder, replacement)) => {
+                    rewritten.extend_from_slice(&self.pending[copied..index]);
+                    rewritten.extend_from_slice(replacement.as_bytes());
+                    index += placeholder.len();
+                    copied = index;
+                },
+                None => index += 1,
+            }
+        }
+        rewritten.extend_from_slice(&self.pending[copied..index]);
+        self.pending.drain(..index);
+        self.inner.write_all(&rewritten)
+    }
+}
+
+impl<W: Write> Write for PlaceholderWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.rewrite_pending(false)?;
+        Ok(buf.len())
+    }
+
+    /// Flushes the inner writer, but not what is held back
+    fn flush(&mut self) -> std::io::Result<()> { self.inner.flush() }
+}
+
+/// The S3 URI that [`stage`] writes the rewritten copy of the given source to:
+/// the key of the source under the staging prefix, under its version (if any)
+/// so that two versions of a file that are loaded at the same time do not
+/// overwrite each other's copy.
+pub fn staged_s3_uri(
+    source: &str,
+    staging_prefix: &str,
+    source_version: Option<&str>,
+) -> Result<String, Error> {
+    let (bucket, key) = parse_s3_uri(source)
+        .ok_or_else(|| Error::ServiceError(format!("Invalid S3 URI {}", source)))?;
+    Ok(
+        match source_version.filter(|version| !version.is_empty()) {
+            Some(version) => {
+                format!(
+                    "s3://{}/{}{}/{}",
+                    bucket, staging_prefix, version, key
+                )
+            },
+            None => format!("s3://{}/{}{}", bucket, staging_prefix, key),
+        },
+    )
+}
+
+/// The staging prefix from the `EKG_STAGING_PREFIX` environment variable,
+/// defaults to [`DEFAULT_STAGING_PREFIX`] when not set
+pub fn staging_prefix_from_env() -> String {
+    match std::env::var(ENV_STAGING_PREFIX) {
+        Ok(prefix) if !prefix.trim().is_empty() => prefix.trim().to_string(),
+        _ => DEFAULT_STAGING_PREFIX.to_string(),
+    }
+}
+
+/// Why [`stage`] failed
+#[derive(Debug)]
+pub enum StagingError {
+    /// Reading the source from S3 or writing the copy to S3 failed, which may
+    /// well work when the source is staged again
+    Transient(Error),
+    /// The source cannot be staged, such as a compressed file that is corrupt
+    Permanent(Error),
+}
+
+impl StagingError {
+    pub fn is_transient(&self) -> bool { matches!(self, Self::Transient(_)) }
+
+    pub fn error(&self) -> &Error {
+        match self {
+            Self::Transient(error) | Self::Permanent(error) => error,
+        }
+    }
+
+    /// An error of the writer that decompresses, rewrites and compresses
+    /// again, which only fails on a file that it cannot decompress
+    fn from_io_error(source: &str, error: std::io::Error) -> Self {
+        let kind = error.kind();
+        let error = Error::ServiceError(format!(
+            "Could not rewrite the placeholders in {}: {}",
+            source, error
+        ));
+        match kind {
+            std::io::ErrorKind::InvalidData | std::io::ErrorKind::InvalidInput => {
+                Self::Permanent(error)
+            },
+            _ => Self::Transient(error),
+        }
+    }
+}
+
+impl From<StagingError> for Error {
+    fn from(error: StagingError) -> Self {
+        match error {
+            StagingError::Transient(error) | StagingError::Permanent(error) => error,
+        }
+    }
+}
+
+impl Display for StagingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { self.error().fmt(f) }
+}
+
+/// Stream the object at `source` from S3, replace its placeholders and write
+/// the result to `target`, in parts. Files that are compressed with gzip
+/// (`.gz`) or bzip2 (`.bz2`) are decompressed and compressed again on the fly.
+pub async fn stage<S: ObjectStore>(
+    object_store: &S,
+    rewriter: &PlaceholderRewriter,
+    source: &str,
+    target: &str,
+) -> Result<(), StagingError> {
+    let invalid_s3_uri = |s3_uri: &str| {
+        StagingError::Permanent(Error::ServiceError(format!(
+            "Invalid S3 URI {}",
+            s3_uri
+        )))
+    };
+    let (source_bucket, source_key) = parse_s3_uri(source).ok_or_else(|| invalid_s3_uri(source))?;
+    let (bucket, key) = parse_s3_uri(target).ok_or_else(|| invalid_s3_uri(target))?;
+    tracing::info!(
+        "Rewriting the placeholders in {} to {}",
+        source,
+        target
+    );
+    let io_error = |error: std::io::Error| StagingError::from_io_error(source, error);
+
+    let mut body = object_store
+        .get_object(source_bucket, source_key)
+        .await
+        .map_err(StagingError::Transient)?;
+    let mut writer = StagingWriter::new(rewriter, source_key);
+    let mut upload = MultipartUpload::new(object_store, bucket, key);
+    let mut buffer = Vec::new();
+    let result = async {
+        while let Some(chunk) = body.try_next().await.map_err(|error| {
+            StagingError::Transient(Error::ServiceError(format!(
+                "Could not read {}: {}",
+                source, error
+            )))
+        })? {
+            writer.write_all(&chunk).map_err(io_error)?;
+            buffer.append(writer.output());
+            if buffer.len() >= PART_SIZE {
+                upload
+                    .push(std::mem::take(&mut buffer))
+                    .await
+                    .map_err(StagingError::Transient)?;
+            }
+        }
+        buffer.append(&mut writer.finish().map_err(io_error)?);
+        upload.finish(buffer).await.map_err(StagingError::Transient)
+    }
+    .await;
+    if result.is_err() {
+        upload.abort().await;
+    }
+    result
+}
+
+/// The chain of writers that decompresses, rewrites and compresses again
+enum StagingWriter {
+    Plain(PlaceholderWriter<Vec<u8>>),
+    Gzip(flate2::write::GzDecoder<PlaceholderWriter<flate2::write::GzEncoder<Vec<u8>>>>),
+    Bzip2(bzip2::write::BzDecoder<PlaceholderWriter<bzip2::write::BzEncoder<Vec<u8>>>>),
+}
+
+impl StagingWriter {
+    fn new(rewriter: &PlaceholderRewriter, key: &str) -> Self {
+        if key.ends_with(".gz") {
+            Self::Gzip(flate2::write::GzDecoder::new(rewriter.writer(
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()),
+            )))
+        } else if key.ends_with(".bz2") {
+            Self::Bzip2(bzip2::write::BzDecoder::new(rewriter.writer(
+                bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default()),
+            )))
+        } else {
+            Self::Plain(rewriter.writer(Vec::new()))
+        }
+    }
+
+    fn write_all(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.write_all(chunk),
+            Self::Gzip(writer) => writer.write_all(chunk),
+            Self::Bzip2(writer) => writer.write_all(chunk),
+        }
+    }
+
+    /// The output that has been written so far (and not taken before)
+    fn output(&mut self) -> &mut Vec<u8> {
+        match self {
+            Self::Plain(writer) => writer.get_mut(),
+            Self::Gzip(writer) => writer.get_mut().get_mut().get_mut(),
+            Self::Bzip2(writer) => writer.get_mut().get_mut().get_mut(),
+        }
+    }
+
+    /// The rest of the output
+    fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Plain(writer) => writer.finish(),
+            Self::Gzip(writer) => writer.finish()?.finish()?.finish(),
+            // The bzip2 decoder finishes by reference
+            Self::Bzip2(mut writer) => writer.finish()?.finish()?.finish(),
+        }
+    }
+}
+
+/// Uploads an object in parts, or in one go if it fits in a single part
+struct MultipartUpload<'a, S: ObjectStore> {
+    object_store: &'a S,
+    bucket:       &'a str,
+    key:          &'a str,
+    upload_id:    Option<String>,
+    parts:        Vec<(i32, String)>,
+}
+
+impl<'a, S: ObjectStore> MultipartUpload<'a, S> {
+    fn new(object_store: &'a S, bucket: &'a str, key: &'a str) -> Self {
+        Self {
+            object_store,
+            bucket,
+            key,
+            upload_id: None,
+            parts: Vec::new(),
+        }
+    }
+
+    async fn push(&mut self, part: Vec<u8>) -> Result<(), Error> {
+        let upload_id = match &self.upload_id {
+            Some(upload_id) => upload_id.clone(),
+            None => {
+                let upload_id = self
+                    .object_store
+                    .create_multipart_upload(self.bucket, self.key)
+                    .await?;
+                self.upload_id = Some(upload_id.clone());
+                upload_id
+            },
+        };
+        let part_number = self.parts.len() as i32 + 1;
+        let e_tag = self
+            .object_store
+            .upload_part(
+                self.bucket,
+                self.key,
+                upload_id.as_str(),
+                part_number,
+                part,
+            )
+            .await?;
+        self.parts.push((part_number, e_tag));
+        Ok(())
+    }
+
+    async fn finish(&mut self, last_part: Vec<u8>) -> Result<(), Error> {
+        if self.upload_id.is_none() {
+            return self
+                .object_store
+                .put_object(self.bucket, self.key, last_part)
+                .await;
+        }
+        if !last_part.is_empty() {
+            self.push(last_part).await?;
+        }
+        let upload_id = self.upload_id.take().unwrap();
+        self.object_store
+            .complete_multipart_upload(
+                self.bucket,
+                self.key,
+                upload_id.as_str(),
+                std::mem::take(&mut self.parts),
+            )
+            .await
+    }
+
+    async fn abort(&mut self) {
+        if let Some(upload_id) = self.upload_id.take() {
+            if let Err(error) = self
+                .object_store
+                .abort_multipart_upload(self.bucket, self.key, upload_id.as_str())
+                .await
+            {
+                tracing::error!("{}", error);
+            }
+        }
+    }
+}
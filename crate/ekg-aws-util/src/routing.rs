@@ -0,0 +1,236 @@
+//! Route the S3 objects of the source bucket by their key: which pipeline
+//! loads them, into which named graph, with which base URI and format, or
+//! whether they are loaded at all.
+//!
+//! For example (in TOML, JSON with the same structure works as well):
+//!
+//! ```toml
+//! [pipelines]
+//! ontology = "arn:aws:states:antartica-01:123456789012:stateMachine:ontology"
+//!
+//! [[rule]]
+//! glob = "**/*.draft.ttl"
+//! skip = true
+//!
+//! [[rule]]
+//! prefix = "ontology/"
+//! pipeline = "ontology"
+//! graph = "{graph_base}ontology/{stem}"
+//!
+//! [[rule]]
+//! prefix = "static-dataset/"
+//! base_uri = "https://kg.example.com/id/"
+//! format = "turtle"
+//! ```
+//!
+//! The first rule that matches the (decoded) key of an object applies, objects
+//! that no rule matches are loaded by the pipeline of the invoke lambda
+//! function into the graph named by the graph naming strategy of that pipeline,
+//! see [`GraphNamingStrategy`].
+use {
+    crate::{
+        neptune::{is_rdf_format, GraphNamingStrategy},
+        object_store::ObjectStore,
+        s3::parse_s3_uri,
+        serde_util::deserialize_optional_format_from_str,
+        S3EventRecord,
+    },
+    aws_sdk_neptunedata::types::Format,
+    ekg_error::Error,
+    ekg_identifier::{ABoxNamespaceIRI, EkgIdentifierContexts},
+    globset::{GlobBuilder, GlobMatcher},
+    serde::Deserialize,
+    std::collections::HashMap,
+};
+
+/// The environment variable that holds the routing table itself, in TOML or
+/// JSON
+pub const ENV_ROUTING_TABLE: &str = "EKG_ROUTING_TABLE";
+/// The environment variable that holds the S3 URI of the routing table, in
+/// TOML or JSON
+pub const ENV_ROUTING_TABLE_S3_URI: &str = "EKG_ROUTING_TABLE_S3_URI";
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RoutingTable {
+    /// The ARN of the RDF load state machine of each pipeline that a rule can
+    /// route to, other than the pipeline of the invoke lambda function itself
+    #[serde(default)]
+    pub pipelines: HashMap<String, String>,
+    #[serde(default, rename = "rule", alias = "rules")]
+    pub rules:     Vec<RoutingRule>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RoutingRule {
+    /// The rule applies to the keys that start with this prefix
+    #[serde(default)]
+    pub prefix:   Option<String>,
+    /// The rule applies to the keys that match this glob pattern, where a `*`
+    /// does not match a `/` but a `**` does. Given both a prefix and a glob,
+    /// the key has to match both.
+    #[serde(default)]
+    pub glob:     Option<String>,
+    /// The pipeline that loads the matching objects
+    #[serde(default)]
+    pub pipeline: Option<String>,
+    /// The graph naming strategy for the matching objects: `s3-uri`, `hash`
+    /// or a template of the IRI of the named graph, see
+    /// [`GraphNamingStrategy`]
+    #[serde(default)]
+    pub graph:    Option<String>,
+    /// The base URI for the relative IRIs in the matching objects, instead of
+    /// the internal `ekg_id_base`
+    #[serde(default)]
+    pub base_uri: Option<ABoxNamespaceIRI>,
+    /// The RDF format of the matching objects, regardless of their extension
+    #[serde(default, deserialize_with = "deserialize_optional_format_from_str")]
+    pub format:   Option<Format>,
+    /// Do not load the matching objects at all
+    #[serde(default)]
+    pub skip:     bool,
+    #[serde(skip)]
+    matcher:      Option<GlobMatcher>,
+    #[serde(skip)]
+    graph_naming: Option<GraphNamingStrategy>,
+}
+
+impl RoutingTable {
+    /// Parse the given routing table, JSON if it is a JSON object and TOML
+    /// otherwise
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        let format = if content.trim_start().starts_with('{') {
+            config::FileFormat::Json
+        } else {
+            config::FileFormat::Toml
+        };
+        let routing_table = config::Config::builder()
+            .add_source(config::File::from_str(content, format))
+            .build()
+            .and_then(|config| config.try_deserialize::<Self>())
+            .map_err(|error| Error::ServiceError(format!("Invalid routing table: {}", error)))?;
+        routing_table.compiled()
+    }
+
+    /// Get the routing table from the `EKG_ROUTING_TABLE` environment variable
+    /// or else from the S3 object at `EKG_ROUTING_TABLE_S3_URI`, an empty
+    /// routing table (that routes everything to the pipeline of the invoke
+    /// lambda function) if neither is set.
+    pub async fn from_env<S: ObjectStore>(object_store: &S) -> Result<Self, Error> {
+        if let Ok(content) = std::env::var(ENV_ROUTING_TABLE) {
+            if !content.trim().is_empty() {
+                return Self::parse(content.as_str());
+            }
+        }
+        match std::env::var(ENV_ROUTING_TABLE_S3_URI) {
+            Ok(s3_uri) if !s3_uri.trim().is_empty() => {
+                Self::from_s3(object_store, s3_uri.trim()).await
+            },
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Get the routing table from the S3 object at the given S3 URI
+    pub async fn from_s3<S: ObjectStore>(object_store: &S, s3_uri: &str) -> Result<Self, Error> {
+        let (bucket, key) = parse_s3_uri(s3_uri).ok_or_else(|| {
+            Error::ServiceError(format!(
+                "Invalid S3 URI for the routing table: {}",
+                s3_uri
+            ))
+        })?;
+        tracing::info!("Loading the routing table from {}", s3_uri);
+        let content = object_store
+            .get_object(bucket, key)
+            .await?
+            .collect()
+            .await
+            .map_err(|error| {
+                Error::ServiceError(format!(
+                    "Could not read the routing table {}: {}",
+                    s3_uri, error
+                ))
+            })?;
+        Self::parse(String::from_utf8_lossy(&content.into_bytes()).as_ref())
+    }
+
+    /// The first rule that applies to the given (decoded) key, if any
+    pub fn route(&self, key: &str) -> Option<&RoutingRule> {
+        self.rules.iter().find(|rule| rule.matches(key))
+    }
+
+    /// The ARN of the RDF load state machine of the given pipeline, if it is
+    /// in the `pipelines` table
+    pub fn state_machine_arn(&self, pipeline_id: &str) -> Option<&str> {
+        self.pipelines.get(pipeline_id).map(String::as_str)
+    }
+
+    /// Check the rules and compile their glob patterns
+    fn compiled(mut self) -> Result<Self, Error> {
+        for (index, rule) in self.rules.iter_mut().enumerate() {
+            let invalid = |reason: String| {
+                Error::ServiceError(format!(
+                    "Invalid routing rule {}: {}",
+                    index + 1,
+                    reason
+                ))
+            };
+            if rule.prefix.is_none() && rule.glob.is_none() {
+                return Err(invalid("it needs a prefix or a glob".to_string()));
+            }
+            if let Some(glob) = &rule.glob {
+                let glob = GlobBuilder::new(glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|error| invalid(error.to_string()))?;
+                rule.matcher = Some(glob.compile_matcher());
+            }
+            if let Some(format) = &rule.format {
+                if !is_rdf_format(format) {
+                    return Err(invalid(format!(
+                        "{} is not an RDF format",
+                        format.as_str()
+                    )));
+                }
+            }
+            if let Some(graph) = &rule.graph {
+                rule.graph_naming = Some(
+                    graph
+                        .parse::<GraphNamingStrategy>()
+                        .map_err(|error| invalid(error.to_string()))?,
+                );
+            }
+            if let Some(pipeline) = &rule.pipeline {
+                if pipeline.trim().is_empty() {
+                    return Err(invalid("the pipeline is empty".to_string()));
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl RoutingRule {
+    pub fn matches(&self, key: &str) -> bool {
+        self.prefix
+            .as_deref()
+            .map_or(true, |prefix| key.starts_with(prefix)) &&
+            self.matcher
+                .as_ref()
+                .map_or(true, |matcher| matcher.is_match(key))
+    }
+
+    /// The graph naming strategy of this rule, if it has one
+    pub fn graph_naming(&self) -> Option<&GraphNamingStrategy> { self.graph_naming.as_ref() }
+
+    /// The IRI of the named graph of the object of the given S3 event record
+    /// according to the graph naming strategy of this rule, if it has one
+    pub fn graph_iri(
+        &self,
+        s3_event_record: &S3EventRecord,
+        identifier_contexts: &EkgIdentifierContexts,
+    ) -> Result<Option<String>, Error> {
+        self.graph_naming
+            .as_ref()
+            .map(|graph_naming| graph_naming.graph_iri(s3_event_record, identifier_contexts))
+            .transpose()
+    }
+}
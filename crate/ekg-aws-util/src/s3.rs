@@ -1,8 +1,42 @@
 use {
     crate::{OwnerIdentity, RequestParameters, ResponseElements, UserId},
+    percent_encoding::{
+        percent_decode_str,
+        utf8_percent_encode,
+        AsciiSet,
+        CONTROLS,
+        NON_ALPHANUMERIC,
+    },
     serde::Deserialize,
+    sha2::{Digest, Sha256},
 };
 
+/// The characters of an object key that cannot be used as is in an IRI, see
+/// the `IRIREF` production of the SPARQL grammar. The `%` is encoded as well
+/// so that a key that contains one does not turn into another key.
+const IRI_UNSAFE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'<')
+    .add(b'>')
+    .add(b'"')
+    .add(b'{')
+    .add(b'}')
+    .add(b'|')
+    .add(b'^')
+    .add(b'`')
+    .add(b'\\')
+    .add(b'%');
+
+/// The characters of an object key that S3 event notifications leave as they
+/// are, the space becomes a `+` and all others are percent-encoded
+const KEY_SAFE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b' ')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'*')
+    .remove(b'/');
+
 /// The embedded JSON message structure.
 /// For example:
 /// {
@@ -63,13 +97,25 @@ pub struct S3EventRecord {
     /// The `event_name` references the list of [event notification
     /// types](https://docs.aws.amazon.com/AmazonS3/latest/userguide/notification-how-to-event-types-and-destinations.html)
     /// but doesn't contain the s3: prefix.
-    /// TODO: Convert to enum
-    pub event_name: String,
+    pub event_name: S3EventName,
     /// User who caused the event
     pub user_identity: UserId,
     pub request_parameters: RequestParameters,
     pub response_elements: ResponseElements,
     pub s3: S3,
+    /// Only for `ObjectRestore:Completed` events (and `ObjectRestore:Post`
+    /// events of objects in the S3 Glacier storage classes)
+    #[serde(default)]
+    pub glacier_event_data: Option<GlacierEventData>,
+    /// Only for `LifecycleTransition` events
+    #[serde(default)]
+    pub lifecycle_event_data: Option<LifecycleEventData>,
+    /// Only for `IntelligentTiering` events
+    #[serde(default)]
+    pub intelligent_tiering_event_data: Option<IntelligentTieringEventData>,
+    /// Only for `Replication:*` events
+    #[serde(default)]
+    pub replication_event_data: Option<ReplicationEventData>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -93,18 +139,312 @@ pub struct S3Bucket {
 }
 
 /// See https://docs.aws.amazon.com/AmazonS3/latest/userguide/notification-content-structure.html
+///
+/// The `ObjectRemoved` events have no size and eTag, the `ObjectTagging`,
+/// `ObjectAcl` and `ObjectRestore` events have no sequencer.
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct S3Object {
-    /// Object key
+    /// Object key, URL-encoded the way an HTML form is encoded (a space
+    /// becomes a `+`), see [`S3Object::decoded_key`]
     pub key: String,
     /// Size in bytes
-    pub size: u64,
+    #[serde(default)]
+    pub size: Option<u64>,
     /// Object eTag
-    pub e_tag: String,
+    #[serde(default)]
+    pub e_tag: Option<String>,
     /// object version if bucket is versioning-enabled, otherwise null
+    #[serde(default)]
     pub version_id: Option<String>,
     /// a string representation of a hexadecimal value used to
     /// determine event sequence, only used with PUTs and DELETEs
-    pub sequencer: String,
+    #[serde(default)]
+    pub sequencer: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GlacierEventData {
+    pub restore_event_data: RestoreEventData,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreEventData {
+    /// When the restored copy of the object expires, such as
+    /// `2023-09-20T00:00:00.000Z`
+    pub lifecycle_restoration_expiry_time: String,
+    /// The storage class of the restored copy, such as `Standard`
+    pub lifecycle_restore_storage_class:   String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LifecycleEventData {
+    pub transition_event_data: TransitionEventData,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TransitionEventData {
+    /// The storage class that the object has transitioned to, such as
+    /// `GLACIER`
+    pub destination_storage_class: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IntelligentTieringEventData {
+    /// The access tier that the object has moved to, such as
+    /// `ARCHIVE_ACCESS`
+    pub destination_access_tier: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicationEventData {
+    pub replication_rule_id: String,
+    pub destination_bucket:  String,
+    pub s3_operation:        String,
+    pub request_time:        String,
+    /// Only for `Replication:OperationFailedReplication` events
+    #[serde(default)]
+    pub failure_reason:      Option<String>,
+    /// Only for the `Replication:Operation*Threshold` events
+    #[serde(default)]
+    pub threshold:           Option<String>,
+    /// Only for the `Replication:Operation*Threshold` events
+    #[serde(default)]
+    pub replication_time:    Option<String>,
+}
+
+impl S3EventRecord {
+    /// The S3 URI (`s3://bucket/key`) of the object that this event is about,
+    /// with the key as it is in the bucket, see [`S3Object::decoded_key`]
+    pub fn s3_uri(&self) -> String {
+        format!(
+            "s3://{}/{}",
+            self.s3.bucket.name,
+            self.s3.object.decoded_key()
+        )
+    }
+
+    /// The S3 URI of the object as an IRI, the same as [`S3EventRecord::s3_uri`]
+    /// unless the key contains characters such as spaces that an IRI cannot
+    /// contain, see [`s3_uri_to_iri`]
+    pub fn s3_iri(&self) -> String { s3_uri_to_iri(self.s3_uri().as_str()) }
+
+    /// A deterministic name for the Step Functions execution that loads the
+    /// object of this event, so that a redelivery of the same event (SNS and
+    /// S3 deliver at least once) cannot start a second execution.
+    ///
+    /// Execution names are at most 80 characters of `[A-Za-z0-9-_]` so the
+    /// bucket, key, version and sequencer are hashed rather than concatenated.
+    pub fn execution_name(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [
+            self.s3.bucket.name.as_str(),
+            self.s3.object.key.as_str(),
+            self.s3.object.version(),
+            self.s3.object.sequencer.as_deref().unwrap_or_default(),
+        ] {
+            hasher.update(part.as_bytes());
+            // Separate the parts so that "ab" + "c" differs from "a" + "bc"
+            hasher.update([0_u8]);
+        }
+        let digest = hasher.finalize();
+        let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("rdf-load-{}", hex)
+    }
+}
+
+impl S3Object {
+    /// The key of the object as it is in the bucket. S3 event notifications
+    /// URL-encode the key, so `ontology/data+use%C3%A9.ttl` is the key
+    /// `ontology/data useé.ttl` (and a `+` in the key is encoded as `%2B`).
+    pub fn decoded_key(&self) -> String {
+        percent_decode_str(self.key.replace('+', " ").as_str())
+            .decode_utf8_lossy()
+            .to_string()
+    }
+
+    /// The version of the object: its version ID if the bucket is
+    /// versioning-enabled, its eTag otherwise
+    pub fn version(&self) -> &str {
+        self.version_id
+            .as_deref()
+            .or(self.e_tag.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// URL-encode the given key the way S3 event notifications do, the inverse of
+/// [`S3Object::decoded_key`]
+pub fn encode_key(key: &str) -> String {
+    utf8_percent_encode(key, KEY_SAFE)
+        .to_string()
+        .replace(' ', "+")
+}
+
+/// Percent-encode the characters of the given S3 URI (such as spaces) that an
+/// IRI cannot contain, so that it can be used as the IRI of a named graph or
+/// dataset. Other characters, including non-ASCII ones, are left as they are.
+pub fn s3_uri_to_iri(s3_uri: &str) -> String {
+    utf8_percent_encode(s3_uri, IRI_UNSAFE).to_string()
+}
+
+/// Split the given S3 URI, such as `s3://bucket/some/key.ttl`, into its bucket
+/// and its key
+pub fn parse_s3_uri(s3_uri: &str) -> Option<(&str, &str)> {
+    s3_uri
+        .strip_prefix("s3://")
+        .and_then(|bucket_and_key| bucket_and_key.split_once('/'))
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+}
+
+/// The event notification types, see
+/// https://docs.aws.amazon.com/AmazonS3/latest/userguide/notification-how-to-event-types-and-destinations.html
+///
+/// The names in S3 event records do not have the `s3:` prefix that is used in
+/// the bucket notification configuration, so `ObjectCreated:Put` rather than
+/// `s3:ObjectCreated:Put`. Event types that S3 might add later end up as
+/// [`S3EventName::Other`].
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String")]
+pub enum S3EventName {
+    ObjectCreatedPut,
+    ObjectCreatedPost,
+    ObjectCreatedCopy,
+    ObjectCreatedCompleteMultipartUpload,
+    ObjectRemovedDelete,
+    ObjectRemovedDeleteMarkerCreated,
+    ObjectRestorePost,
+    ObjectRestoreCompleted,
+    ObjectRestoreDelete,
+    ReducedRedundancyLostObject,
+    ReplicationOperationFailedReplication,
+    ReplicationOperationMissedThreshold,
+    ReplicationOperationReplicatedAfterThreshold,
+    ReplicationOperationNotTracked,
+    LifecycleExpirationDelete,
+    LifecycleExpirationDeleteMarkerCreated,
+    LifecycleTransition,
+    IntelligentTiering,
+    ObjectTaggingPut,
+    ObjectTaggingDelete,
+    ObjectAclPut,
+    Other(String),
+}
+
+impl S3EventName {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::ObjectCreatedPut => "ObjectCreated:Put",
+            Self::ObjectCreatedPost => "ObjectCreated:Post",
+            Self::ObjectCreatedCopy => "ObjectCreated:Copy",
+            Self::ObjectCreatedCompleteMultipartUpload => "ObjectCreated:CompleteMultipartUpload",
+            Self::ObjectRemovedDelete => "ObjectRemoved:Delete",
+            Self::ObjectRemovedDeleteMarkerCreated => "ObjectRemoved:DeleteMarkerCreated",
+            Self::ObjectRestorePost => "ObjectRestore:Post",
+            Self::ObjectRestoreCompleted => "ObjectRestore:Completed",
+            Self::ObjectRestoreDelete => "ObjectRestore:Delete",
+            Self::ReducedRedundancyLostObject => "ReducedRedundancyLostObject",
+            Self::ReplicationOperationFailedReplication => "Replication:OperationFailedReplication",
+            Self::ReplicationOperationMissedThreshold => "Replication:OperationMissedThreshold",
+            Self::ReplicationOperationReplicatedAfterThreshold => {
+                "Replication:OperationReplicatedAfterThreshold"
+            },
+            Self::ReplicationOperationNotTracked => "Replication:OperationNotTracked",
+            Self::LifecycleExpirationDelete => "LifecycleExpiration:Delete",
+            Self::LifecycleExpirationDeleteMarkerCreated => "LifecycleExpiration:DeleteMarkerCreated",
+            Self::LifecycleTransition => "LifecycleTransition",
+            Self::IntelligentTiering => "IntelligentTiering",
+            Self::ObjectTaggingPut => "ObjectTagging:Put",
+            Self::ObjectTaggingDelete => "ObjectTagging:Delete",
+            Self::ObjectAclPut => "ObjectAcl:Put",
+            Self::Other(event_name) => event_name.as_str(),
+        }
+    }
+
+    /// Return true if the object has been created (or overwritten)
+    pub fn is_object_created(&self) -> bool {
+        matches!(
+            self,
+            Self::ObjectCreatedPut |
+                Self::ObjectCreatedPost |
+                Self::ObjectCreatedCopy |
+                Self::ObjectCreatedCompleteMultipartUpload
+        )
+    }
+
+    /// Return true if the object has been deleted, which includes the creation
+    /// of a delete marker in a versioning-enabled bucket and the expiration of
+    /// the object by a lifecycle rule
+    pub fn is_object_removed(&self) -> bool {
+        matches!(
+            self,
+            Self::ObjectRemovedDelete |
+                Self::ObjectRemovedDeleteMarkerCreated |
+                Self::LifecycleExpirationDelete |
+                Self::LifecycleExpirationDeleteMarkerCreated
+        )
+    }
+
+    /// Return true if a delete marker has been created, which makes it the
+    /// current version of the object in a versioning-enabled bucket
+    pub fn is_delete_marker_created(&self) -> bool {
+        matches!(
+            self,
+            Self::ObjectRemovedDeleteMarkerCreated | Self::LifecycleExpirationDeleteMarkerCreated
+        )
+    }
+
+    pub fn is_object_tagging(&self) -> bool {
+        matches!(
+            self,
+            Self::ObjectTaggingPut | Self::ObjectTaggingDelete
+        )
+    }
+}
+
+impl From<&str> for S3EventName {
+    fn from(event_name: &str) -> Self {
+        match event_name.strip_prefix("s3:").unwrap_or(event_name) {
+            "ObjectCreated:Put" => Self::ObjectCreatedPut,
+            "ObjectCreated:Post" => Self::ObjectCreatedPost,
+            "ObjectCreated:Copy" => Self::ObjectCreatedCopy,
+            "ObjectCreated:CompleteMultipartUpload" => Self::ObjectCreatedCompleteMultipartUpload,
+            "ObjectRemoved:Delete" => Self::ObjectRemovedDelete,
+            "ObjectRemoved:DeleteMarkerCreated" => Self::ObjectRemovedDeleteMarkerCreated,
+            "ObjectRestore:Post" => Self::ObjectRestorePost,
+            "ObjectRestore:Completed" => Self::ObjectRestoreCompleted,
+            "ObjectRestore:Delete" => Self::ObjectRestoreDelete,
+            "ReducedRedundancyLostObject" => Self::ReducedRedundancyLostObject,
+            "Replication:OperationFailedReplication" => Self::ReplicationOperationFailedReplication,
+            "Replication:OperationMissedThreshold" => Self::ReplicationOperationMissedThreshold,
+            "Replication:OperationReplicatedAfterThreshold" => {
+                Self::ReplicationOperationReplicatedAfterThreshold
+            },
+            "Replication:OperationNotTracked" => Self::ReplicationOperationNotTracked,
+            "LifecycleExpiration:Delete" => Self::LifecycleExpirationDelete,
+            "LifecycleExpiration:DeleteMarkerCreated" => Self::LifecycleExpirationDeleteMarkerCreated,
+            "LifecycleTransition" => Self::LifecycleTransition,
+            "IntelligentTiering" => Self::IntelligentTiering,
+            "ObjectTagging:Put" => Self::ObjectTaggingPut,
+            "ObjectTagging:Delete" => Self::ObjectTaggingDelete,
+            "ObjectAcl:Put" => Self::ObjectAclPut,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for S3EventName {
+    fn from(event_name: String) -> Self { Self::from(event_name.as_str()) }
+}
+
+impl std::fmt::Display for S3EventName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
@@ -20,3 +20,15 @@ where D: serde::Deserializer<'de> {
     let s: String = Deserialize::deserialize(deserializer)?;
     aws_sdk_neptunedata::types::Format::try_parse(s.as_str()).map_err(serde::de::Error::custom)
 }
+
+/// The same as [`deserialize_format_from_str`] for an optional format
+pub fn deserialize_optional_format_from_str<'de, D>(
+    deserializer: D,
+) -> Result<Option<aws_sdk_neptunedata::types::Format>, D::Error>
+where D: serde::Deserializer<'de> {
+    let s: Option<String> = Deserialize::deserialize(deserializer)?;
+    s.map(|s| {
+        aws_sdk_neptunedata::types::Format::try_parse(s.as_str()).map_err(serde::de::Error::custom)
+    })
+    .transpose()
+}
@@ -0,0 +1,19 @@
+pub use {
+    record::{SnsEventRecord, SnsRecord},
+    signature::{
+        check_signing_cert_url,
+        string_to_sign,
+        verify_signature,
+        CertificateProvider,
+        HttpsCertificateProvider,
+        SnsSignatureVerifier,
+        VerificationError,
+        ENV_VERIFY_SNS_SIGNATURES,
+    },
+};
+
+#[cfg(feature = "fake")]
+pub use signature::InMemoryCertificateProvider;
+
+mod record;
+mod signature;
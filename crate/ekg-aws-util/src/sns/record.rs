@@ -17,13 +17,19 @@ pub struct SnsRecord {
     pub type_: String,
     pub message_id: String,
     pub topic_arn: String,
-    pub subject: String,
+    // Null (or absent) when the notification has no subject
+    #[serde(default)]
+    pub subject: Option<String>,
     // The stringified JSON message embedded in the SNS message.
     pub message: String,
     pub timestamp: String,
     pub signature_version: String,
     pub signature: String,
+    // SNS notifications in SQS messages spell it SigningCertURL
+    #[serde(alias = "SigningCertURL")]
     pub signing_cert_url: String,
+    #[serde(alias = "UnsubscribeURL")]
     pub unsubscribe_url: String,
+    #[serde(default)]
     pub message_attributes: Value,
 }
@@ -0,0 +1,335 @@
+//! Verify that an SNS notification was actually sent by SNS, see
+//! https://docs.aws.amazon.com/sns/latest/dg/sns-verify-signature-of-message.html
+use {
+    crate::sns::SnsRecord,
+    base64::{engine::general_purpose::STANDARD, Engine},
+    ekg_error::Error,
+    rsa::{
+        pkcs1v15::{Signature, VerifyingKey},
+        pkcs8::DecodePublicKey,
+        signature::Verifier,
+        RsaPublicKey,
+    },
+    sha1::Sha1,
+    sha2::Sha256,
+    std::{
+        collections::HashMap,
+        fmt::{Display, Formatter},
+        future::Future,
+        sync::{Arc, Mutex},
+        time::SystemTime,
+    },
+    x509_cert::{
+        der::{DecodePem, Encode},
+        Certificate,
+    },
+};
+
+/// The environment variable that, when `true`, makes the invoke lambda
+/// function reject the SNS notifications that are not signed by SNS
+pub const ENV_VERIFY_SNS_SIGNATURES: &str = "EKG_VERIFY_SNS_SIGNATURES";
+
+/// Gets the (PEM encoded) X.509 certificate at the `SigningCertURL` of an SNS
+/// notification
+pub trait CertificateProvider: Clone + Send + Sync + 'static {
+    fn certificate(&self, url: &str) -> impl Future<Output = Result<String, Error>> + Send;
+}
+
+/// Downloads the certificates from SNS, once per URL. Clones share their
+/// certificates.
+#[derive(Clone, Default)]
+pub struct HttpsCertificateProvider {
+    certificates: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl HttpsCertificateProvider {
+    pub fn new() -> Self { Self::default() }
+}
+
+impl CertificateProvider for HttpsCertificateProvider {
+    fn certificate(&self, url: &str) -> impl Future<Output = Result<String, Error>> + Send {
+        async move {
+            let cached = self.certificates.lock().unwrap().get(url).cloned();
+            if let Some(certificate) = cached {
+                return Ok(certificate);
+            }
+            let uri = url.parse::<hyper::Uri>().map_err(|error| {
+                Error::ServiceError(format!(
+                    "Invalid certificate URL {}: {}",
+                    url, error
+                ))
+            })?;
+            let client = hyper::Client::builder()
+                .build::<_, hyper::Body>(crate::tls_connector::create().await?);
+            let response = client.get(uri).await.map_err(|error| {
+                Error::ServiceError(format!(
+                    "Could not get the certificate at {}: {}",
+                    url, error
+                ))
+            })?;
+            if !response.status().is_success() {
+                return Err(Error::ServiceError(format!(
+                    "Could not get the certificate at {}: {}",
+                    url,
+                    response.status()
+                )));
+            }
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .map_err(|error| {
+                    Error::ServiceError(format!(
+                        "Could not read the certificate at {}: {}",
+                        url, error
+                    ))
+                })?;
+            let certificate = String::from_utf8_lossy(&body).to_string();
+            self.certificates
+                .lock()
+                .unwrap()
+                .insert(url.to_string(), certificate.clone());
+            Ok(certificate)
+        }
+    }
+}
+
+/// Serves the certificates that it is given rather than the ones of SNS
+/// itself, for unit tests of the lambda functions that need no network at all
+#[cfg(feature = "fake")]
+#[derive(Clone, Default)]
+pub struct InMemoryCertificateProvider {
+    certificates: HashMap<String, String>,
+}
+
+#[cfg(feature = "fake")]
+impl InMemoryCertificateProvider {
+    pub fn new() -> Self { Self::default() }
+
+    /// Serve the given (PEM encoded) certificate at the given URL
+    pub fn with_certificate(mut self, url: &str, certificate: &str) -> Self {
+        self.certificates
+            .insert(url.to_string(), certificate.to_string());
+        self
+    }
+}
+
+#[cfg(feature = "fake")]
+impl CertificateProvider for InMemoryCertificateProvider {
+    fn certificate(&self, url: &str) -> impl Future<Output = Result<String, Error>> + Send {
+        let certificate = self
+            .certificates
+            .get(url)
+            .cloned()
+            .ok_or_else(|| Error::ServiceError(format!("No certificate at {}", url)));
+        async move { certificate }
+    }
+}
+
+/// Why [`SnsSignatureVerifier::verify`] failed
+#[derive(Debug)]
+pub enum VerificationError {
+    /// The signing certificate could not be fetched, which may well work when
+    /// the notification is delivered again
+    CertificateUnavailable(Error),
+    /// The notification is not signed by SNS, or has been tampered with
+    NotVerified(Error),
+}
+
+impl VerificationError {
+    pub fn is_retryable(&self) -> bool { matches!(self, Self::CertificateUnavailable(_)) }
+
+    pub fn error(&self) -> &Error {
+        match self {
+            Self::CertificateUnavailable(error) | Self::NotVerified(error) => error,
+        }
+    }
+}
+
+impl From<VerificationError> for Error {
+    fn from(error: VerificationError) -> Self {
+        match error {
+            VerificationError::CertificateUnavailable(error) |
+            VerificationError::NotVerified(error) => error,
+        }
+    }
+}
+
+impl Display for VerificationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { self.error().fmt(f) }
+}
+
+/// Checks the signature of SNS notifications with the certificates of the
+/// given provider
+#[derive(Clone, Default)]
+pub struct SnsSignatureVerifier<P = HttpsCertificateProvider> {
+    certificate_provider: P,
+}
+
+impl SnsSignatureVerifier {
+    /// A verifier that downloads the certificates from SNS when the
+    /// `EKG_VERIFY_SNS_SIGNATURES` environment variable is `true`, none when it
+    /// is not set or `false`
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        match std::env::var(ENV_VERIFY_SNS_SIGNATURES) {
+            Ok(value) if !value.trim().is_empty() => {
+                match value.trim().to_ascii_lowercase().as_str() {
+                    "true" => Ok(Some(Self::default())),
+                    "false" => Ok(None),
+                    _ => {
+                        Err(Error::ServiceError(format!(
+                            "Invalid value {} of {}, expected true or false",
+                            value, ENV_VERIFY_SNS_SIGNATURES
+                        )))
+                    },
+                }
+            },
+            _ => Ok(None),
+        }
+    }
+}
+
+impl<P: CertificateProvider> SnsSignatureVerifier<P> {
+    pub fn new(certificate_provider: P) -> Self { Self { certificate_provider } }
+
+    pub async fn verify(&self, record: &SnsRecord) -> Result<(), VerificationError> {
+        // Never get a certificate from anywhere but SNS itself
+        check_signing_cert_url(
+            record.signing_cert_url.as_str(),
+            record.topic_arn.as_str(),
+        )
+        .map_err(VerificationError::NotVerified)?;
+        let certificate = self
+            .certificate_provider
+            .certificate(record.signing_cert_url.as_str())
+            .await
+            .map_err(VerificationError::CertificateUnavailable)?;
+        verify_signature(record, certificate.as_str()).map_err(VerificationError::NotVerified)
+    }
+}
+
+/// The DNS suffix of the endpoints in the given partition of AWS (the second
+/// field of an ARN)
+fn dns_suffix(partition: &str) -> Option<&'static str> {
+    match partition {
+        "aws" | "aws-us-gov" => Some("amazonaws.com"),
+        "aws-cn" => Some("amazonaws.com.cn"),
+        _ => None,
+    }
+}
+
+/// Only a certificate that SNS itself serves, from
+/// `https://sns.<region>.<dns suffix>/` where the region is the region of the
+/// topic and the DNS suffix the one of its partition (`amazonaws.com`, or
+/// `amazonaws.com.cn` in China), can be trusted
+pub fn check_signing_cert_url(signing_cert_url: &str, topic_arn: &str) -> Result<(), Error> {
+    let invalid_topic_arn = || Error::ServiceError(format!("Invalid topic ARN {}", topic_arn));
+    let mut fields = topic_arn.split(':');
+    if fields.next() != Some("arn") {
+        return Err(invalid_topic_arn());
+    }
+    let dns_suffix = fields
+        .next()
+        .and_then(dns_suffix)
+        .ok_or_else(invalid_topic_arn)?;
+    let region = fields
+        .nth(1)
+        .filter(|region| {
+            !region.is_empty() &&
+                region
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+        .ok_or_else(invalid_topic_arn)?;
+    let expected_host = format!("sns.{}.{}", region, dns_suffix);
+    let trusted = signing_cert_url
+        .strip_prefix("https://")
+        .and_then(|rest| rest.split_once('/'))
+        .is_some_and(|(host, path)| {
+            host == expected_host && path.ends_with(".pem") && !path.contains(['?', '#'])
+        });
+    if trusted {
+        Ok(())
+    } else {
+        Err(Error::ServiceError(format!(
+            "Untrusted signing certificate URL {} (expected https://{}/...pem)",
+            signing_cert_url, expected_host
+        )))
+    }
+}
+
+/// The string that SNS signs for a notification: the name and the value of
+/// each of its fields on their own line, in this order, leaving out the
+/// subject if there is none
+pub fn string_to_sign(record: &SnsRecord) -> Result<String, Error> {
+    if record.type_ != "Notification" {
+        return Err(Error::ServiceError(format!(
+            "Cannot verify SNS message {} of type {}",
+            record.message_id, record.type_
+        )));
+    }
+    let mut fields = vec![
+        ("Message", record.message.as_str()),
+        ("MessageId", record.message_id.as_str()),
+    ];
+    if let Some(subject) = &record.subject {
+        fields.push(("Subject", subject.as_str()));
+    }
+    fields.extend([
+        ("Timestamp", record.timestamp.as_str()),
+        ("TopicArn", record.topic_arn.as_str()),
+        ("Type", record.type_.as_str()),
+    ]);
+    Ok(fields
+        .into_iter()
+        .map(|(name, value)| format!("{}\n{}\n", name, value))
+        .collect())
+}
+
+/// Check the signature of the given notification, SignatureVersion 1 (SHA1)
+/// or 2 (SHA256), with the given (PEM encoded) certificate
+pub fn verify_signature(record: &SnsRecord, certificate: &str) -> Result<(), Error> {
+    let certificate = Certificate::from_pem(certificate.as_bytes())
+        .map_err(|error| not_verified(record, error))?;
+    let validity = &certificate.tbs_certificate.validity;
+    let now = SystemTime::now();
+    if now < validity.not_before.to_system_time() || now > validity.not_after.to_system_time() {
+        return Err(not_verified(
+            record,
+            "the signing certificate has expired or is not valid yet",
+        ));
+    }
+    let public_key = certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .map_err(|error| not_verified(record, error))
+        .and_then(|der| {
+            RsaPublicKey::from_public_key_der(der.as_slice())
+                .map_err(|error| not_verified(record, error))
+        })?;
+    let signature = STANDARD
+        .decode(record.signature.as_bytes())
+        .map_err(|error| not_verified(record, error))?;
+    let signature =
+        Signature::try_from(signature.as_slice()).map_err(|error| not_verified(record, error))?;
+    let string_to_sign = string_to_sign(record)?;
+    let verified = match record.signature_version.as_str() {
+        "1" => VerifyingKey::<Sha1>::new(public_key).verify(string_to_sign.as_bytes(), &signature),
+        "2" => {
+            VerifyingKey::<Sha256>::new(public_key).verify(string_to_sign.as_bytes(), &signature)
+        },
+        other => {
+            return Err(not_verified(
+                record,
+                format!("unsupported SignatureVersion {}", other),
+            ))
+        },
+    };
+    verified.map_err(|_| not_verified(record, "invalid signature"))
+}
+
+fn not_verified(record: &SnsRecord, reason: impl std::fmt::Display) -> Error {
+    Error::ServiceError(format!(
+        "Could not verify SNS message {}: {}",
+        record.message_id, reason
+    ))
+}
@@ -0,0 +1,150 @@
+//! A few typed building blocks for the SPARQL statements that the lambda
+//! functions generate.
+//!
+//! Anything that ends up in a SPARQL statement and that is not a constant,
+//! such as an S3 key or a payload returned by Neptune, should be wrapped in an
+//! [`Iri`] or a [`Literal`] and interpolated via its [`Display`]
+//! implementation, which takes care of the `<...>` or `"..."` delimiters and of
+//! the escaping. That way an S3 key with a quote or a `>` in it can never break
+//! (or inject anything into) a SPARQL update.
+use {
+    ekg_error::Error,
+    std::fmt::{Display, Formatter},
+};
+
+/// The XML Schema datatypes namespace, used for typed literals
+pub const NS_XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// An absolute IRI that can safely be interpolated in a SPARQL statement, its
+/// [`Display`] implementation renders it as `<iri>`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Iri(String);
+
+impl Iri {
+    /// Validate the given IRI against the `IRIREF` production of the SPARQL
+    /// grammar (without escape sequences) and check that it has a scheme.
+    pub fn new(iri: impl Into<String>) -> Result<Self, Error> {
+        let iri = iri.into();
+        if let Some(character) = iri.chars().find(|c| !is_iri_char(*c)) {
+            return Err(Error::ServiceError(format!(
+                "Invalid IRI {:?}: character {:?} is not allowed",
+                iri, character
+            )));
+        }
+        if !has_scheme(iri.as_str()) {
+            return Err(Error::ServiceError(format!(
+                "Invalid IRI {:?}: not an absolute IRI",
+                iri
+            )));
+        }
+        Ok(Self(iri))
+    }
+
+    pub fn as_str(&self) -> &str { self.0.as_str() }
+}
+
+impl Display for Iri {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f, "<{}>", self.0) }
+}
+
+impl TryFrom<&str> for Iri {
+    type Error = Error;
+
+    fn try_from(iri: &str) -> Result<Self, Self::Error> { Self::new(iri) }
+}
+
+impl TryFrom<String> for Iri {
+    type Error = Error;
+
+    fn try_from(iri: String) -> Result<Self, Self::Error> { Self::new(iri) }
+}
+
+/// A (possibly typed) literal that can safely be interpolated in a SPARQL
+/// statement, its [`Display`] implementation renders it as `"value"` or
+/// `"value"^^<datatype>` with all special characters escaped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Literal {
+    value:    String,
+    datatype: Option<&'static str>,
+}
+
+impl Literal {
+    /// A plain `xsd:string` literal
+    pub fn string(value: impl Into<String>) -> Self { Self { value: value.into(), datatype: None } }
+
+    pub fn integer(value: u64) -> Self {
+        Self {
+            value:    value.to_string(),
+            datatype: Some("integer"),
+        }
+    }
+
+    /// An `xsd:dateTime` literal, the value has to be in its lexical form
+    /// already (such as `2023-09-18T10:03:16Z`)
+    pub fn date_time(value: impl Into<String>) -> Self {
+        Self { value: value.into(), datatype: Some("dateTime") }
+    }
+
+    /// An `xsd:duration` literal, the value has to be in its lexical form
+    /// already (such as `PT42S`)
+    pub fn duration(value: impl Into<String>) -> Self {
+        Self { value: value.into(), datatype: Some("duration") }
+    }
+
+    pub fn value(&self) -> &str { self.value.as_str() }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", escape_string(self.value.as_str()))?;
+        if let Some(datatype) = self.datatype {
+            write!(f, "^^<{}{}>", NS_XSD, datatype)?;
+        }
+        Ok(())
+    }
+}
+
+/// Escape the given value for use between double quotes, see the `ECHAR` and
+/// `STRING_LITERAL2` productions of the SPARQL grammar.
+///
+/// SPARQL processors are supposed to replace `\uXXXX` sequences before they
+/// parse the statement, so a `u` (or `U`) that follows a backslash is written
+/// as a codepoint escape itself, otherwise `\\u0022` would turn into an
+/// escaped quote.
+pub fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let mut after_backslash = false;
+    for character in value.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            'u' if after_backslash => escaped.push_str("\\u0075"),
+            'U' if after_backslash => escaped.push_str("\\u0055"),
+            character => escaped.push(character),
+        }
+        after_backslash = character == '\\';
+    }
+    escaped
+}
+
+/// See the `IRIREF` production of the SPARQL grammar, a backslash is not
+/// allowed either since we do not support escape sequences in IRIs.
+fn is_iri_char(character: char) -> bool {
+    !matches!(
+        character,
+        '\u{0}'..='\u{20}' | '<' | '>' | '"' | '{' | '}' | '|' | '^' | '`' | '\\'
+    )
+}
+
+/// An IRI (as opposed to a relative reference) starts with a scheme followed
+/// by a colon, for instance `s3:` or `https:`.
+fn has_scheme(iri: &str) -> bool {
+    let Some((scheme, _)) = iri.split_once(':') else {
+        return false;
+    };
+    let mut characters = scheme.chars();
+    characters.next().is_some_and(|c| c.is_ascii_alphabetic()) &&
+        characters.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
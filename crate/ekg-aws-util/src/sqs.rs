@@ -0,0 +1,38 @@
+use {
+    serde::{Deserialize, Serialize},
+    serde_json::Value,
+};
+
+/// A message of an SQS batch, its body is whatever was sent to the queue: an
+/// S3 event notification, an SNS notification or an EventBridge event.
+///
+/// See https://docs.aws.amazon.com/lambda/latest/dg/with-sqs.html
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SqsEventRecord {
+    pub message_id:         String,
+    pub receipt_handle:     String,
+    pub body:               String,
+    #[serde(default)]
+    pub attributes:         Value,
+    #[serde(default)]
+    pub message_attributes: Value,
+    #[serde(default)]
+    pub md5_of_body:        String,
+    pub event_source:       String,
+    #[serde(rename = "eventSourceARN")]
+    pub event_source_arn:   String,
+    pub aws_region:         String,
+}
+
+/// A message of an SQS batch that failed, only the failed messages of a batch
+/// become visible in the queue again (if the event source mapping has
+/// `ReportBatchItemFailures` enabled).
+///
+/// See https://docs.aws.amazon.com/lambda/latest/dg/services-sqs-errorhandling.html#services-sqs-batchfailurereporting
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SqsBatchItemFailure {
+    /// The message ID of the failed message
+    pub item_identifier: String,
+}
@@ -1 +1,1781 @@
 #![cfg(test)]
+
+use {
+    crate::{
+        lambda::{Jitter, LambdaDetailStatus, LambdaResponse, RetryPolicy, Retryability},
+        neptune::{
+            document_to_json,
+            is_rdf_format,
+            json_to_document,
+            resolve_format,
+            GraphNamingStrategy,
+            LoadRequest,
+            LoadStrategy,
+            LoaderJobFeedStatus,
+            LoaderJobStatus,
+            ENV_LOAD_STRATEGY,
+        },
+        placeholder::{staged_s3_uri, PlaceholderRewriter},
+        routing::RoutingTable,
+        s3::{encode_key, parse_s3_uri, s3_uri_to_iri},
+        sns::{
+            check_signing_cert_url,
+            string_to_sign,
+            verify_signature,
+            CertificateProvider,
+            SnsSignatureVerifier,
+        },
+        sparql::{Iri, Literal},
+        EventBridgeEvent,
+        S3EventName,
+        S3EventRecord,
+        S3EventRecords,
+        SnsRecord,
+    },
+    aws_sdk_neptunedata::{
+        error::{ErrorMetadata, SdkError},
+        operation::{
+            get_loader_job_status::GetLoaderJobStatusError,
+            start_loader_job::StartLoaderJobError,
+        },
+        types::{
+            error::{BadRequestException, S3Exception},
+            Format,
+        },
+    },
+    aws_smithy_runtime_api::{
+        client::result::ConnectorError,
+        http::{Response as HttpResponse, StatusCode},
+    },
+    aws_smithy_types::{body::SdkBody, Document, Number},
+    base64::{engine::general_purpose::STANDARD, Engine},
+    ekg_error::Error,
+    ekg_identifier::EkgIdentifierContexts,
+    proptest::prelude::*,
+    rsa::{
+        pkcs1v15::SigningKey,
+        pkcs8::DecodePrivateKey,
+        signature::{SignatureEncoding, Signer},
+        RsaPrivateKey,
+    },
+    sha1::Sha1,
+    sha2::Sha256,
+    spargebra::{
+        term::{Quad, Subject, Term},
+        GraphUpdateOperation,
+    },
+    std::{collections::HashMap, future::Future, io::Write},
+};
+
+#[test_log::test]
+fn test_resolve_format_from_key() {
+    let cases = [
+        ("ontology/cdmc-data-use.ttl", Some(Format::Turtle)),
+        ("ontology/CDMC-DATA-USE.TTL", Some(Format::Turtle)),
+        ("dataset/persons.nt", Some(Format::Ntriples)),
+        ("dataset/persons.nt.gz", Some(Format::Ntriples)),
+        ("dataset/persons.nq.bz2", Some(Format::Nquads)),
+        ("dataset/persons.rdf", Some(Format::Rdfxml)),
+        (
+            "dataset/v1.2/persons.ttl.gz",
+            Some(Format::Turtle),
+        ),
+        ("dataset/persons.trig", None),
+        ("dataset/persons.csv", None),
+        ("dataset/persons.gz", None),
+        ("dataset/persons", None),
+        ("dataset.ttl/persons", None),
+    ];
+    for (key, expected) in cases {
+        assert_eq!(resolve_format(key, None), expected, "key {}", key);
+    }
+}
+
+#[test_log::test]
+fn test_resolve_format_from_content_type() {
+    assert_eq!(
+        resolve_format("dataset/persons", Some("application/n-triples")),
+        Some(Format::Ntriples)
+    );
+    assert_eq!(
+        resolve_format(
+            "dataset/persons.ttl",
+            Some("application/rdf+xml; charset=utf-8")
+        ),
+        Some(Format::Rdfxml)
+    );
+    // Generic content types fall back to the extension
+    assert_eq!(
+        resolve_format(
+            "dataset/persons.nq.gz",
+            Some("binary/octet-stream")
+        ),
+        Some(Format::Nquads)
+    );
+    assert_eq!(
+        resolve_format("dataset/persons", Some("application/trig")),
+        None
+    );
+}
+
+#[test_log::test]
+fn test_is_rdf_format() {
+    assert!(is_rdf_format(&Format::Turtle));
+    assert!(!is_rdf_format(&Format::Csv));
+    assert!(!is_rdf_format(&Format::Opencypher));
+}
+
+#[test_log::test]
+fn test_unsupported_rdf_format_response() {
+    let response = LambdaResponse::unsupported_rdf_format("s3://bucket/persons.trig");
+    assert_eq!(response.status_code, 400);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::UnsupportedRdfFormat
+    ));
+    assert!(!response.detail_status.is_retryable());
+}
+
+#[test_log::test]
+fn test_s3_event_name() {
+    let event_name = serde_json::from_str::<S3EventName>(r#""ObjectRemoved:Delete""#).unwrap();
+    assert_eq!(event_name, S3EventName::ObjectRemovedDelete);
+    assert!(event_name.is_object_removed());
+    assert!(!event_name.is_object_created());
+
+    let event_name = S3EventName::from("s3:ObjectCreated:CompleteMultipartUpload");
+    assert_eq!(
+        event_name,
+        S3EventName::ObjectCreatedCompleteMultipartUpload
+    );
+    assert!(event_name.is_object_created());
+
+    let event_name = S3EventName::from("ObjectRestore:Completed");
+    assert_eq!(event_name, S3EventName::ObjectRestoreCompleted);
+    assert_eq!(event_name.to_string(), "ObjectRestore:Completed");
+    assert!(!event_name.is_object_created() && !event_name.is_object_removed());
+
+    // An object that a lifecycle rule expires is gone just the same
+    assert!(S3EventName::from("LifecycleExpiration:Delete").is_object_removed());
+
+    let event_name = S3EventName::from("ObjectArchived:Put");
+    assert_eq!(
+        event_name,
+        S3EventName::Other("ObjectArchived:Put".to_string())
+    );
+    assert_eq!(event_name.to_string(), "ObjectArchived:Put");
+}
+
+#[test_log::test]
+fn test_load_strategy() {
+    assert_eq!(
+        "append".parse::<LoadStrategy>().unwrap(),
+        LoadStrategy::Append
+    );
+    assert_eq!(
+        " Replace ".parse::<LoadStrategy>().unwrap(),
+        LoadStrategy::Replace
+    );
+    assert!("merge".parse::<LoadStrategy>().is_err());
+    assert_eq!(LoadStrategy::default(), LoadStrategy::Append);
+}
+
+#[test_log::test]
+fn test_load_request_staging_graph() {
+    let mut load_request = serde_json::json!({
+        "source": "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl",
+        "format": "turtle",
+        "iamRoleArn": "arn:aws:iam::123456789012:role/ekgf-dt-dev-neptune-load",
+        "mode": "NEW",
+        "region": "antartica-01",
+        "failOnError": "TRUE",
+        "parallelism": "OVERSUBSCRIBE",
+        "parserConfiguration": {
+            "baseUri": "https://placeholder.kg/id/",
+            "namedGraphUri": "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl"
+        },
+        "updateSingleCardinalityProperties": "FALSE",
+        "queueRequest": "TRUE",
+        "dependencies": []
+    });
+
+    // Load requests without a load strategy are appended
+    let append = serde_json::from_value::<LoadRequest>(load_request.clone()).unwrap();
+    assert_eq!(append.load_strategy, LoadStrategy::Append);
+    assert_eq!(append.staging_graph_uri(), None);
+    assert_eq!(
+        append.target_graph_uri(),
+        "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl"
+    );
+
+    load_request["loadStrategy"] = "replace".into();
+    load_request["targetGraphUri"] = "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl".into();
+    load_request["parserConfiguration"]["namedGraphUri"] =
+        "https://kg.example.com/graph/staging/9c3e/0065082063F0F5766D".into();
+    let replace = serde_json::from_value::<LoadRequest>(load_request).unwrap();
+    assert_eq!(replace.load_strategy, LoadStrategy::Replace);
+    assert_eq!(
+        replace.staging_graph_uri(),
+        Some("https://kg.example.com/graph/staging/9c3e/0065082063F0F5766D")
+    );
+    assert_eq!(
+        replace.target_graph_uri(),
+        "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl"
+    );
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var(
+        "AWS_NEPTUNE_LOAD_IAM_ROLE_ARN",
+        "arn:aws:iam::123456789012:role/ekgf-dt-dev-neptune-load",
+    );
+    std::env::set_var("AWS_REGION", "antartica-01");
+
+    // A staging graph is a graph of its own under the graph base, whatever
+    // the IRI of the graph that it replaces
+    std::env::set_var(ENV_LOAD_STRATEGY, "replace");
+    let replace = LoadRequest::from_s3_event_record(
+        &s3_event_record(None, "0065082063F0F5766D"),
+        Format::Turtle,
+        &EkgIdentifierContexts::from_env().unwrap(),
+        None,
+    );
+    std::env::remove_var(ENV_LOAD_STRATEGY);
+    let replace = replace.unwrap();
+    let staging_graph = replace.staging_graph_uri().unwrap();
+    assert!(staging_graph.starts_with(
+        EkgIdentifierContexts::from_env()
+            .unwrap()
+            .internal
+            .ekg_graph_base
+            .as_base_iri()
+            .as_str()
+    ));
+    assert!(staging_graph.ends_with("/0065082063F0F5766D"));
+    assert!(!staging_graph.contains('#'));
+    assert_eq!(
+        replace.target_graph_uri(),
+        "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl"
+    );
+}
+
+fn s3_event_record(version_id: Option<&str>, sequencer: &str) -> S3EventRecord {
+    serde_json::from_value(serde_json::json!({
+        "eventVersion": "2.1",
+        "eventSource": "aws:s3",
+        "awsRegion": "antartica-01",
+        "eventTime": "2023-09-18T10:03:15.979Z",
+        "eventName": "ObjectCreated:Put",
+        "userIdentity": { "principalId": "AWS:AIDAWVGREJ265Q72HOJUP" },
+        "requestParameters": { "sourceIPAddress": "193.237.90.75" },
+        "responseElements": {
+            "x-amz-request-id": "JJ807NMA5B2VMJ0D",
+            "x-amz-id-2": "wSZ0gf3XaMj63uKcY7A43KSJ3fAMm27hZcWZQRTNzhFIq4oaTZ7fO1RaIL35VbG3"
+        },
+        "s3": {
+            "s3SchemaVersion": "1.0",
+            "configurationId": "tf-s3-topic-20230915095940816500000001",
+            "bucket": {
+                "name": "ekgf-dt-dev-metadata",
+                "ownerIdentity": { "principalId": "A1M8OTUP4LUCQC" },
+                "arn": "arn:aws:s3:::ekgf-dt-dev-metadata"
+            },
+            "object": {
+                "key": "ontology/cdmc-data-use.ttl",
+                "size": 1206,
+                "eTag": "455c556f7d1b7f8587ecabe2dd8184af",
+                "versionId": version_id,
+                "sequencer": sequencer
+            }
+        }
+    }))
+    .unwrap()
+}
+
+#[test_log::test]
+fn test_s3_event_record_execution_name() {
+    let record = s3_event_record(
+        Some("LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2"),
+        "0065082063F0F5766D",
+    );
+    assert_eq!(
+        record.s3.object.version(),
+        "LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2"
+    );
+
+    // A redelivery of the same event gets the same execution name, which Step
+    // Functions accepts: at most 80 characters of [A-Za-z0-9-_]
+    let name = record.execution_name();
+    assert_eq!(
+        name,
+        s3_event_record(
+            Some("LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2"),
+            "0065082063F0F5766D"
+        )
+        .execution_name()
+    );
+    assert!(name.starts_with("rdf-load-"));
+    assert!(name.len() <= 80);
+    assert!(name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+    // Another PUT of the same key is another event
+    assert_ne!(
+        name,
+        s3_event_record(
+            Some("LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2"),
+            "0065082063F0F5766E"
+        )
+        .execution_name()
+    );
+    assert_ne!(
+        name,
+        s3_event_record(Some("3HL4kqtJlcpXroDTDmJ"), "0065082063F0F5766D").execution_name()
+    );
+
+    // Without versioning, the eTag is the version
+    let unversioned = s3_event_record(None, "0065082063F0F5766D");
+    assert_eq!(
+        unversioned.s3.object.version(),
+        "455c556f7d1b7f8587ecabe2dd8184af"
+    );
+    assert_ne!(name, unversioned.execution_name());
+}
+
+#[test_log::test]
+fn test_event_bridge_s3_event_record() {
+    let mut event = serde_json::json!({
+        "version": "0",
+        "id": "2ee9cc15-d022-99ea-1fb8-1b1bac4850f9",
+        "detail-type": "Object Deleted",
+        "source": "aws.s3",
+        "account": "123456789012",
+        "time": "2023-09-18T10:03:15Z",
+        "region": "antartica-01",
+        "resources": ["arn:aws:s3:::ekgf-dt-dev-metadata"],
+        "detail": {
+            "version": "0",
+            "bucket": { "name": "ekgf-dt-dev-metadata" },
+            "object": {
+                "key": "ontology/cdmc-data-use.ttl",
+                "version-id": "3HL4kqtJlcpXroDTDmJ",
+                "sequencer": "0065082063F0F5766E"
+            },
+            "request-id": "0BH729840619AG5K",
+            "requester": "123456789012",
+            "source-ip-address": "193.237.90.75",
+            "reason": "DeleteObject",
+            "deletion-type": "Delete Marker Created"
+        }
+    });
+    let record = serde_json::from_value::<EventBridgeEvent>(event.clone())
+        .unwrap()
+        .s3_event_record()
+        .unwrap();
+    assert_eq!(
+        record.event_name,
+        S3EventName::ObjectRemovedDeleteMarkerCreated
+    );
+    assert_eq!(
+        record.s3_uri(),
+        "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl"
+    );
+    assert_eq!(record.s3.object.version(), "3HL4kqtJlcpXroDTDmJ");
+    assert_eq!(
+        record.s3.object.sequencer.as_deref(),
+        Some("0065082063F0F5766E")
+    );
+    assert_eq!(record.aws_region, "antartica-01");
+    assert_eq!(
+        record.s3.bucket.arn,
+        "arn:aws:s3:::ekgf-dt-dev-metadata"
+    );
+
+    event["detail-type"] = "Object Created".into();
+    event["detail"]["reason"] = "CompleteMultipartUpload".into();
+    event["detail"]["object"]["etag"] = "455c556f7d1b7f8587ecabe2dd8184af-2".into();
+    let record = serde_json::from_value::<EventBridgeEvent>(event.clone())
+        .unwrap()
+        .s3_event_record()
+        .unwrap();
+    assert_eq!(
+        record.event_name,
+        S3EventName::ObjectCreatedCompleteMultipartUpload
+    );
+    assert_eq!(
+        record.s3.object.e_tag.as_deref(),
+        Some("455c556f7d1b7f8587ecabe2dd8184af-2")
+    );
+
+    // EventBridge does not URL-encode the key
+    event["detail"]["object"]["key"] = "ontology/data use+.ttl".into();
+    let record = serde_json::from_value::<EventBridgeEvent>(event.clone())
+        .unwrap()
+        .s3_event_record()
+        .unwrap();
+    assert_eq!(record.s3.object.key, "ontology/data+use%2B.ttl");
+    assert_eq!(
+        record.s3_uri(),
+        "s3://ekgf-dt-dev-metadata/ontology/data use+.ttl"
+    );
+
+    // Only S3 events can be turned into S3 event records
+    event["source"] = "aws.ec2".into();
+    assert!(serde_json::from_value::<EventBridgeEvent>(event)
+        .unwrap()
+        .s3_event_record()
+        .is_err());
+}
+
+const SIGNING_CERT_URL: &str =
+    "https://sns.antartica-01.amazonaws.com/SimpleNotificationService-0000000000000000000000.pem";
+
+/// An SNS notification signed with test-data/sns/signing-key.pem, the key of
+/// test-data/sns/signing-cert.pem
+fn signed_sns_record(signature_version: &str, subject: Option<&str>) -> SnsRecord {
+    let mut record = SnsRecord {
+        type_:              "Notification".to_string(),
+        message_id:         "95df01b4-ee98-5cb9-9903-4c221d41eb5e".to_string(),
+        topic_arn:          "arn:aws:sns:antartica-01:123456789012:ekgf-dt-dev-rdf-load"
+            .to_string(),
+        subject:            subject.map(str::to_string),
+        message:            r#"{"Records":[]}"#.to_string(),
+        timestamp:          "2023-09-18T10:03:16.015Z".to_string(),
+        signature_version:  signature_version.to_string(),
+        signature:          String::new(),
+        signing_cert_url:   SIGNING_CERT_URL.to_string(),
+        unsubscribe_url:    String::new(),
+        message_attributes: serde_json::Value::Null,
+    };
+    let private_key =
+        RsaPrivateKey::from_pkcs8_pem(include_str!("../test-data/sns/signing-key.pem")).unwrap();
+    let string_to_sign = string_to_sign(&record).unwrap();
+    let signature = match signature_version {
+        "1" => {
+            SigningKey::<Sha1>::new(private_key)
+                .sign(string_to_sign.as_bytes())
+                .to_bytes()
+        },
+        _ => {
+            SigningKey::<Sha256>::new(private_key)
+                .sign(string_to_sign.as_bytes())
+                .to_bytes()
+        },
+    };
+    record.signature = STANDARD.encode(signature);
+    record
+}
+
+/// Serves the certificates of the test-data directory rather than the ones of
+/// SNS itself
+#[derive(Clone)]
+struct TestCertificateProvider {
+    certificates: HashMap<String, String>,
+}
+
+impl TestCertificateProvider {
+    fn new(certificate: &str) -> Self {
+        Self {
+            certificates: HashMap::from([(
+                SIGNING_CERT_URL.to_string(),
+                certificate.to_string(),
+            )]),
+        }
+    }
+}
+
+impl CertificateProvider for TestCertificateProvider {
+    fn certificate(&self, url: &str) -> impl Future<Output = Result<String, Error>> + Send {
+        let certificate = self
+            .certificates
+            .get(url)
+            .cloned()
+            .ok_or_else(|| Error::ServiceError(format!("No certificate at {}", url)));
+        async move { certificate }
+    }
+}
+
+#[test_log::test]
+fn test_sns_string_to_sign() {
+    let record = signed_sns_record("2", Some("Amazon S3 Notification"));
+    assert_eq!(
+        string_to_sign(&record).unwrap(),
+        concat!(
+            "Message\n",
+            "{\"Records\":[]}\n",
+            "MessageId\n",
+            "95df01b4-ee98-5cb9-9903-4c221d41eb5e\n",
+            "Subject\n",
+            "Amazon S3 Notification\n",
+            "Timestamp\n",
+            "2023-09-18T10:03:16.015Z\n",
+            "TopicArn\n",
+            "arn:aws:sns:antartica-01:123456789012:ekgf-dt-dev-rdf-load\n",
+            "Type\n",
+            "Notification\n",
+        )
+    );
+    // Without a subject, there's no Subject line at all
+    let record = signed_sns_record("2", None);
+    assert!(!string_to_sign(&record).unwrap().contains("Subject"));
+
+    let mut record = signed_sns_record("2", None);
+    record.type_ = "SubscriptionConfirmation".to_string();
+    assert!(string_to_sign(&record).is_err());
+}
+
+#[test_log::test]
+fn test_sns_check_signing_cert_url() {
+    let topic_arn = "arn:aws:sns:antartica-01:123456789012:ekgf-dt-dev-rdf-load";
+    assert!(check_signing_cert_url(SIGNING_CERT_URL, topic_arn).is_ok());
+    for url in [
+        "http://sns.antartica-01.amazonaws.com/SimpleNotificationService-0.pem",
+        "https://sns.us-east-1.amazonaws.com/SimpleNotificationService-0.pem",
+        "https://sns.antartica-01.amazonaws.com.example.com/SimpleNotificationService-0.pem",
+        "https://sns.antartica-01.amazonaws.com@example.com/SimpleNotificationService-0.pem",
+        "https://example.com/sns.antartica-01.amazonaws.com/SimpleNotificationService-0.pem",
+        "https://sns.antartica-01.amazonaws.com/SimpleNotificationService-0.txt",
+        "https://sns.antartica-01.amazonaws.com/redirect?to=example.com/cert.pem",
+    ] {
+        assert!(
+            check_signing_cert_url(url, topic_arn).is_err(),
+            "{} should not be trusted",
+            url
+        );
+    }
+    assert!(check_signing_cert_url(SIGNING_CERT_URL, "arn:aws:sns:../x:1:topic").is_err());
+    assert!(check_signing_cert_url(SIGNING_CERT_URL, "not-an-arn").is_err());
+
+    // The DNS suffix of SNS depends on the partition of the topic
+    let topic_arn = "arn:aws-cn:sns:cn-north-1:123456789012:ekgf-dt-dev-rdf-load";
+    assert!(check_signing_cert_url(
+        "https://sns.cn-north-1.amazonaws.com.cn/SimpleNotificationService-0.pem",
+        topic_arn
+    )
+    .is_ok());
+    assert!(check_signing_cert_url(
+        "https://sns.cn-north-1.amazonaws.com/SimpleNotificationService-0.pem",
+        topic_arn
+    )
+    .is_err());
+    assert!(check_signing_cert_url(
+        "https://sns.us-gov-west-1.amazonaws.com/SimpleNotificationService-0.pem",
+        "arn:aws-us-gov:sns:us-gov-west-1:123456789012:ekgf-dt-dev-rdf-load"
+    )
+    .is_ok());
+    assert!(check_signing_cert_url(
+        "https://sns.antartica-01.amazonaws.com.cn/SimpleNotificationService-0.pem",
+        "arn:aws:sns:antartica-01:123456789012:ekgf-dt-dev-rdf-load"
+    )
+    .is_err());
+    assert!(check_signing_cert_url(
+        SIGNING_CERT_URL,
+        "arn:example:sns:antartica-01:123456789012:ekgf-dt-dev-rdf-load"
+    )
+    .is_err());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_sns_signature() {
+    let verifier = SnsSignatureVerifier::new(TestCertificateProvider::new(include_str!(
+        "../test-data/sns/signing-cert.pem"
+    )));
+    for signature_version in ["1", "2"] {
+        for subject in [Some("Amazon S3 Notification"), None] {
+            let record = signed_sns_record(signature_version, subject);
+            verifier.verify(&record).await.unwrap();
+        }
+    }
+
+    // A message that has been tampered with, delivering it again won't help
+    let mut record = signed_sns_record("2", None);
+    record.message = r#"{"Records":[{}]}"#.to_string();
+    assert!(!verifier.verify(&record).await.unwrap_err().is_retryable());
+
+    // A certificate that could not be fetched, maybe it can be next time
+    let mut record = signed_sns_record("2", None);
+    record.signing_cert_url =
+        "https://sns.antartica-01.amazonaws.com/SimpleNotificationService-1.pem".to_string();
+    assert!(verifier.verify(&record).await.unwrap_err().is_retryable());
+
+    // A signature of the right message, but for the other signature version
+    let mut record = signed_sns_record("1", None);
+    record.signature_version = "2".to_string();
+    assert!(verifier.verify(&record).await.is_err());
+
+    let mut record = signed_sns_record("2", None);
+    record.signature_version = "3".to_string();
+    assert!(verifier.verify(&record).await.is_err());
+
+    // A certificate URL outside of SNS is never even requested
+    let mut record = signed_sns_record("2", None);
+    record.signing_cert_url = "https://example.com/SimpleNotificationService-0.pem".to_string();
+    assert!(!verifier.verify(&record).await.unwrap_err().is_retryable());
+
+    // A certificate of another key
+    let record = signed_sns_record("2", None);
+    let verifier = SnsSignatureVerifier::new(TestCertificateProvider::new(include_str!(
+        "../test-data/sns/other-cert.pem"
+    )));
+    assert!(verifier.verify(&record).await.is_err());
+
+    // A certificate of the right key that has expired
+    assert!(verify_signature(
+        &record,
+        include_str!("../test-data/sns/expired-cert.pem")
+    )
+    .is_err());
+    assert!(verify_signature(
+        &record,
+        include_str!("../test-data/sns/signing-cert.pem")
+    )
+    .is_ok());
+    assert!(verify_signature(&record, "not a certificate").is_err());
+}
+
+/// One S3 event notification of every documented event type, see
+/// https://docs.aws.amazon.com/AmazonS3/latest/userguide/notification-how-to-event-types-and-destinations.html
+const S3_EVENT_FIXTURES: [(&str, S3EventName); 21] = [
+    (
+        include_str!("../test-data/s3-events/object-created-put.json"),
+        S3EventName::ObjectCreatedPut,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-created-post.json"),
+        S3EventName::ObjectCreatedPost,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-created-copy.json"),
+        S3EventName::ObjectCreatedCopy,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-created-complete-multipart-upload.json"),
+        S3EventName::ObjectCreatedCompleteMultipartUpload,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-removed-delete.json"),
+        S3EventName::ObjectRemovedDelete,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-removed-delete-marker-created.json"),
+        S3EventName::ObjectRemovedDeleteMarkerCreated,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-restore-post.json"),
+        S3EventName::ObjectRestorePost,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-restore-completed.json"),
+        S3EventName::ObjectRestoreCompleted,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-restore-delete.json"),
+        S3EventName::ObjectRestoreDelete,
+    ),
+    (
+        include_str!("../test-data/s3-events/reduced-redundancy-lost-object.json"),
+        S3EventName::ReducedRedundancyLostObject,
+    ),
+    (
+        include_str!("../test-data/s3-events/replication-operation-failed-replication.json"),
+        S3EventName::ReplicationOperationFailedReplication,
+    ),
+    (
+        include_str!("../test-data/s3-events/replication-operation-missed-threshold.json"),
+        S3EventName::ReplicationOperationMissedThreshold,
+    ),
+    (
+        include_str!(
+            "../test-data/s3-events/replication-operation-replicated-after-threshold.json"
+        ),
+        S3EventName::ReplicationOperationReplicatedAfterThreshold,
+    ),
+    (
+        include_str!("../test-data/s3-events/replication-operation-not-tracked.json"),
+        S3EventName::ReplicationOperationNotTracked,
+    ),
+    (
+        include_str!("../test-data/s3-events/lifecycle-expiration-delete.json"),
+        S3EventName::LifecycleExpirationDelete,
+    ),
+    (
+        include_str!("../test-data/s3-events/lifecycle-expiration-delete-marker-created.json"),
+        S3EventName::LifecycleExpirationDeleteMarkerCreated,
+    ),
+    (
+        include_str!("../test-data/s3-events/lifecycle-transition.json"),
+        S3EventName::LifecycleTransition,
+    ),
+    (
+        include_str!("../test-data/s3-events/intelligent-tiering.json"),
+        S3EventName::IntelligentTiering,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-tagging-put.json"),
+        S3EventName::ObjectTaggingPut,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-tagging-delete.json"),
+        S3EventName::ObjectTaggingDelete,
+    ),
+    (
+        include_str!("../test-data/s3-events/object-acl-put.json"),
+        S3EventName::ObjectAclPut,
+    ),
+];
+
+fn parse_s3_event_fixture(fixture: &str) -> S3EventRecord {
+    let mut records = serde_json::from_str::<S3EventRecords>(fixture)
+        .unwrap()
+        .records;
+    assert_eq!(records.len(), 1);
+    records.remove(0)
+}
+
+#[test_log::test]
+fn test_s3_event_fixtures() {
+    for (fixture, event_name) in S3_EVENT_FIXTURES {
+        let record = parse_s3_event_fixture(fixture);
+        assert_eq!(record.event_name, event_name);
+        assert_eq!(S3EventName::from(event_name.as_str()), event_name);
+        assert!(!matches!(event_name, S3EventName::Other(_)));
+        assert_eq!(
+            record.glacier_event_data.is_some(),
+            event_name == S3EventName::ObjectRestoreCompleted
+        );
+        assert_eq!(
+            record.replication_event_data.is_some(),
+            event_name.as_str().starts_with("Replication:")
+        );
+    }
+
+    let record = parse_s3_event_fixture(S3_EVENT_FIXTURES[4].0);
+    assert_eq!(record.s3.object.size, None);
+    assert_eq!(record.s3.object.e_tag, None);
+    assert_eq!(record.s3.object.version(), "");
+    // An object without a version has no source version, rather than an empty
+    // one that would match every other object without a version
+    EkgIdentifierContexts::default_test();
+    std::env::set_var(
+        "AWS_NEPTUNE_LOAD_IAM_ROLE_ARN",
+        "arn:aws:iam::123456789012:role/ekgf-dt-dev-neptune-load",
+    );
+    std::env::set_var("AWS_REGION", "antartica-01");
+    let load_request = LoadRequest::from_s3_event_record(
+        &record,
+        Format::Turtle,
+        &EkgIdentifierContexts::from_env().unwrap(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(load_request.source_version, None);
+
+    let record = parse_s3_event_fixture(S3_EVENT_FIXTURES[7].0);
+    assert_eq!(record.s3.object.sequencer, None);
+    let restore_event_data = record.glacier_event_data.unwrap().restore_event_data;
+    assert_eq!(
+        restore_event_data.lifecycle_restoration_expiry_time,
+        "2023-09-20T00:00:00.000Z"
+    );
+    assert_eq!(
+        restore_event_data.lifecycle_restore_storage_class,
+        "Standard"
+    );
+
+    let record = parse_s3_event_fixture(S3_EVENT_FIXTURES[10].0);
+    assert_eq!(
+        record
+            .replication_event_data
+            .unwrap()
+            .failure_reason
+            .as_deref(),
+        Some("AssumeRoleNotPermitted")
+    );
+
+    let record = parse_s3_event_fixture(S3_EVENT_FIXTURES[16].0);
+    assert_eq!(
+        record
+            .lifecycle_event_data
+            .unwrap()
+            .transition_event_data
+            .destination_storage_class,
+        "GLACIER"
+    );
+
+    let record = parse_s3_event_fixture(S3_EVENT_FIXTURES[17].0);
+    assert_eq!(
+        record
+            .intelligent_tiering_event_data
+            .unwrap()
+            .destination_access_tier,
+        "ARCHIVE_ACCESS"
+    );
+}
+
+#[test_log::test]
+fn test_s3_object_decoded_key() {
+    let record = parse_s3_event_fixture(S3_EVENT_FIXTURES[0].0);
+    assert_eq!(
+        record.s3.object.key,
+        "ontology/data+use+%C3%A9%C3%A9n+%2B+twee.ttl"
+    );
+    assert_eq!(
+        record.s3.object.decoded_key(),
+        "ontology/data use één + twee.ttl"
+    );
+    assert_eq!(
+        encode_key(record.s3.object.decoded_key().as_str()),
+        record.s3.object.key
+    );
+    // The loader reads the object as it is in the bucket, the graph gets an IRI
+    assert_eq!(
+        record.s3_uri(),
+        "s3://ekgf-dt-dev-metadata/ontology/data use één + twee.ttl"
+    );
+    assert_eq!(
+        record.s3_iri(),
+        "s3://ekgf-dt-dev-metadata/ontology/data%20use%20één%20+%20twee.ttl"
+    );
+    assert!(Iri::new(record.s3_iri()).is_ok());
+    assert_eq!(
+        s3_uri_to_iri("s3://ekgf-dt-dev-metadata/100%<done>.ttl"),
+        "s3://ekgf-dt-dev-metadata/100%25%3Cdone%3E.ttl"
+    );
+
+    // A key without special characters is the same in every form
+    let record = s3_event_record(None, "0065082063F0F5766D");
+    assert_eq!(
+        record.s3.object.decoded_key(),
+        record.s3.object.key
+    );
+    assert_eq!(record.s3_iri(), record.s3_uri());
+}
+
+const ROUTING_TABLE: &str = r#"
+    [pipelines]
+    ontology = "arn:aws:states:antartica-01:123456789012:stateMachine:ontology"
+
+    [[rule]]
+    glob = "**/*.draft.ttl"
+    skip = true
+
+    [[rule]]
+    prefix = "ontology/"
+    pipeline = "ontology"
+    graph = "{graph_base}ontology/{stem}"
+
+    [[rule]]
+    prefix = "static-dataset/"
+    glob = "static-dataset/*/*.nt"
+    format = "turtle"
+"#;
+
+#[test_log::test]
+fn test_routing_table_route() {
+    let routing_table = RoutingTable::parse(ROUTING_TABLE).unwrap();
+    assert_eq!(
+        routing_table.state_machine_arn("ontology"),
+        Some("arn:aws:states:antartica-01:123456789012:stateMachine:ontology")
+    );
+    assert_eq!(routing_table.state_machine_arn("test"), None);
+
+    // The first rule that matches applies
+    let rule = routing_table
+        .route("ontology/cdmc-data-use.draft.ttl")
+        .unwrap();
+    assert!(rule.skip);
+    let rule = routing_table.route("ontology/cdmc-data-use.ttl").unwrap();
+    assert!(!rule.skip);
+    assert_eq!(rule.pipeline.as_deref(), Some("ontology"));
+
+    // Given a prefix and a glob, both have to match, and a * does not match a /
+    let rule = routing_table
+        .route("static-dataset/personas/persons.nt")
+        .unwrap();
+    assert_eq!(rule.format, Some(Format::Turtle));
+    assert!(routing_table
+        .route("static-dataset/personas/2023/persons.nt")
+        .is_none());
+    assert!(routing_table.route("dataset/personas/persons.nt").is_none());
+
+    // JSON works just as well
+    let routing_table = RoutingTable::parse(
+        r#"{ "rules": [ { "prefix": "ontology/", "base_uri": "https://kg.example.com/id/" } ] }"#,
+    )
+    .unwrap();
+    assert!(routing_table
+        .route("ontology/cdmc-data-use.ttl")
+        .unwrap()
+        .base_uri
+        .is_some());
+    assert!(RoutingTable::default()
+        .route("ontology/cdmc-data-use.ttl")
+        .is_none());
+}
+
+#[test_log::test]
+fn test_routing_table_invalid() {
+    for routing_table in [
+        // A rule that applies to nothing
+        "[[rule]]\nskip = true",
+        "[[rule]]\nglob = \"ontology/[\"",
+        "[[rule]]\nprefix = \"ontology/\"\nformat = \"csv\"",
+        "[[rule]]\nprefix = \"ontology/\"\ngraph = \"{graph_base}{file}\"",
+        "[[rule]]\nprefix = \"ontology/\"\ngraph = \"{graph_base\"",
+        "[[rule]]\nprefix = \"ontology/\"\npipeline = \"\"",
+    ] {
+        assert!(
+            RoutingTable::parse(routing_table).is_err(),
+            "{}",
+            routing_table
+        );
+    }
+}
+
+#[test_log::test]
+fn test_routing_rule_graph_iri() {
+    EkgIdentifierContexts::default_test();
+    let identifier_contexts = EkgIdentifierContexts::from_env().unwrap();
+    let graph_base = identifier_contexts.internal.ekg_graph_base.as_base_iri();
+    let routing_table = RoutingTable::parse(
+        r#"
+        [[rule]]
+        prefix = "ontology/"
+        graph = "{graph_base}{bucket}/{prefix}/{stem}"
+
+        [[rule]]
+        prefix = "static-dataset/"
+    "#,
+    )
+    .unwrap();
+
+    let mut record = s3_event_record(None, "0065082063F0F5766D");
+    record.s3.object.key = "ontology/data+use.ttl".to_string();
+    let rule = routing_table.route("ontology/data use.ttl").unwrap();
+    assert_eq!(
+        rule.graph_iri(&record, &identifier_contexts).unwrap(),
+        Some(format!(
+            "{}ekgf-dt-dev-metadata/ontology/data%20use",
+            graph_base
+        ))
+    );
+
+    // Without a graph template, the load request falls back to the graph
+    // naming strategy of the pipeline
+    let rule = routing_table.route("static-dataset/persons.ttl").unwrap();
+    assert_eq!(
+        rule.graph_iri(&record, &identifier_contexts).unwrap(),
+        None
+    );
+}
+
+#[test_log::test]
+fn test_graph_naming_strategy() {
+    EkgIdentifierContexts::default_test();
+    let identifier_contexts = EkgIdentifierContexts::from_env().unwrap();
+    let graph_base = identifier_contexts.internal.ekg_graph_base.as_base_iri();
+    let mut record = s3_event_record(None, "0065082063F0F5766D");
+    record.s3.object.key = "ontology/data+use.ttl".to_string();
+
+    assert_eq!(
+        " S3-URI ".parse::<GraphNamingStrategy>().unwrap(),
+        GraphNamingStrategy::S3Uri
+    );
+    assert_eq!(
+        GraphNamingStrategy::default()
+            .graph_iri(&record, &identifier_contexts)
+            .unwrap(),
+        "s3://ekgf-dt-dev-metadata/ontology/data%20use.ttl"
+    );
+
+    // The template leaves the bucket out of the graph IRI
+    let template = "{graph_base}{prefix}/{stem}"
+        .parse::<GraphNamingStrategy>()
+        .unwrap();
+    assert_eq!(
+        template,
+        GraphNamingStrategy::Template("{graph_base}{prefix}/{stem}".to_string())
+    );
+    assert_eq!(
+        template.graph_iri(&record, &identifier_contexts).unwrap(),
+        format!("{}ontology/data%20use", graph_base)
+    );
+
+    // The hash only depends on the key, not on the bucket
+    let hash = "hash".parse::<GraphNamingStrategy>().unwrap();
+    let graph_iri = hash.graph_iri(&record, &identifier_contexts).unwrap();
+    assert_eq!(graph_iri.len(), graph_base.len() + 64);
+    assert!(graph_iri.starts_with(graph_base.as_str()));
+    assert_eq!(
+        "{graph_base}{hash}"
+            .parse::<GraphNamingStrategy>()
+            .unwrap()
+            .graph_iri(&record, &identifier_contexts)
+            .unwrap(),
+        graph_iri
+    );
+    record.s3.bucket.name = "ekgf-dt-prd-metadata".to_string();
+    assert_eq!(
+        hash.graph_iri(&record, &identifier_contexts).unwrap(),
+        graph_iri
+    );
+    record.s3.object.key = "ontology/data+use.nt".to_string();
+    assert_ne!(
+        hash.graph_iri(&record, &identifier_contexts).unwrap(),
+        graph_iri
+    );
+
+    // The stem only loses the RDF and compression extensions, and a key at the
+    // root of the bucket has no prefix and no slash after it
+    record.s3.object.key = "ontology/data+use.v2.nt.gz".to_string();
+    assert_eq!(
+        template.graph_iri(&record, &identifier_contexts).unwrap(),
+        format!("{}ontology/data%20use.v2", graph_base)
+    );
+    record.s3.object.key = "data.csv.ttl".to_string();
+    assert_eq!(
+        template.graph_iri(&record, &identifier_contexts).unwrap(),
+        format!("{}data.csv", graph_base)
+    );
+
+    // A template has to render an absolute IRI
+    for invalid in [
+        "uuid",
+        "{graph_base}{file}",
+        "{graph_base",
+        "{prefix}/{stem}",
+        "{graph_base}{stem} graph",
+        "{graph_base}{prefix}#{stem}",
+    ] {
+        assert!(
+            invalid.parse::<GraphNamingStrategy>().is_err(),
+            "{} should be invalid",
+            invalid
+        );
+    }
+
+    // A routing rule with a graph naming strategy overrides the one of the
+    // pipeline
+    let routing_table = RoutingTable::parse(
+        r#"
+        [[rule]]
+        prefix = "ontology/"
+        graph = "hash"
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        GraphNamingStrategy::for_routing_rule(routing_table.route("ontology/data use.ttl"))
+            .unwrap(),
+        GraphNamingStrategy::Hash
+    );
+}
+
+#[test_log::test]
+fn test_placeholder_rewriter() {
+    let rewriter = PlaceholderRewriter::new([
+        ("https://placeholder.kg", "https://kg.example.com"),
+        (
+            "https://placeholder.kg/id/",
+            "https://kg.example.com/id/",
+        ),
+        (
+            "https://placeholder.kg/graph",
+            "https://placeholder.kg/graph/",
+        ),
+    ]);
+    let content = concat!(
+        "<https://placeholder.kg/id/person-1> <https://placeholder.kg/ontology/name> \"1\" ",
+        "<https://placeholder.kg/graph/persons> .\n",
+        "<https://placeholder.kgx/id/person-2> <http://placeholder.kg/id/name> \"2\" .\n",
+    );
+    // The graph placeholder is the graph base of the environment itself, which
+    // keeps the shorter base placeholder from rewriting it
+    let expected = concat!(
+        "<https://kg.example.com/id/person-1> <https://kg.example.com/ontology/name> \"1\" ",
+        "<https://placeholder.kg/graph/persons> .\n",
+        "<https://placeholder.kgx/id/person-2> <http://placeholder.kg/id/name> \"2\" .\n",
+    );
+    assert_eq!(
+        String::from_utf8(rewriter.rewrite(content.as_bytes())).unwrap(),
+        expected
+    );
+
+    // A placeholder that is split over two writes is replaced all the same
+    for chunk_size in [1, 2, 7, 23, content.len()] {
+        let mut writer = rewriter.writer(Vec::new());
+        for chunk in content.as_bytes().chunks(chunk_size) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(writer.finish().unwrap()).unwrap(),
+            expected,
+            "chunks of {} bytes",
+            chunk_size
+        );
+    }
+
+    // Placeholders that are the bases of the environment itself (or that are
+    // empty) leave nothing to rewrite
+    let rewriter = PlaceholderRewriter::new([
+        (
+            "https://placeholder.kg",
+            "https://placeholder.kg/",
+        ),
+        ("", "https://kg.example.com/graph/"),
+    ]);
+    assert!(rewriter.is_empty());
+    assert!(!PlaceholderRewriter::new([
+        ("https://placeholder.kg", "https://kg.example.com"),
+        (
+            "https://placeholder.kg/graph",
+            "https://placeholder.kg/graph",
+        ),
+    ])
+    .is_empty());
+    assert_eq!(
+        rewriter.rewrite(content.as_bytes()),
+        content.as_bytes()
+    );
+}
+
+#[test_log::test]
+fn test_staged_s3_uri() {
+    assert_eq!(
+        parse_s3_uri("s3://ekgf-dt-dev-metadata/ontology/data use.ttl"),
+        Some(("ekgf-dt-dev-metadata", "ontology/data use.ttl"))
+    );
+    assert_eq!(parse_s3_uri("s3://ekgf-dt-dev-metadata/"), None);
+    assert_eq!(
+        parse_s3_uri("https://ekgf-dt-dev-metadata/a.ttl"),
+        None
+    );
+
+    assert_eq!(
+        staged_s3_uri(
+            "s3://ekgf-dt-dev-metadata/ontology/data use.ttl",
+            "ekg-staging/",
+            Some("LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2"),
+        )
+        .unwrap(),
+        "s3://ekgf-dt-dev-metadata/ekg-staging/LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2/ontology/data \
+         use.ttl"
+    );
+    assert_eq!(
+        staged_s3_uri(
+            "s3://ekgf-dt-dev-metadata/ontology/data use.ttl",
+            "ekg-staging/",
+            None,
+        )
+        .unwrap(),
+        "s3://ekgf-dt-dev-metadata/ekg-staging/ontology/data use.ttl"
+    );
+    assert!(staged_s3_uri("ontology/data use.ttl", "ekg-staging/", None).is_err());
+}
+
+fn object<const N: usize>(entries: [(&str, Document); N]) -> Document {
+    Document::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect::<HashMap<_, _>>(),
+    )
+}
+
+#[test_log::test]
+fn test_loader_job_status_from_document() {
+    let payload = object([
+        (
+            "feedCount",
+            Document::Array(vec![object([(
+                "LOAD_FAILED",
+                Document::Number(Number::PosInt(1)),
+            )])]),
+        ),
+        (
+            "overallStatus",
+            object([
+                (
+                    "fullUri",
+                    Document::String("s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl".into()),
+                ),
+                ("runNumber", Document::Number(Number::PosInt(1))),
+                ("retryNumber", Document::Number(Number::PosInt(0))),
+                ("status", Document::String("LOAD_FAILED".into())),
+                (
+                    "totalTimeSpent",
+                    Document::Number(Number::PosInt(3)),
+                ),
+                (
+                    "startTime",
+                    Document::Number(Number::PosInt(1695031396)),
+                ),
+                (
+                    "totalRecords",
+                    Document::Number(Number::PosInt(118)),
+                ),
+                (
+                    "parsingErrors",
+                    Document::Number(Number::PosInt(1)),
+                ),
+            ]),
+        ),
+        (
+            "errors",
+            object([
+                ("startIndex", Document::Number(Number::PosInt(1))),
+                ("endIndex", Document::Number(Number::PosInt(1))),
+                ("loadId", Document::String("9d6e8f2a".into())),
+                (
+                    "errorLogs",
+                    Document::Array(vec![object([
+                        (
+                            "errorCode",
+                            Document::String("PARSING_ERROR".into()),
+                        ),
+                        (
+                            "errorMessage",
+                            Document::String("Expected '.'".into()),
+                        ),
+                        (
+                            "fileName",
+                            Document::String("cdmc-data-use.ttl".into()),
+                        ),
+                        ("recordNum", Document::Number(Number::PosInt(12))),
+                    ])]),
+                ),
+            ]),
+        ),
+    ]);
+    let status = LoaderJobStatus::try_from(&payload).unwrap();
+    assert!(matches!(
+        status.detail_status(),
+        LambdaDetailStatus::LoaderJobFailed
+    ));
+    assert_eq!(status.feed_count[0]["LOAD_FAILED"], 1);
+    assert_eq!(status.overall_status.total_records, 118);
+    assert_eq!(status.overall_status.start_time, 1695031396);
+    // Counters that Neptune leaves out default to zero
+    assert_eq!(status.overall_status.insert_errors, 0);
+    assert!(status.failed_feeds.is_empty());
+    let errors = status.errors.unwrap();
+    assert_eq!(errors.error_logs.len(), 1);
+    assert_eq!(errors.error_logs[0].record_num, 12);
+
+    // Without overallStatus the payload is not a loader job status
+    assert!(LoaderJobStatus::try_from(&object([])).is_err());
+}
+
+#[test_log::test]
+fn test_json_to_document() {
+    let json = serde_json::json!({
+        "overallStatus": {
+            "status": "LOAD_COMPLETED",
+            "totalRecords": 118,
+            "offset": -1,
+            "ratio": 0.5,
+        },
+        "failedFeeds": [],
+        "errors": null,
+        "queued": true,
+    });
+    let document = json_to_document(&json);
+    assert_eq!(
+        document,
+        object([
+            (
+                "overallStatus",
+                object([
+                    (
+                        "status",
+                        Document::String("LOAD_COMPLETED".into())
+                    ),
+                    (
+                        "totalRecords",
+                        Document::Number(Number::PosInt(118)),
+                    ),
+                    ("offset", Document::Number(Number::NegInt(-1))),
+                    ("ratio", Document::Number(Number::Float(0.5))),
+                ]),
+            ),
+            ("failedFeeds", Document::Array(vec![])),
+            ("errors", Document::Null),
+            ("queued", Document::Bool(true)),
+        ])
+    );
+    assert_eq!(document_to_json(&document), json);
+}
+
+#[test_log::test]
+fn test_loader_job_feed_status_statistics() {
+    let feed_status = LoaderJobFeedStatus {
+        status: "LOAD_COMPLETED".to_string(),
+        total_time_spent: 3,
+        start_time: 1695031396,
+        ..Default::default()
+    };
+    assert!(feed_status.is_finished());
+    assert_eq!(
+        feed_status.started_at_xsd().as_deref(),
+        Some("2023-09-18T10:03:16Z")
+    );
+    assert_eq!(
+        feed_status.finished_at_xsd().as_deref(),
+        Some("2023-09-18T10:03:19Z")
+    );
+    assert_eq!(feed_status.duration_xsd(), "PT3S");
+
+    // A load that has not started yet has no start or finish time
+    let feed_status = LoaderJobFeedStatus {
+        status: "LOAD_IN_QUEUE".to_string(),
+        ..Default::default()
+    };
+    assert!(!feed_status.is_finished());
+    assert!(feed_status.started_at_xsd().is_none());
+    assert!(feed_status.finished_at_xsd().is_none());
+
+    // Every status that the loader reports once the load is over is terminal,
+    // the other ones (including a status that this crate does not know) are not
+    for (status, is_finished) in [
+        ("LOAD_NOT_STARTED", false),
+        ("LOAD_IN_PROGRESS", false),
+        ("LOAD_COMPLETED", true),
+        ("LOAD_CANCELLED_BY_USER", true),
+        ("LOAD_CANCELLED_DUE_TO_ERRORS", true),
+        ("LOAD_UNEXPECTED_ERROR", true),
+        ("LOAD_FAILED", true),
+        ("LOAD_S3_READ_ERROR", true),
+        ("LOAD_S3_ACCESS_DENIED_ERROR", true),
+        ("LOAD_COMMITTED_W_WRITE_CONFLICTS", true),
+        ("LOAD_DATA_DEADLOCK", true),
+        (
+            "LOAD_DATA_FAILED_DUE_TO_FEED_MODIFIED_OR_DELETED",
+            true,
+        ),
+        (
+            "LOAD_FAILED_BECAUSE_DEPENDENCY_NOT_SATISFIED",
+            true,
+        ),
+        ("LOAD_FAILED_INVALID_REQUEST", true),
+        ("LOAD_SOMETHING_NEW", false),
+        ("", false),
+    ] {
+        let feed_status = LoaderJobFeedStatus { status: status.to_string(), ..Default::default() };
+        assert_eq!(
+            feed_status.is_finished(),
+            is_finished,
+            "{}",
+            status
+        );
+    }
+}
+
+/// Parse the given SPARQL update, which should be an `INSERT DATA` of one quad
+fn parse_inserted_quad(update: &str) -> Quad {
+    let update = spargebra::Update::parse(update, None)
+        .unwrap_or_else(|error| panic!("invalid SPARQL update {}: {}", update, error));
+    match update.operations.into_iter().next() {
+        Some(GraphUpdateOperation::InsertData { data }) => data.into_iter().next().unwrap(),
+        operation => panic!("unexpected operation {:?}", operation),
+    }
+}
+
+fn insert_data(subject: &Iri, object: &Literal) -> String {
+    format!(
+        "INSERT DATA {{ GRAPH <https://placeholder.kg/graph/test> {{ {} \
+         <http://www.w3.org/2000/01/rdf-schema#label> {} . }} }}",
+        subject, object
+    )
+}
+
+#[test_log::test]
+fn test_sparql_iri() {
+    assert_eq!(
+        Iri::new("s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl")
+            .unwrap()
+            .to_string(),
+        "<s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl>"
+    );
+    assert!(Iri::new("s3://ekgf-dt-dev-metadata/a>b").is_err());
+    assert!(Iri::new("s3://ekgf-dt-dev-metadata/a b.ttl").is_err());
+    assert!(Iri::new("s3://ekgf-dt-dev-metadata/a\"b.ttl").is_err());
+    assert!(Iri::new("ontology/cdmc-data-use.ttl").is_err());
+    assert!(Iri::new("").is_err());
+}
+
+#[test_log::test]
+fn test_sparql_literal() {
+    assert_eq!(
+        Literal::string(r#"say "hi"\n"#).to_string(),
+        r#""say \"hi\"\\n""#
+    );
+    assert_eq!(
+        Literal::string("line 1\nline 2").to_string(),
+        r#""line 1\nline 2""#
+    );
+    assert_eq!(
+        Literal::integer(42).to_string(),
+        r#""42"^^<http://www.w3.org/2001/XMLSchema#integer>"#
+    );
+    assert_eq!(
+        Literal::duration("PT3S").to_string(),
+        r#""PT3S"^^<http://www.w3.org/2001/XMLSchema#duration>"#
+    );
+    // A backslash followed by a u must not become a codepoint escape
+    assert_eq!(
+        Literal::string(r"\u0022").to_string(),
+        r#""\\\u00750022""#
+    );
+}
+
+proptest! {
+    /// Any string survives a round trip through a SPARQL update as a literal
+    #[test]
+    fn test_sparql_literal_round_trip(value in any::<String>()) {
+        let subject = Iri::new("https://placeholder.kg/id/test").unwrap();
+        let literal = Literal::string(value.as_str());
+        let quad = parse_inserted_quad(insert_data(&subject, &literal).as_str());
+        match quad.object {
+            Term::Literal(literal) => prop_assert_eq!(literal.value(), value.as_str()),
+            object => prop_assert!(false, "unexpected object {:?}", object),
+        }
+    }
+
+    /// An object key with quotes, brackets, braces, backslashes or triple
+    /// quotes either yields a valid IRI that round trips, or is rejected
+    #[test]
+    fn test_sparql_hostile_s3_keys(
+        key in r#"[ -~]{0,20}("|>|<|\{|\}|\\|'''|"""|\n|\}\} ; DROP ALL ; #)?[ -~]{0,20}"#
+    ) {
+        let s3_uri = format!("s3://ekgf-dt-dev-metadata/{}", key);
+        match Iri::new(s3_uri.as_str()) {
+            Ok(iri) => {
+                let label = format!("S3 file {}", s3_uri);
+                let literal = Literal::string(label.as_str());
+                let quad = parse_inserted_quad(insert_data(&iri, &literal).as_str());
+                match quad.subject {
+                    Subject::NamedNode(subject) => {
+                        prop_assert_eq!(subject.as_str(), s3_uri.as_str())
+                    },
+                    subject => prop_assert!(false, "unexpected subject {:?}", subject),
+                }
+                match quad.object {
+                    Term::Literal(literal) => prop_assert_eq!(literal.value(), label.as_str()),
+                    object => prop_assert!(false, "unexpected object {:?}", object),
+                }
+            },
+            Err(_) => prop_assert!(s3_uri
+                .chars()
+                .any(|c| c <= ' ' || "<>\"{}|^`\\".contains(c))),
+        }
+    }
+}
+
+type StartLoaderJobSdkError = SdkError<StartLoaderJobError, HttpResponse>;
+
+fn raw_response(status: u16) -> HttpResponse {
+    HttpResponse::new(
+        StatusCode::try_from(status).unwrap(),
+        SdkBody::empty(),
+    )
+}
+
+#[test_log::test]
+fn test_sdk_error_construction_failure() {
+    let response: LambdaResponse = StartLoaderJobSdkError::construction_failure("no source").into();
+    assert_eq!(response.status_code, 500);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::UserError
+    ));
+}
+
+#[test_log::test]
+fn test_sdk_error_timeout() {
+    let response: LambdaResponse = StartLoaderJobSdkError::timeout_error("timed out").into();
+    assert_eq!(response.status_code, 504);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::Timedout
+    ));
+    assert!(response.suggested_retry_seconds.is_some());
+}
+
+#[test_log::test]
+fn test_sdk_error_dispatch_failure() {
+    let response: LambdaResponse =
+        StartLoaderJobSdkError::dispatch_failure(ConnectorError::io("connection reset".into()))
+            .into();
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::IOError
+    ));
+    assert_eq!(
+        response.detailed_message.as_deref(),
+        Some("connection reset")
+    );
+    assert!(response.suggested_retry_seconds.is_some());
+
+    let response: LambdaResponse =
+        StartLoaderJobSdkError::dispatch_failure(ConnectorError::timeout("too slow".into())).into();
+    assert_eq!(response.status_code, 504);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::Timedout
+    ));
+
+    let response: LambdaResponse =
+        StartLoaderJobSdkError::dispatch_failure(ConnectorError::user("bad endpoint".into()))
+            .into();
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::UserError
+    ));
+
+    let response: LambdaResponse =
+        StartLoaderJobSdkError::dispatch_failure(ConnectorError::other("boom".into(), None)).into();
+    assert_eq!(response.status_code, 500);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::LoaderJobUnexpectedError
+    ));
+    assert!(response.suggested_retry_seconds.is_none());
+}
+
+#[test_log::test]
+fn test_sdk_error_response_error() {
+    let response: LambdaResponse =
+        StartLoaderJobSdkError::response_error("unparseable body", raw_response(200)).into();
+    assert_eq!(response.status_code, 502);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::LoaderJobUnexpectedError
+    ));
+}
+
+#[test_log::test]
+fn test_sdk_error_service_error() {
+    let exc = BadRequestException::builder()
+        .code("BadRequestException")
+        .request_id("7a3f9f0e")
+        .detailed_message("Max load task queue size limit breached. Limit is 64")
+        .message("Max load task queue size limit breached. Limit is 64")
+        .build()
+        .unwrap();
+    let response: LambdaResponse = StartLoaderJobSdkError::service_error(
+        StartLoaderJobError::BadRequestException(exc),
+        raw_response(400),
+    )
+    .into();
+    assert_eq!(response.status_code, 400);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::MaxLoadTaskQueueSizeLimitBreached
+    ));
+    assert_eq!(response.retryability, Retryability::Transient);
+    assert_eq!(
+        response.detailed_message.as_deref(),
+        Some("Max load task queue size limit breached. Limit is 64")
+    );
+
+    let exc = S3Exception::builder()
+        .code("S3Exception")
+        .request_id("7a3f9f0e")
+        .detailed_message("Failed to read s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl")
+        .meta(
+            ErrorMetadata::builder()
+                .code("S3Exception")
+                .message("Failed to read S3 object")
+                .build(),
+        )
+        .build()
+        .unwrap();
+    let response: LambdaResponse = StartLoaderJobSdkError::service_error(
+        StartLoaderJobError::S3Exception(exc),
+        raw_response(400),
+    )
+    .into();
+    assert_eq!(response.status_code, 400);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::S3Error
+    ));
+    assert_eq!(response.retryability, Retryability::Permanent);
+
+    // An error that the SDK does not know about (yet)
+    let response: LambdaResponse =
+        SdkError::<GetLoaderJobStatusError, HttpResponse>::service_error(
+            GetLoaderJobStatusError::generic(
+                ErrorMetadata::builder()
+                    .code("BulkLoadIdNotFoundException")
+                    .message("Load id not found")
+                    .build(),
+            ),
+            raw_response(404),
+        )
+        .into();
+    assert_eq!(response.status_code, 404);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::BulkLoadIdNotFound
+    ));
+
+    let response: LambdaResponse =
+        SdkError::<GetLoaderJobStatusError, HttpResponse>::service_error(
+            GetLoaderJobStatusError::generic(ErrorMetadata::builder().build()),
+            raw_response(500),
+        )
+        .into();
+    assert_eq!(response.status_code, 500);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::LoaderJobUnexpectedError
+    ));
+}
+
+#[test_log::test]
+fn test_detail_status_retryability() {
+    let status = LambdaDetailStatus::from_error_code(Some("ThrottlingException"));
+    assert!(matches!(status, LambdaDetailStatus::Throttled));
+    assert_eq!(status.http_status_code(), 429);
+    assert_eq!(status.retryability(), Retryability::Transient);
+
+    let status = LambdaDetailStatus::from_error_code(Some("LoadUrlAccessDeniedException"));
+    assert!(matches!(
+        status,
+        LambdaDetailStatus::LoadUrlAccessDenied
+    ));
+    assert_eq!(status.http_status_code(), 400);
+    assert_eq!(status.retryability(), Retryability::Permanent);
+
+    let status = LambdaDetailStatus::from_error_code(Some("InvalidParameterException"));
+    assert!(matches!(
+        status,
+        LambdaDetailStatus::InvalidParameter
+    ));
+    assert_eq!(status.retryability(), Retryability::Permanent);
+
+    let exc = BadRequestException::builder()
+        .code("BadRequestException")
+        .request_id("7a3f9f0e")
+        .detailed_message("A bulk load is already in progress for this source")
+        .message("A bulk load is already in progress for this source")
+        .build()
+        .unwrap();
+    let response = LambdaResponse::from(&exc);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::BulkLoadInProgress
+    ));
+    assert_eq!(response.retryability, Retryability::Transient);
+    assert!(response.suggested_retry_seconds.is_some());
+
+    // Final statuses of a loader job should never make the state machine
+    // check again
+    let status = LambdaDetailStatus::from_loader_job_status("LOAD_DATA_DEADLOCK");
+    assert_eq!(status.retryability(), Retryability::Permanent);
+    let status = LambdaDetailStatus::from_loader_job_status("LOAD_IN_PROGRESS");
+    assert_eq!(status.retryability(), Retryability::Pending);
+    assert!(status.is_retryable());
+    let status = LambdaDetailStatus::from_loader_job_status("LOAD_COMPLETED");
+    assert_eq!(status.retryability(), Retryability::Succeeded);
+}
+
+#[test_log::test]
+fn test_retry_policy_backoff() {
+    let policy = RetryPolicy { jitter: Jitter::None, ..Default::default() };
+    assert_eq!(policy.delay_seconds(1, None), 10);
+    assert_eq!(policy.delay_seconds(2, Some(10)), 20);
+    assert_eq!(policy.delay_seconds(3, Some(20)), 40);
+    assert_eq!(policy.delay_seconds(10, None), 300);
+    assert_eq!(policy.delay_seconds(u16::MAX, None), 300);
+
+    let policy = RetryPolicy { jitter: Jitter::Full, ..Default::default() };
+    for attempt in 1..20 {
+        let delay = policy.delay_seconds(attempt, None);
+        assert!((1..=policy.backoff_seconds(attempt)).contains(&delay));
+    }
+
+    let policy = RetryPolicy { jitter: Jitter::Decorrelated, ..Default::default() };
+    let mut previous = None;
+    for attempt in 1..20 {
+        let delay = policy.delay_seconds(attempt, previous);
+        assert!((policy.base_delay_seconds..=policy.max_delay_seconds).contains(&delay));
+        assert!(delay <= previous.unwrap_or(policy.base_delay_seconds) * 3);
+        previous = Some(delay);
+    }
+}
+
+#[test_log::test]
+fn test_retry_policy_from_env() {
+    std::env::set_var("EKG_RETRY_MAX_ATTEMPTS", "5");
+    std::env::set_var("EKG_RETRY_JITTER", "decorrelated");
+    let policy = RetryPolicy::from_env().unwrap();
+    assert_eq!(policy.max_attempts, 5);
+    assert_eq!(policy.jitter, Jitter::Decorrelated);
+    assert_eq!(
+        policy.base_delay_seconds,
+        RetryPolicy::default().base_delay_seconds
+    );
+    std::env::set_var("EKG_RETRY_JITTER", "random");
+    assert!(RetryPolicy::from_env().is_err());
+    std::env::remove_var("EKG_RETRY_MAX_ATTEMPTS");
+    std::env::remove_var("EKG_RETRY_JITTER");
+}
+
+#[test_log::test]
+fn test_retry_budget_exhausted() {
+    let policy = RetryPolicy { max_attempts: 3, ..Default::default() };
+
+    let first = LambdaResponse::ok(LambdaDetailStatus::LoaderJobInProgress, None)
+        .with_retry_policy(&policy, None);
+    assert_eq!(first.attempt, 1);
+    assert_eq!(first.retryability, Retryability::Pending);
+    assert!(first.suggested_retry_seconds.is_some());
+
+    let second = LambdaResponse::ok(LambdaDetailStatus::LoaderJobInProgress, None)
+        .with_retry_policy(&policy, Some(&first));
+    assert_eq!(second.attempt, 2);
+    assert_eq!(second.retryability, Retryability::Pending);
+
+    let third = LambdaResponse::ok(LambdaDetailStatus::LoaderJobInProgress, None)
+        .with_retry_policy(&policy, Some(&second));
+    assert_eq!(third.attempt, 3);
+    assert!(matches!(
+        third.detail_status,
+        LambdaDetailStatus::RetryBudgetExhausted
+    ));
+    assert_eq!(third.retryability, Retryability::Permanent);
+    assert!(third.suggested_retry_seconds.is_none());
+
+    // A final status is never turned into a failure, whatever the attempt
+    let completed = LambdaResponse::ok(LambdaDetailStatus::LoaderJobCompleted, None)
+        .with_retry_policy(&policy, Some(&third));
+    assert!(matches!(
+        completed.detail_status,
+        LambdaDetailStatus::LoaderJobCompleted
+    ));
+    assert!(completed.suggested_retry_seconds.is_none());
+}
+
+#[test_log::test]
+fn test_detail_status_all() {
+    assert_eq!(
+        LambdaDetailStatus::ALL.len(),
+        LambdaDetailStatus::COUNT
+    );
+    assert!(LambdaDetailStatus::ALL.contains(&LambdaDetailStatus::default()));
+    let mut names = std::collections::HashSet::new();
+    for status in LambdaDetailStatus::ALL.iter() {
+        let name = serde_json::to_string(status).unwrap();
+        assert!(
+            names.insert(name.clone()),
+            "{name} is listed twice"
+        );
+        assert_eq!(
+            &serde_json::from_str::<LambdaDetailStatus>(name.as_str()).unwrap(),
+            status
+        );
+    }
+    assert!(serde_json::from_str::<LambdaDetailStatus>("\"NotAStatus\"").is_err());
+}
@@ -1,3 +1,747 @@
-pub use request::Request;
+//! The handler of the check lambda function, in a library so that it can be
+//! invoked in-process as well (see the `ekg-sfn-local` crate).
+pub use {
+    clients::Clients,
+    lambda_runtime::{Error as LambdaError, LambdaEvent},
+    request::Request,
+};
+use {
+    ekg_aws_util::{
+        graph_store::GraphStore,
+        lambda::{
+            default_load_request_label,
+            load_error_iri,
+            load_request_iri,
+            LambdaDetailStatus::{self},
+            LambdaResponse,
+            RetryPolicy,
+            CLASS_DATAOPS_FAILED_LOAD_REQUEST,
+            CLASS_DATAOPS_FINISHED_LOAD_REQUEST,
+            CLASS_DATAOPS_LOADING_LOAD_REQUEST,
+            CLASS_DATAOPS_LOAD_ERROR,
+            CLASS_DATAOPS_LOAD_REQUEST,
+            CLASS_DATAOPS_LOAD_REQUEST_STATUS_CHANGE,
+            CLASS_DATAOPS_QUEUED_LOAD_REQUEST,
+        },
+        neptune::{
+            BulkLoader,
+            LoadRequest,
+            LoaderJobErrorLog,
+            LoaderJobFeedStatus,
+            LoaderJobStatus,
+        },
+        sparql::{Iri, Literal},
+    },
+    ekg_identifier::{
+        EkgIdentifierContexts,
+        NS_DATAOPS,
+        NS_PREFIX_DATAOPS,
+        NS_PREFIX_RDFS,
+        NS_RDFS,
+    },
+    ekg_sparql::Prefixes,
+    indoc::formatdoc,
+    serde_json::Value,
+    std::ops::Deref,
+};
 
 mod request;
+
+mod clients;
+
+#[cfg(test)]
+mod tests;
+
+/// The number of error log entries to request per page from the Neptune
+/// loader Get-Status API (which defaults to 10)
+const LOADER_JOB_ERRORS_PER_PAGE: i32 = 100;
+
+/// The maximum number of pages of error log entries that we fetch for one
+/// loader job, a file with thousands of errors is better fixed as a whole
+const LOADER_JOB_MAX_ERROR_PAGES: i32 = 10;
+
+// noinspection DuplicatedCode
+/// The actual handler of the Lambda request.
+pub async fn handle_lambda_event<L: BulkLoader, G: GraphStore>(
+    event: LambdaEvent<Value>,
+    pipeline_id: &'static str,
+    clients: Clients<L, G>,
+) -> Result<LambdaResponse, LambdaError> {
+    tracing::trace!("Event {:#?}\n\n", event.clone());
+
+    let ekg_identifier_contexts = EkgIdentifierContexts::from_env()?;
+
+    let (payload, _ctx) = event.into_parts();
+
+    handle_lambda_payload(
+        payload,
+        &ekg_identifier_contexts,
+        pipeline_id,
+        clients,
+    )
+    .await
+}
+
+pub async fn handle_lambda_payload<L: BulkLoader, G: GraphStore>(
+    payload: Value,
+    ekg_identifier_contexts: &EkgIdentifierContexts,
+    pipeline_id: &'static str,
+    clients: Clients<L, G>,
+) -> Result<LambdaResponse, LambdaError> {
+    tracing::info!(
+        "Payload {}",
+        serde_json::to_string_pretty(&payload)?
+    );
+
+    let load_output = payload
+        .as_object()
+        .ok_or(LambdaError::from("Payload is not an object"))?
+        .get("LoadOutput")
+        .ok_or(LambdaError::from("Missing LoadOutput in payload"))?
+        .clone();
+
+    tracing::info!(
+        "Load output: {:}",
+        serde_json::to_string(&load_output)?
+    );
+
+    // The output of the ekg_lfn_load Lambda function is the input to this one.
+    let request = serde_json::from_value::<LambdaResponse>(load_output).map_err(|e| {
+        tracing::error!("Error parsing request: {}", e);
+        e
+    })?;
+    // The output of the previous check, if the state machine is polling
+    let check_output = payload
+        .as_object()
+        .and_then(|payload| payload.get("CheckOutput"))
+        .map(|check_output| serde_json::from_value::<LambdaResponse>(check_output.clone()))
+        .transpose()?;
+    let retry_policy = RetryPolicy::from_env()?;
+
+    let Some(load_request_id) = request.result_identifier.as_ref() else {
+        return Err(LambdaError::from(
+            "Missing result_identifier in request",
+        ));
+    };
+
+    let load_request = payload
+        .as_object()
+        .ok_or(LambdaError::from("Payload is not an object"))?
+        .get("load_request")
+        .ok_or(LambdaError::from(
+            "Missing load_request in payload",
+        ))?
+        .clone();
+    let load_request = serde_json::from_value::<LoadRequest>(load_request).map_err(|e| {
+        tracing::error!("Error parsing load_request: {}", e);
+        e
+    })?;
+    let source_iri = load_request.source_iri();
+    let source_iri = source_iri.as_str();
+
+    match handle_lambda_request(
+        &request,
+        &load_request,
+        &ekg_identifier_contexts,
+        pipeline_id,
+        load_request_id.as_str(),
+        source_iri,
+        clients.clone(),
+    )
+    .await
+    {
+        Ok(response) => {
+            let response = response.with_retry_policy(&retry_policy, check_output.as_ref());
+            if matches!(
+                response.detail_status,
+                LambdaDetailStatus::RetryBudgetExhausted
+            ) {
+                // Giving up on the loader job, so it should not load (into the
+                // staging graph) after all
+                if let Err(error) = clients
+                    .aws_neptunedata_client
+                    .cancel_load(load_request_id.as_str())
+                    .await
+                {
+                    tracing::error!(
+                        "Could not cancel loader job {}: {:?}",
+                        load_request_id,
+                        error
+                    );
+                }
+                handle_staging_graph(
+                    &load_request,
+                    &response.detail_status,
+                    clients.clone(),
+                )
+                .await?;
+                register_load_request_status(
+                    Ok(&response),
+                    ekg_identifier_contexts,
+                    pipeline_id,
+                    load_request_id.as_str(),
+                    &load_request,
+                    clients.clone(),
+                )
+                .await?;
+            }
+            tracing::info!("Response: {:}", serde_json::to_string(&response)?);
+            Ok(response)
+        },
+        Err(error) => {
+            tracing::error!("Error handling request: {:?}", error);
+            register_load_request_status(
+                Err(&error),
+                ekg_identifier_contexts,
+                pipeline_id,
+                load_request_id.as_str(),
+                &load_request,
+                clients.clone(),
+            )
+            .await?;
+            Err(error)
+        },
+    }
+}
+
+/// The actual handler of the Lambda payload.
+///
+/// - `request`: The output of the ekg_lfn_load Lambda function is the input to
+///   this one.
+pub async fn handle_lambda_request<L: BulkLoader, G: GraphStore>(
+    load_status_response: &LambdaResponse,
+    load_request: &LoadRequest,
+    ekg_identifier_contexts: &EkgIdentifierContexts,
+    pipeline_id: &'static str,
+    load_request_id: &str,
+    source_iri: &str,
+    clients: Clients<L, G>,
+) -> Result<LambdaResponse, LambdaError> {
+    let load_id = load_status_response
+        .result_identifier
+        .as_deref()
+        .ok_or(LambdaError::from(
+            "Missing result_identifier in request",
+        ))?;
+
+    tracing::info!(
+        "Check whether load job has finished: {:?}",
+        load_id
+    );
+
+    let result = clients
+        .aws_neptunedata_client
+        .load_status(load_id, LOADER_JOB_ERRORS_PER_PAGE, 1)
+        .await;
+
+    match result {
+        Ok(output) => {
+            let loader_job_status = match LoaderJobStatus::try_from(output.payload()) {
+                Ok(loader_job_status) => loader_job_status,
+                Err(error) => {
+                    let detailed_message =
+                        format!("Unexpected loader job status payload: {}", error);
+                    return Ok(LambdaResponse::ok(
+                        LambdaDetailStatus::LoaderJobStatusUnknown,
+                        Some(detailed_message.as_str()),
+                    ));
+                },
+            };
+            let status = loader_job_status.detail_status();
+            let detailed_message = if status.should_show_detail() {
+                Some(format!(
+                    "Loader job status for {} is {} with payload {}",
+                    source_iri,
+                    output.status(),
+                    serde_json::to_string(&loader_job_status)?
+                ))
+            } else {
+                None
+            };
+            handle_staging_graph(load_request, &status, clients.clone()).await?;
+            if loader_job_status.overall_status.is_finished() {
+                register_load_statistics(
+                    &loader_job_status.overall_status,
+                    ekg_identifier_contexts,
+                    pipeline_id,
+                    load_request_id,
+                    clients.clone(),
+                )
+                .await?;
+                // The load errors are only there to help the data producer, not
+                // being able to (fully) register them should not fail the check
+                let error_logs =
+                    fetch_load_errors(load_id, &loader_job_status, clients.clone()).await;
+                if let Err(error) = register_load_errors(
+                    &error_logs,
+                    ekg_identifier_contexts,
+                    pipeline_id,
+                    load_request_id,
+                    clients.clone(),
+                )
+                .await
+                {
+                    tracing::error!(
+                        "Could not register the load errors of load request {}: {:?}",
+                        load_request_id,
+                        error
+                    );
+                }
+            }
+            let response = LambdaResponse::ok(status, detailed_message.as_deref());
+            register_load_request_status(
+                Ok(&response),
+                ekg_identifier_contexts,
+                pipeline_id,
+                load_request_id,
+                load_request,
+                clients.clone(),
+            )
+            .await?;
+
+            Ok(response)
+        },
+        Err(error) => Ok(error.into()),
+    }
+}
+
+/// With [`LoadStrategy::Replace`] the Neptune bulk loader loads into a staging
+/// graph. Once the loader job has completed, the staging graph replaces the
+/// target graph in one SPARQL update (so readers never see a half-empty graph),
+/// if the loader job failed the staging graph is dropped.
+///
+/// [`LoadStrategy::Replace`]: ekg_aws_util::neptune::LoadStrategy::Replace
+async fn handle_staging_graph<L: BulkLoader, G: GraphStore>(
+    load_request: &LoadRequest,
+    status: &LambdaDetailStatus,
+    clients: Clients<L, G>,
+) -> Result<(), LambdaError> {
+    let Some(staging_graph) = load_request.staging_graph_uri() else {
+        return Ok(());
+    };
+    let staging_graph = Iri::new(staging_graph)?;
+    let target_graph = Iri::new(load_request.target_graph_uri())?;
+    let sparql = match status {
+        LambdaDetailStatus::LoaderJobCompleted => {
+            tracing::info!(
+                "Replacing graph {} with staging graph {}",
+                target_graph,
+                staging_graph
+            );
+            format!("MOVE SILENT GRAPH {staging_graph} TO GRAPH {target_graph}")
+        },
+        status if status.is_retryable() => return Ok(()),
+        _ => {
+            tracing::info!(
+                "Dropping staging graph {} of failed load into {}",
+                staging_graph,
+                target_graph
+            );
+            format!("DROP SILENT GRAPH {staging_graph}")
+        },
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    clients.sparql_client.execute(&statement).await?;
+
+    Ok(())
+}
+
+/// Once the loader job has finished, register its statistics as typed literals
+/// on the `dataops:LoadRequest` so that load throughput and error rates can be
+/// queried with SPARQL.
+async fn register_load_statistics<L: BulkLoader, G: GraphStore>(
+    overall_status: &LoaderJobFeedStatus,
+    ekg_identifier_contexts: &EkgIdentifierContexts,
+    pipeline_id: &str,
+    load_request_id: &str,
+    clients: Clients<L, G>,
+) -> Result<(), LambdaError> {
+    let graph_load_requests = Iri::new(ekg_aws_util::lambda::load_requests_graph_iri(
+        ekg_identifier_contexts,
+        pipeline_id,
+    ))?;
+    let load_request_iri = Iri::new(load_request_iri(
+        ekg_identifier_contexts,
+        load_request_id,
+    ))?;
+
+    let mut statistics = vec![
+        (
+            "totalRecords",
+            Literal::integer(overall_status.total_records),
+        ),
+        (
+            "totalDuplicates",
+            Literal::integer(overall_status.total_duplicates),
+        ),
+        (
+            "parsingErrors",
+            Literal::integer(overall_status.parsing_errors),
+        ),
+        (
+            "datatypeMismatchErrors",
+            Literal::integer(overall_status.datatype_mismatch_errors),
+        ),
+        (
+            "insertErrors",
+            Literal::integer(overall_status.insert_errors),
+        ),
+        (
+            "duration",
+            Literal::duration(overall_status.duration_xsd()),
+        ),
+    ];
+    if let Some(started_at) = overall_status.started_at_xsd() {
+        statistics.push(("startedAt", Literal::date_time(started_at)));
+    }
+    if let Some(finished_at) = overall_status.finished_at_xsd() {
+        statistics.push(("finishedAt", Literal::date_time(finished_at)));
+    }
+    let properties = statistics
+        .iter()
+        .map(|(property, _)| format!("{}{}", NS_PREFIX_DATAOPS, property))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let values = statistics
+        .iter()
+        .map(|(property, literal)| format!("{}{} {}", NS_PREFIX_DATAOPS, property, literal))
+        .collect::<Vec<_>>()
+        .join(" ;\n        ");
+
+    let sparql = formatdoc! {
+        r#"
+            WITH {graph_load_requests}
+            DELETE {{
+                {load_request_iri} ?statistic ?value .
+            }}
+            WHERE {{
+                VALUES ?statistic {{ {properties} }}
+                {load_request_iri} ?statistic ?value .
+            }} ;
+            INSERT DATA {{
+                GRAPH {graph_load_requests} {{
+                    {load_request_iri} {values} .
+                }}
+            }}
+        "#,
+        graph_load_requests = graph_load_requests,
+        load_request_iri = load_request_iri,
+        properties = properties,
+        values = values,
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    clients.sparql_client.execute(&statement).await?;
+
+    tracing::info!(
+        "Load statistics registered for load request {} in pipeline {}: {} records in {}",
+        load_request_id,
+        pipeline_id,
+        overall_status.total_records,
+        overall_status.duration_xsd()
+    );
+
+    Ok(())
+}
+
+/// Collect the error log entries of the given loader job, the first page came
+/// with the status itself, the remaining pages (if any) are requested one by
+/// one until a page is not full. If a page cannot be fetched, the entries of
+/// the pages fetched so far are returned.
+async fn fetch_load_errors<L: BulkLoader, G: GraphStore>(
+    load_id: &str,
+    loader_job_status: &LoaderJobStatus,
+    clients: Clients<L, G>,
+) -> Vec<LoaderJobErrorLog> {
+    let Some(errors) = loader_job_status.errors.as_ref() else {
+        return vec![];
+    };
+    let mut error_logs = errors.error_logs.clone();
+    let mut last_page_size = error_logs.len();
+    let mut page = 1;
+    while last_page_size == LOADER_JOB_ERRORS_PER_PAGE as usize {
+        if page == LOADER_JOB_MAX_ERROR_PAGES {
+            tracing::warn!(
+                "Loader job {} has more than {} errors, ignoring the rest",
+                load_id,
+                error_logs.len()
+            );
+            break;
+        }
+        page += 1;
+        let page_error_logs = match fetch_load_errors_page(load_id, page, &clients).await {
+            Ok(page_error_logs) => page_error_logs,
+            Err(error) => {
+                tracing::error!(
+                    "Could not get page {} of the errors of loader job {}, only registering the \
+                     first {} errors: {:?}",
+                    page,
+                    load_id,
+                    error_logs.len(),
+                    error
+                );
+                break;
+            },
+        };
+        last_page_size = page_error_logs.len();
+        error_logs.extend(page_error_logs);
+    }
+    error_logs
+}
+
+async fn fetch_load_errors_page<L: BulkLoader, G: GraphStore>(
+    load_id: &str,
+    page: i32,
+    clients: &Clients<L, G>,
+) -> Result<Vec<LoaderJobErrorLog>, LambdaError> {
+    let output = clients
+        .aws_neptunedata_client
+        .load_status(load_id, LOADER_JOB_ERRORS_PER_PAGE, page)
+        .await?;
+    Ok(LoaderJobStatus::try_from(output.payload())?
+        .errors
+        .map(|errors| errors.error_logs)
+        .unwrap_or_default())
+}
+
+/// Register each error log entry of the loader job as a `dataops:LoadError`
+/// that is linked to its `dataops:LoadRequest`, so that data producers can
+/// find out what is wrong with their file with a SPARQL query.
+async fn register_load_errors<L: BulkLoader, G: GraphStore>(
+    error_logs: &[LoaderJobErrorLog],
+    ekg_identifier_contexts: &EkgIdentifierContexts,
+    pipeline_id: &str,
+    load_request_id: &str,
+    clients: Clients<L, G>,
+) -> Result<(), LambdaError> {
+    if error_logs.is_empty() {
+        return Ok(());
+    }
+    let graph_load_requests = Iri::new(ekg_aws_util::lambda::load_requests_graph_iri(
+        ekg_identifier_contexts,
+        pipeline_id,
+    ))?;
+    let load_request_iri = Iri::new(load_request_iri(
+        ekg_identifier_contexts,
+        load_request_id,
+    ))?;
+
+    let load_errors = error_logs
+        .iter()
+        .enumerate()
+        .map(
+            |(index, error_log)| -> Result<String, ekg_error::Error> {
+                let load_error_iri = Iri::new(load_error_iri(
+                    ekg_identifier_contexts,
+                    load_request_id,
+                    index as u64 + 1,
+                ))?;
+                Ok(formatdoc! {
+                    r#"
+                    {load_request_iri} {dataops}loadError {load_error_iri} .
+                    {load_error_iri} a {load_error_type} ;
+                        {dataops}loadRequest {load_request_iri} ;
+                        {dataops}errorCode {error_code} ;
+                        {dataops}errorMessage {error_message} ;
+                        {dataops}fileName {file_name} ;
+                        {dataops}recordNumber {record_num} .
+                "#,
+                    dataops = NS_PREFIX_DATAOPS,
+                    load_request_iri = load_request_iri,
+                    load_error_iri = load_error_iri,
+                    load_error_type = CLASS_DATAOPS_LOAD_ERROR.display_turtle(),
+                    error_code = Literal::string(error_log.error_code.as_str()),
+                    error_message = Literal::string(error_log.error_message.as_str()),
+                    file_name = Literal::string(error_log.file_name.as_str()),
+                    record_num = Literal::integer(error_log.record_num),
+                })
+            },
+        )
+        .collect::<Result<String, _>>()?;
+
+    // Remove the errors of an earlier check of the same load request first
+    let sparql = formatdoc! {
+        r#"
+            WITH {graph_load_requests}
+            DELETE {{
+                {load_request_iri} {dataops}loadError ?loadError .
+                ?loadError ?property ?value .
+            }}
+            WHERE {{
+                {load_request_iri} {dataops}loadError ?loadError .
+                ?loadError ?property ?value .
+            }} ;
+            INSERT DATA {{
+                GRAPH {graph_load_requests} {{
+            {load_errors}
+                }}
+            }}
+        "#,
+        dataops = NS_PREFIX_DATAOPS,
+        graph_load_requests = graph_load_requests,
+        load_request_iri = load_request_iri,
+        load_errors = load_errors,
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    clients.sparql_client.execute(&statement).await?;
+
+    tracing::info!(
+        "{} load errors registered for load request {} in pipeline {}",
+        error_logs.len(),
+        load_request_id,
+        pipeline_id
+    );
+
+    Ok(())
+}
+
+/// After we checked Neptune for the load status, we need to register that
+/// status back into the database (if at all possible, if the result of the load
+/// status check points out that there's a problem with the database we may not
+/// be able to update the status).
+async fn register_load_request_status<L: BulkLoader, G: GraphStore>(
+    check_result: Result<&LambdaResponse, &LambdaError>,
+    ekg_identifier_contexts: &EkgIdentifierContexts,
+    pipeline_id: &str,
+    load_request_id: &str,
+    load_request: &LoadRequest,
+    clients: Clients<L, G>,
+) -> Result<(), LambdaError> {
+    let source_iri = load_request.source_iri();
+    let source_iri = source_iri.as_str();
+    let graph_load_requests =
+        ekg_aws_util::lambda::load_requests_graph_iri(ekg_identifier_contexts, pipeline_id);
+
+    tracing::info!(
+        "Load request status registration for load request {} in pipeline {} for source IRI {}",
+        load_request_id,
+        pipeline_id,
+        source_iri
+    );
+
+    let load_request_type = match check_result {
+        Ok(response) => response.detail_status.rdf_class(),
+        Err(_) => LambdaDetailStatus::LoaderJobStatusUnknown.rdf_class(),
+    };
+
+    // First record the transition to the new status (if it is a transition at
+    // all) as a dataops:LoadRequestStatusChange that refers to the status and
+    // the status change before it, then replace the current status.
+    let sparql = formatdoc! {
+        r#"
+            WITH {graph_load_requests}
+            DELETE {{
+                ?loadRequest {dataops}latestStatusChange ?previousStatusChange .
+            }}
+            INSERT {{
+                ?loadRequest {dataops}latestStatusChange ?statusChange .
+                ?statusChange a {status_change_type} ;
+                    {dataops}loadRequest ?loadRequest ;
+                    {dataops}fromStatus ?previousStatus ;
+                    {dataops}toStatus {load_request_status_type} ;
+                    {dataops}previousStatusChange ?previousStatusChange ;
+                    {dataops}changedAt ?now .
+            }}
+            WHERE {{
+                VALUES ?loadRequest {{
+                    {load_request_iri}
+                }}
+                ?loadRequest a {load_request_type} .
+                OPTIONAL {{
+                    VALUES ?previousStatus {{ {status_types} }}
+                    ?loadRequest a ?previousStatus .
+                }}
+                OPTIONAL {{
+                    ?loadRequest {dataops}latestStatusChange ?previousStatusChange .
+                }}
+                FILTER (!BOUND(?previousStatus) || ?previousStatus != {load_request_status_type})
+                BIND(NOW() AS ?now)
+                BIND(IRI(CONCAT(STR(?loadRequest), "-status-", STRUUID())) AS ?statusChange)
+            }} ;
+            WITH {graph_load_requests}
+            DELETE {{
+                ?loadRequest a {load_request_type} .
+                ?loadRequest a ?loadRequestType .
+                ?loadRequest {rdfs}label ?loadRequestLabel .
+                ?loadRequest {rdfs}comment ?loadRequestComment .
+                ?loadRequest {dataops}source ?loadRequestSource .
+                ?loadRequest {dataops}graph ?loadRequestGraph .
+            }}
+            INSERT {{
+                ?loadRequest a {load_request_type} .
+                ?loadRequest a {load_request_status_type} .
+                ?loadRequest {rdfs}label {load_request_label} .
+                ?loadRequest {dataops}source {source_iri} .
+                ?loadRequest {dataops}graph {graph_iri} .
+            }}
+            WHERE {{
+                VALUES ?loadRequest {{
+                    {load_request_iri}
+                }}
+                ?loadRequest a {load_request_type} .
+                ?loadRequest a ?loadRequestType .
+                OPTIONAL {{
+                    ?loadRequest {dataops}source ?loadRequestSource .
+                }}
+                OPTIONAL {{
+                    ?loadRequest {dataops}graph ?loadRequestGraph .
+                }}
+                OPTIONAL {{
+                    ?loadRequest {rdfs}label ?loadRequestLabel .
+                }}
+                OPTIONAL {{
+                    ?loadRequest {rdfs}comment ?loadRequestComment .
+                }}
+            }}
+        "#,
+        dataops = NS_PREFIX_DATAOPS,
+        rdfs = NS_PREFIX_RDFS,
+        graph_load_requests = Iri::new(graph_load_requests)?,
+        load_request_iri = Iri::new(load_request_iri(ekg_identifier_contexts, load_request_id))?,
+        source_iri = Iri::new(source_iri)?,
+        graph_iri = Iri::new(load_request.target_graph_uri())?,
+        load_request_type = CLASS_DATAOPS_LOAD_REQUEST.display_turtle(),
+        load_request_status_type = load_request_type.display_turtle(),
+        status_change_type = CLASS_DATAOPS_LOAD_REQUEST_STATUS_CHANGE.display_turtle(),
+        status_types = [
+            CLASS_DATAOPS_QUEUED_LOAD_REQUEST.deref(),
+            CLASS_DATAOPS_LOADING_LOAD_REQUEST.deref(),
+            CLASS_DATAOPS_FINISHED_LOAD_REQUEST.deref(),
+            CLASS_DATAOPS_FAILED_LOAD_REQUEST.deref(),
+        ]
+        .iter()
+        .map(|status_type| status_type.display_turtle().to_string())
+        .collect::<Vec<_>>()
+        .join(" "),
+        load_request_label = Literal::string(default_load_request_label(load_request_type, load_request_id, source_iri)),
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder()
+            .declare(NS_DATAOPS.deref())
+            .declare(NS_RDFS.deref())
+            .build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    clients.sparql_client.execute(&statement).await?;
+
+    tracing::info!(
+        "Load request status \"{}\" registered for load request {} in pipeline {}",
+        load_request_type,
+        load_request_id,
+        pipeline_id
+    );
+
+    Ok(())
+}
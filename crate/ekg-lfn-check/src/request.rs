@@ -1,7 +1,5 @@
-use ekg_aws_util::neptune::LoadRequest;
 use {
-    ekg_aws_util::lambda::LambdaResponse,
-    ekg_aws_util::ARN,
+    ekg_aws_util::{lambda::LambdaResponse, neptune::LoadRequest, ARN},
     serde::{Deserialize, Serialize},
 };
 
@@ -9,7 +7,7 @@ use {
 /// bulk load request has finished.
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Request {
-    pub load_request: LoadRequest,
+    pub load_request:     LoadRequest,
     pub rdf_load_sfn_arn: ARN,
-    pub load_output: LambdaResponse,
+    pub load_output:      LambdaResponse,
 }
@@ -1,24 +1,588 @@
 #![cfg(test)]
 
-#[ignore]
+use {
+    aws_sdk_neptunedata::types::{Format, S3BucketRegion},
+    ekg_aws_util::{
+        graph_store::OxigraphStore,
+        lambda::{load_request_iri, load_requests_graph_iri, LambdaDetailStatus, RetryPolicy},
+        neptune::{BulkLoader, InMemoryBulkLoader, LoadRequest},
+        object_store::InMemoryObjectStore,
+    },
+    ekg_identifier::{EkgIdentifierContexts, NS_DATAOPS},
+    ekg_mock_neptune_loader::{JobLifecycle, MockLoader, StartOutcome},
+    ekg_sparql::Prefixes,
+    std::ops::Deref,
+};
+
+/// The given query with the prefixes that the lambda functions use
+fn sparql(query: &str) -> Result<String, ekg_error::Error> {
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(query),
+    )?;
+    Ok(statement.to_string())
+}
+
+/// The tests point the SPARQL client to their own mock loader via environment
+/// variables, so they must not run at the same time
+static ENV_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
 #[test_log::test(tokio::test)]
 async fn test_check_01() -> Result<(), ekg_error::Error> {
     tracing::info!("test_check_01");
 
     ekg_identifier::EkgIdentifierContexts::default_test();
+    let ekg_identifier_contexts = ekg_identifier::EkgIdentifierContexts::from_env()?;
+    let store = OxigraphStore::new()?;
+    let clients = crate::Clients {
+        aws_neptunedata_client: InMemoryBulkLoader::new(),
+        sparql_client:          store.clone(),
+    };
+
+    let mut payload: serde_json::Value = serde_json::from_str(include_str!("../event.json"))?;
+    let load_request = serde_json::from_value::<LoadRequest>(payload["load_request"].clone())?;
+    let output = clients
+        .aws_neptunedata_client
+        .start_load(&load_request)
+        .await
+        .expect("could not start the loader job");
+    let load_id = output.payload.get("loadId").expect("missing loadId");
+    payload["LoadOutput"]["resultIdentifier"] = serde_json::Value::from(load_id.as_str());
+
+    // The load request as the load lambda function registers it, queued
+    let load_request = load_request_iri(&ekg_identifier_contexts, load_id.as_str());
+    let graph_load_requests = load_requests_graph_iri(&ekg_identifier_contexts, "test");
+    store.update(&sparql(
+        format!(
+            r#"
+            INSERT DATA {{
+                GRAPH <{graph_load_requests}> {{
+                    <{load_request}> a dataops:LoadRequest, dataops:QueuedLoadRequest .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?;
+
+    // The in-memory loader job advances one status per check
+    for expected in [
+        LambdaDetailStatus::LoaderJobInQueue,
+        LambdaDetailStatus::LoaderJobInProgress,
+        LambdaDetailStatus::LoaderJobCompleted,
+    ] {
+        let lambda_output = crate::handle_lambda_payload(
+            payload.clone(),
+            &ekg_identifier_contexts,
+            "test",
+            clients.clone(),
+        )
+        .await
+        .expect("check failed");
+        tracing::info!("result: {:#?}", lambda_output);
+        assert_eq!(lambda_output.status_code, 200);
+        assert_eq!(lambda_output.detail_status, expected);
+        payload["CheckOutput"] = serde_json::to_value(&lambda_output)?;
+    }
+    assert_eq!(
+        payload["CheckOutput"]["attempt"],
+        serde_json::Value::from(3)
+    );
+
+    let jobs = clients.aws_neptunedata_client.jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].checks, 3);
+
+    // Checking a job that is still queued is not a transition, the other two
+    // checks are
+    let solutions = store.select(&sparql(
+        format!(
+            r#"
+            SELECT ?status ?previousStatus
+            WHERE {{
+                GRAPH <{graph_load_requests}> {{
+                    ?statusChange a dataops:LoadRequestStatusChange ;
+                        dataops:loadRequest <{load_request}> ;
+                        dataops:fromStatus ?previousStatus ;
+                        dataops:toStatus ?status .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?;
+    let mut transitions = solutions
+        .iter()
+        .map(|solution| {
+            (
+                local_name(&solution["previousStatus"]),
+                local_name(&solution["status"]),
+            )
+        })
+        .collect::<Vec<_>>();
+    transitions.sort();
+    assert_eq!(transitions, vec![
+        ("LoadingLoadRequest", "FinishedLoadRequest"),
+        ("QueuedLoadRequest", "LoadingLoadRequest"),
+    ]);
+
+    // The load request is finished, and nothing else
+    let solutions = store.select(&sparql(
+        format!(
+            r#"
+            SELECT ?status
+            WHERE {{
+                GRAPH <{graph_load_requests}> {{
+                    VALUES ?status {{
+                        dataops:QueuedLoadRequest
+                        dataops:LoadingLoadRequest
+                        dataops:FinishedLoadRequest
+                        dataops:FailedLoadRequest
+                    }}
+                    <{load_request}> a ?status .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?;
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(
+        local_name(&solutions[0]["status"]),
+        "FinishedLoadRequest"
+    );
+
+    // Only the finished load has its statistics registered, once
+    let solutions = store.select(&sparql(
+        format!(
+            r#"
+            SELECT ?totalRecords ?parsingErrors ?duration
+            WHERE {{
+                GRAPH <{graph_load_requests}> {{
+                    <{load_request}> dataops:totalRecords ?totalRecords ;
+                        dataops:parsingErrors ?parsingErrors ;
+                        dataops:duration ?duration .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?;
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(solutions[0]["totalRecords"], "0");
+    assert_eq!(solutions[0]["parsingErrors"], "0");
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_check_load_errors() -> Result<(), ekg_error::Error> {
+    tracing::info!("test_check_load_errors");
+    let _env = ENV_LOCK.lock().await;
+
+    // More errors than fit on one page, so the check has to page through them
+    let mut lifecycle = JobLifecycle::new(["LOAD_FAILED"]);
+    for record_num in 1..=150 {
+        lifecycle = lifecycle.with_error(
+            "PARSING_ERROR",
+            "Expected '.', found '<'",
+            "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl",
+            record_num,
+        );
+    }
+    let mock_loader = MockLoader::new();
+    mock_loader.push_start_outcome(StartOutcome::Accept(lifecycle));
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    ekg_identifier::EkgIdentifierContexts::default_test();
+    let ekg_identifier_contexts = ekg_identifier::EkgIdentifierContexts::from_env()?;
+    std::env::set_var(
+        "EKG_SPARQL_LOADER_ENDPOINT",
+        handle.loader_endpoint(),
+    );
+    std::env::set_var(
+        "EKG_SPARQL_QUERY_ENDPOINT",
+        handle.sparql_endpoint(),
+    );
+    std::env::set_var(
+        "EKG_SPARQL_UPDATE_ENDPOINT",
+        handle.sparql_endpoint(),
+    );
+    std::env::set_var("AWS_REGION", "antartica-01");
+    std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDTEST");
+    std::env::set_var("AWS_SECRET_ACCESS_KEY", "SECRET");
+    let aws_config = aws_config::load_from_env().await;
+    let clients = crate::Clients {
+        aws_neptunedata_client: ekg_aws_util::neptune::get_neptunedata_client(&aws_config)?,
+        sparql_client:          ekg_sparql::SPARQLClient::from_env().await?,
+    };
+
+    let output = clients
+        .aws_neptunedata_client
+        .start_loader_job()
+        .source("s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl")
+        .format(Format::Turtle)
+        .iam_role_arn("arn:aws:iam::123456789012:role/ekgf-dt-dev-neptune-load")
+        .s3_bucket_region(S3BucketRegion::from("antartica-01"))
+        .send()
+        .await
+        .expect("could not start the loader job");
+    let load_id = output.payload.get("loadId").expect("missing loadId");
+
+    let mut payload: serde_json::Value = serde_json::from_str(include_str!("../event.json"))?;
+    payload["LoadOutput"]["resultIdentifier"] = serde_json::Value::from(load_id.as_str());
+    let lambda_output =
+        crate::handle_lambda_payload(payload, &ekg_identifier_contexts, "test", clients)
+            .await
+            .expect("check failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::LoaderJobFailed
+    ));
+
+    let sparql_updates = mock_loader.sparql_updates();
+    let load_errors = sparql_updates
+        .iter()
+        .find(|sparql| sparql.contains("dataops:LoadError"))
+        .expect("no load errors registered");
+    assert_eq!(
+        load_errors.matches("dataops:errorCode").count(),
+        150
+    );
+    assert!(load_errors.contains("-error-150>"));
+    assert!(load_errors.contains(r#"dataops:errorMessage "Expected '.', found '<'""#));
+
+    // The transition to the failed status is recorded in the status history
+    assert!(sparql_updates.iter().any(|sparql| {
+        sparql.contains("dataops:LoadRequestStatusChange") &&
+            sparql.contains("dataops:previousStatusChange")
+    }));
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_check_status_history() -> Result<(), ekg_error::Error> {
+    EkgIdentifierContexts::default_test();
+    let ekg_identifier_contexts = EkgIdentifierContexts::from_env()?;
+    let bulk_loader = InMemoryBulkLoader::new();
+    let store = OxigraphStore::new()?;
+
+    // Let the load lambda function start the job and register the queued load
+    // request, in the same store
+    let mut payload: serde_json::Value =
+        serde_json::from_str(include_str!("../../ekg-lfn-load/event.json"))?;
+    let load_output =
+        ekg_lfn_load::handle_lambda_payload(payload.clone(), "test", ekg_lfn_load::Clients {
+            aws_neptunedata_client: bulk_loader.clone(),
+            sparql_client:          store.clone(),
+            aws_s3_client:          InMemoryObjectStore::new(),
+        })
+        .await
+        .expect("load request failed");
+    let load_id = load_output
+        .result_identifier
+        .clone()
+        .expect("missing load id");
+    payload["LoadOutput"] = serde_json::to_value(&load_output)?;
+
+    let clients = crate::Clients {
+        aws_neptunedata_client: bulk_loader,
+        sparql_client:          store.clone(),
+    };
+    for _ in 0..3 {
+        let check_output = crate::handle_lambda_payload(
+            payload.clone(),
+            &ekg_identifier_contexts,
+            "test",
+            clients.clone(),
+        )
+        .await
+        .expect("check failed");
+        payload["CheckOutput"] = serde_json::to_value(&check_output)?;
+    }
+    assert_eq!(
+        payload["CheckOutput"]["detailStatus"],
+        "LoaderJobCompleted"
+    );
+
+    let load_request = load_request_iri(&ekg_identifier_contexts, load_id.as_str());
+
+    // Queued (by the load lambda function), loading and finished: checking a
+    // job that is still queued is not a transition
+    let solutions = store.select(&sparql(
+        format!(
+            r#"
+            SELECT ?status ?previousStatus
+            WHERE {{
+                GRAPH ?graph {{
+                    ?statusChange a dataops:LoadRequestStatusChange ;
+                        dataops:loadRequest <{load_request}> ;
+                        dataops:toStatus ?status .
+                    OPTIONAL {{ ?statusChange dataops:fromStatus ?previousStatus }}
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?;
+    let mut transitions = solutions
+        .iter()
+        .map(|solution| {
+            (
+                solution
+                    .get("previousStatus")
+                    .map(|status| local_name(status.as_str())),
+                local_name(&solution["status"]),
+            )
+        })
+        .collect::<Vec<_>>();
+    transitions.sort();
+    assert_eq!(transitions, vec![
+        (None, "QueuedLoadRequest"),
+        (Some("LoadingLoadRequest"), "FinishedLoadRequest"),
+        (Some("QueuedLoadRequest"), "LoadingLoadRequest"),
+    ]);
+
+    // The latest status change is the one to finished, which links back to the
+    // one before it
+    assert!(store.ask(&sparql(
+        format!(
+            r#"
+            ASK {{
+                GRAPH ?graph {{
+                    <{load_request}> a dataops:FinishedLoadRequest ;
+                        dataops:latestStatusChange ?latest .
+                    ?latest dataops:toStatus dataops:FinishedLoadRequest ;
+                        dataops:previousStatusChange/dataops:toStatus dataops:LoadingLoadRequest .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?);
+    // The load request has exactly one status
+    assert!(!store.ask(&sparql(
+        format!(
+            r#"
+            ASK {{
+                GRAPH ?graph {{
+                    VALUES ?status {{ dataops:QueuedLoadRequest dataops:LoadingLoadRequest }}
+                    <{load_request}> a ?status .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?);
+    // The status is not described by the loader job status payload, the
+    // statistics, errors and status history are
+    assert!(!store.ask(&sparql(
+        format!(
+            r#"
+            ASK {{
+                GRAPH ?graph {{
+                    <{load_request}> <http://www.w3.org/2000/01/rdf-schema#comment> ?comment .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?);
+    // The statistics of the finished load were registered
+    assert!(store.ask(&sparql(
+        format!(
+            r#"
+            ASK {{
+                GRAPH ?graph {{
+                    <{load_request}> dataops:totalRecords 0 ;
+                        dataops:duration ?duration .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?);
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_check_retry_budget_exhausted() -> Result<(), ekg_error::Error> {
+    EkgIdentifierContexts::default_test();
+    let ekg_identifier_contexts = EkgIdentifierContexts::from_env()?;
+    // A loader job that never gets out of the queue
+    let bulk_loader = InMemoryBulkLoader::new().with_statuses(["LOAD_IN_QUEUE"]);
+    let store = OxigraphStore::new()?;
+
+    // A load that replaces the target graph via a staging graph
+    let staging_graph = "https://placeholder.kg/graph/staging/retry-budget-exhausted";
+    let mut payload: serde_json::Value =
+        serde_json::from_str(include_str!("../../ekg-lfn-load/event.json"))?;
+    payload["load_request"]["loadStrategy"] = "replace".into();
+    payload["load_request"]["targetGraphUri"] =
+        payload["load_request"]["parserConfiguration"]["namedGraphUri"].clone();
+    payload["load_request"]["parserConfiguration"]["namedGraphUri"] = staging_graph.into();
+    let load_output =
+        ekg_lfn_load::handle_lambda_payload(payload.clone(), "test", ekg_lfn_load::Clients {
+            aws_neptunedata_client: bulk_loader.clone(),
+            sparql_client:          store.clone(),
+            aws_s3_client:          InMemoryObjectStore::new(),
+        })
+        .await
+        .expect("load request failed");
+    let load_id = load_output
+        .result_identifier
+        .clone()
+        .expect("missing load id");
+    payload["LoadOutput"] = serde_json::to_value(&load_output)?;
+    // What the loader job has loaded so far
+    store.update(
+        format!("INSERT DATA {{ GRAPH <{staging_graph}> {{ <urn:s> <urn:p> <urn:o> }} }}").as_str(),
+    )?;
+
+    // The check before the last allowed attempt
+    let clients = crate::Clients {
+        aws_neptunedata_client: bulk_loader.clone(),
+        sparql_client:          store.clone(),
+    };
+    let mut check_output = crate::handle_lambda_payload(
+        payload.clone(),
+        &ekg_identifier_contexts,
+        "test",
+        clients.clone(),
+    )
+    .await
+    .expect("check failed");
+    assert_eq!(
+        check_output.detail_status,
+        LambdaDetailStatus::LoaderJobInQueue
+    );
+    check_output.attempt = RetryPolicy::default().max_attempts - 1;
+    payload["CheckOutput"] = serde_json::to_value(&check_output)?;
+
+    let check_output = crate::handle_lambda_payload(
+        payload.clone(),
+        &ekg_identifier_contexts,
+        "test",
+        clients,
+    )
+    .await
+    .expect("check failed");
+    assert_eq!(
+        check_output.detail_status,
+        LambdaDetailStatus::RetryBudgetExhausted
+    );
+
+    // The loader job is cancelled and its staging graph is dropped
+    let jobs = bulk_loader.jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(
+        jobs[0].status().overall_status.status,
+        "LOAD_CANCELLED_BY_USER"
+    );
+    assert!(!store.ask(format!("ASK {{ GRAPH <{staging_graph}> {{ ?s ?p ?o }} }}").as_str())?);
+
+    let load_request = load_request_iri(&ekg_identifier_contexts, load_id.as_str());
+    assert!(store.ask(&sparql(
+        format!(
+            r#"
+            ASK {{
+                GRAPH ?graph {{
+                    <{load_request}> a dataops:FailedLoadRequest .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?)?);
+
+    Ok(())
+}
+
+fn local_name(iri: &str) -> &str { iri.rsplit(['#', '/']).next().unwrap_or(iri) }
+
+#[test_log::test(tokio::test)]
+async fn test_check_load_errors_page_failure() -> Result<(), ekg_error::Error> {
+    // The second page of errors cannot be fetched, the errors of the first page
+    // are registered anyway and so is the failed status
+    let _env = ENV_LOCK.lock().await;
+    let mut lifecycle = JobLifecycle::new(["LOAD_FAILED"]);
+    for record_num in 1..=150 {
+        lifecycle = lifecycle.with_error(
+            "PARSING_ERROR",
+            "Expected '.', found '<'",
+            "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl",
+            record_num,
+        );
+    }
+    let mock_loader = MockLoader::new();
+    mock_loader.push_start_outcome(StartOutcome::Accept(lifecycle));
+    mock_loader.fail_error_pages_from(2);
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    ekg_identifier::EkgIdentifierContexts::default_test();
+    let ekg_identifier_contexts = ekg_identifier::EkgIdentifierContexts::from_env()?;
     std::env::set_var(
         "EKG_SPARQL_LOADER_ENDPOINT",
-        "http://localhost:8787/checker",
+        handle.loader_endpoint(),
+    );
+    std::env::set_var(
+        "EKG_SPARQL_QUERY_ENDPOINT",
+        handle.sparql_endpoint(),
+    );
+    std::env::set_var(
+        "EKG_SPARQL_UPDATE_ENDPOINT",
+        handle.sparql_endpoint(),
     );
     std::env::set_var("AWS_REGION", "antartica-01");
-    let aws_config = aws_config::from_env().load().await;
-    let aws_neptunedata_client = ekg_aws_util::neptune::get_neptunedata_client(&aws_config)?;
-    let event = include_str!("../event.json");
-    let request_as_value: serde_json::Value = serde_json::from_str(event).unwrap();
-    println!("result: {:#?}", request_as_value);
+    std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDTEST");
+    std::env::set_var("AWS_SECRET_ACCESS_KEY", "SECRET");
+    let aws_config = aws_config::load_from_env().await;
+    let clients = crate::Clients {
+        aws_neptunedata_client: ekg_aws_util::neptune::get_neptunedata_client(&aws_config)?,
+        sparql_client:          ekg_sparql::SPARQLClient::from_env().await?,
+    };
+
+    let output = clients
+        .aws_neptunedata_client
+        .start_loader_job()
+        .source("s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl")
+        .format(Format::Turtle)
+        .iam_role_arn("arn:aws:iam::123456789012:role/ekgf-dt-dev-neptune-load")
+        .s3_bucket_region(S3BucketRegion::from("antartica-01"))
+        .send()
+        .await
+        .expect("could not start the loader job");
+    let load_id = output.payload.get("loadId").expect("missing loadId");
+
+    let mut payload: serde_json::Value = serde_json::from_str(include_str!("../event.json"))?;
+    payload["LoadOutput"]["resultIdentifier"] = serde_json::Value::from(load_id.as_str());
     let lambda_output =
-        crate::handle_lambda_payload(request_as_value.clone(), aws_neptunedata_client).await?;
-    println!("result: {:#?}", lambda_output);
-    assert_eq!(lambda_output.status_code, 200);
+        crate::handle_lambda_payload(payload, &ekg_identifier_contexts, "test", clients)
+            .await
+            .expect("check failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::LoaderJobFailed
+    ));
+
+    let sparql_updates = mock_loader.sparql_updates();
+    let load_errors = sparql_updates
+        .iter()
+        .find(|sparql| sparql.contains("dataops:LoadError"))
+        .expect("no load errors registered");
+    assert_eq!(
+        load_errors.matches("dataops:errorCode").count(),
+        100
+    );
+    assert!(sparql_updates
+        .iter()
+        .any(|sparql| sparql.contains("dataops:LoadRequestStatusChange")));
+
     Ok(())
 }
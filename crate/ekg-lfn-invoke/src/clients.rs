@@ -0,0 +1,29 @@
+use {
+    ekg_aws_util::{
+        routing::RoutingTable,
+        sns::{HttpsCertificateProvider, SnsSignatureVerifier},
+    },
+    std::sync::Arc,
+};
+
+/// The clients that the lambda function uses, the real ones by default, see
+/// the `fake` feature of `ekg-aws-util` for in-memory ones.
+#[derive(Clone)]
+pub struct Clients<
+    G = ekg_sparql::SPARQLClient,
+    S = aws_sdk_s3::Client,
+    C = HttpsCertificateProvider,
+> {
+    pub aws_sfn_client:         aws_sdk_sfn::Client,
+    pub sparql_client:          G,
+    /// Used to check whether a deleted object still has a current version
+    pub aws_s3_client:          S,
+    /// Loaded once, when the lambda function starts
+    pub routing_table:          Arc<RoutingTable>,
+    /// The prefix of the keys of the rewritten copies that the load lambda
+    /// function stages in the same bucket, their events are skipped
+    pub staging_prefix:         String,
+    /// Rejects the SNS notifications that are not signed by SNS, only when
+    /// `EKG_VERIFY_SNS_SIGNATURES` is `true`
+    pub sns_signature_verifier: Option<SnsSignatureVerifier<C>>,
+}
@@ -1,3 +1,3 @@
-pub use request::Request;
+pub use request::{Message, Request};
 
 mod request;
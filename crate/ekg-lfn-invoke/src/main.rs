@@ -1,16 +1,35 @@
 /// See https://github.com/awslabs/aws-lambda-rust-runtime for more info on Rust runtime for AWS Lambda
 use lambda_runtime::{service_fn, Error as LambdaError, LambdaEvent};
-pub use request::Request;
+pub use request::{Message, Request};
 use {
-    crate::sfn_state_machine::StateMachine,
-    ekg_aws_util::{S3EventRecord, S3EventRecords, SnsEventRecord},
+    crate::{
+        clients::Clients,
+        outcome::{InvokeResponse, RecordError, RecordOutcome, RecordResult},
+        sfn_state_machine::StateMachine,
+    },
+    ekg_aws_util::{
+        graph_store::GraphStore,
+        lambda::{prune_merge_targets, LambdaResponse, NS_PROV},
+        neptune::GraphNamingStrategy,
+        object_store::ObjectStore,
+        placeholder::staging_prefix_from_env,
+        routing::{RoutingRule, RoutingTable},
+        sns::{CertificateProvider, SnsSignatureVerifier},
+        sparql::Iri,
+        S3EventRecord,
+    },
     ekg_error::Error,
-    ekg_identifier::EkgIdentifierContexts,
+    ekg_identifier::{EkgIdentifierContexts, NS_DATAOPS},
+    ekg_sparql::Prefixes,
     ekg_util::env::{mandatory_env_var, mandatory_env_var_static},
+    indoc::formatdoc,
     serde::Serialize,
-    serde_json::{json, Value},
+    serde_json::Value,
+    std::{ops::Deref, sync::Arc},
 };
 
+mod clients;
+mod outcome;
 mod request;
 mod sfn_state_machine;
 #[cfg(test)]
@@ -31,32 +50,41 @@ async fn main() -> Result<(), LambdaError> {
 
     // Get the AWS config
     let aws_config = aws_config::load_from_env().await;
-    let aws_sfn_client = aws_sdk_sfn::Client::new(&aws_config);
+    let aws_s3_client = aws_sdk_s3::Client::new(&aws_config);
+    let clients = Clients {
+        aws_sfn_client: aws_sdk_sfn::Client::new(&aws_config),
+        // The SPARQL client is used to drop the named graphs of deleted S3 objects
+        sparql_client: ekg_sparql::SPARQLClient::from_env().await?,
+        routing_table: Arc::new(RoutingTable::from_env(&aws_s3_client).await?),
+        aws_s3_client,
+        staging_prefix: staging_prefix_from_env(),
+        sns_signature_verifier: SnsSignatureVerifier::from_env()?,
+    };
 
     // call the actual handler of the request
-    let func = service_fn(move |req| handle_lambda_event(req, pipeline_id, aws_sfn_client.clone()));
+    let func = service_fn(move |req| handle_lambda_event(req, pipeline_id, clients.clone()));
     lambda_runtime::run(func).await?;
     Ok(())
 }
 
 // noinspection DuplicatedCode
 /// The actual handler of the Lambda request.
-pub(crate) async fn handle_lambda_event(
+pub(crate) async fn handle_lambda_event<G: GraphStore, S: ObjectStore, C: CertificateProvider>(
     event: LambdaEvent<Value>,
     pipeline_id: &'static str,
-    aws_sfn_client: aws_sdk_sfn::Client,
+    clients: Clients<G, S, C>,
 ) -> Result<Value, LambdaError> {
     tracing::trace!("Event {:#?}\n\n", event.clone());
 
     let (payload, _ctx) = event.into_parts();
 
-    handle_lambda_payload(payload, pipeline_id, aws_sfn_client).await
+    handle_lambda_payload(payload, pipeline_id, clients).await
 }
 
-pub(crate) async fn handle_lambda_payload(
+pub(crate) async fn handle_lambda_payload<G: GraphStore, S: ObjectStore, C: CertificateProvider>(
     payload: Value,
     pipeline_id: &'static str,
-    aws_sfn_client: aws_sdk_sfn::Client,
+    clients: Clients<G, S, C>,
 ) -> Result<Value, LambdaError> {
     tracing::trace!(
         "Payload {}",
@@ -68,7 +96,7 @@ pub(crate) async fn handle_lambda_payload(
         e
     })?;
 
-    handle_lambda_request(&request, pipeline_id, aws_sfn_client)
+    handle_lambda_request(request, pipeline_id, clients)
         .await
         .map_err(|e| {
             tracing::error!("Error handling request: {}", e);
@@ -76,85 +104,428 @@ pub(crate) async fn handle_lambda_payload(
         })
 }
 
-pub(crate) async fn handle_lambda_request(
-    request: &Request,
+pub(crate) async fn handle_lambda_request<G: GraphStore, S: ObjectStore, C: CertificateProvider>(
+    request: Request,
     pipeline_id: &'static str,
-    aws_sfn_client: aws_sdk_sfn::Client,
+    clients: Clients<G, S, C>,
 ) -> Result<Value, Error> {
     let identifier_contexts = EkgIdentifierContexts::from_env()?;
 
-    for record in &request.records {
-        handle_sns_event_record(
-            &record,
-            pipeline_id,
-            &identifier_contexts,
-            aws_sfn_client.clone(),
-        )
-        .await?;
+    // Every record is processed on its own, a failing record does not stop the
+    // records after it
+    let is_sqs = request.is_sqs();
+    let mut records = Vec::new();
+    for message in request
+        .into_messages(clients.sns_signature_verifier.as_ref())
+        .await
+    {
+        records.extend(
+            handle_message(
+                message,
+                pipeline_id,
+                &identifier_contexts,
+                clients.clone(),
+            )
+            .await,
+        );
     }
+    let response = InvokeResponse::new(records);
 
-    Ok(json!({"statusCode": 200}))
+    // Only the SQS messages with a failed record become visible in the queue
+    // again, the other ones are done
+    if is_sqs {
+        let response = response.with_batch_item_failures();
+        let response_as_value = serde_json::to_value(&response)?;
+        tracing::info!("Response: {}", response_as_value);
+        return Ok(response_as_value);
+    }
+
+    let response_as_value = serde_json::to_value(&response)?;
+    tracing::info!("Response: {}", response_as_value);
+
+    // Fail the invocation, so that SNS (or S3 or EventBridge) delivers the event
+    // again, only if that could help. Records that were processed already are not
+    // processed twice, see S3EventRecord::execution_name().
+    let failures = response
+        .retryable_failures()
+        .map(|record| {
+            format!(
+                "{}: {}",
+                record
+                    .s3_uri
+                    .as_deref()
+                    .unwrap_or(record.message_id.as_str()),
+                match &record.outcome {
+                    RecordOutcome::Failed { reason, .. } => reason.as_str(),
+                    _ => "",
+                }
+            )
+        })
+        .collect::<Vec<_>>();
+    if !failures.is_empty() {
+        return Err(Error::ServiceError(format!(
+            "{} of {} records failed: {}",
+            failures.len(),
+            response.records.len(),
+            failures.join(", ")
+        )));
+    }
+
+    Ok(response_as_value)
 }
 
-async fn handle_sns_event_record(
-    s3_event_record: &SnsEventRecord,
+/// Process all S3 event records in the given message, returning the outcome
+/// of each of them
+async fn handle_message<G: GraphStore, S: ObjectStore, C: CertificateProvider>(
+    message: Message,
     pipeline_id: &'static str,
     identifier_contexts: &EkgIdentifierContexts,
-    aws_sfn_client: aws_sdk_sfn::Client,
-) -> Result<(), Error> {
-    let sns = &s3_event_record.sns;
-    tracing::trace!("SNS record: {:#?}", sns);
-    // Get the embedded JSON message
-    let message = &sns.message;
-    tracing::trace!("SNS Message: {:#?}", message);
-    // Convert to serde Value first, not straight to S3EventRecords to get better
-    // errors
-    let s3_event_records_as_value = serde_json::from_str::<Value>(&message)?;
-    let s3_event_records = serde_json::from_value::<S3EventRecords>(s3_event_records_as_value)?;
-    if s3_event_records.records.len() == 0 {
-        return Err(Error::NoInputRecords);
-    }
-    for s3_event_record in s3_event_records.records {
-        handle_s3_event_record(
+    clients: Clients<G, S, C>,
+) -> Vec<RecordResult> {
+    let message_id = message.id;
+
+    let s3_event_records = match message.s3_event_records {
+        Ok(s3_event_records) => s3_event_records,
+        Err(error) => {
+            tracing::error!(
+                "Could not parse message {}: {}",
+                message_id,
+                error
+            );
+            // Delivering the same message again won't help, unless the
+            // certificate to verify its signature could not be fetched
+            return vec![RecordResult {
+                message_id,
+                s3_uri: None,
+                outcome: RecordOutcome::failed(&error, message.is_retryable),
+            }];
+        },
+    };
+
+    let mut results = Vec::new();
+    for s3_event_record in s3_event_records {
+        let s3_uri = s3_event_record.s3_uri();
+        let outcome = match handle_s3_event_record(
             s3_event_record,
             pipeline_id,
-            &identifier_contexts,
-            aws_sfn_client.clone(),
+            identifier_contexts,
+            clients.clone(),
         )
-        .await?;
+        .await
+        {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                tracing::error!("Could not process {}: {}", s3_uri, error);
+                error.into()
+            },
+        };
+        results.push(RecordResult {
+            message_id: message_id.clone(),
+            s3_uri: Some(s3_uri),
+            outcome,
+        });
     }
-    Ok(())
+    results
 }
 
-async fn handle_s3_event_record(
+async fn handle_s3_event_record<G: GraphStore, S: ObjectStore, C: CertificateProvider>(
     s3_event_record: S3EventRecord,
     pipeline_id: &'static str,
     identifier_contexts: &EkgIdentifierContexts,
-    aws_sfn_client: aws_sdk_sfn::Client,
-) -> Result<(), Error> {
+    clients: Clients<G, S, C>,
+) -> Result<RecordOutcome, RecordError> {
     tracing::trace!("S3 Event Record: {:#?}", s3_event_record);
 
+    let key = s3_event_record.s3.object.decoded_key();
+    let key = key.as_str();
+
+    // The load lambda function writes the copies of the objects with their
+    // placeholders rewritten to the same bucket, don't load those again
+    if key.starts_with(clients.staging_prefix.as_str()) {
+        let reason = format!(
+            "Skipping {}: a staged copy of another object",
+            s3_event_record.s3_uri()
+        );
+        tracing::info!("{}", reason);
+        return Ok(RecordOutcome::skipped(reason));
+    }
+
+    // The routing table decides which pipeline loads the object, if any
+    let routing_table = clients.routing_table.clone();
+    let routing_rule = routing_table.route(key);
+    if routing_rule.is_some_and(|routing_rule| routing_rule.skip) {
+        let reason = format!(
+            "Skipping {}: excluded by the routing table",
+            s3_event_record.s3_uri()
+        );
+        tracing::info!("{}", reason);
+        return Ok(RecordOutcome::skipped(reason));
+    }
+    let routed_pipeline_id = routing_rule
+        .and_then(|routing_rule| routing_rule.pipeline.as_deref())
+        .unwrap_or(pipeline_id);
+
+    let event_name = &s3_event_record.event_name;
+    if !event_name.is_object_removed() &&
+        !event_name.is_object_created() &&
+        !event_name.is_object_tagging()
+    {
+        let reason = format!(
+            "Ignoring S3 event {} for {}",
+            event_name,
+            s3_event_record.s3_uri()
+        );
+        tracing::info!("{}", reason);
+        return Ok(RecordOutcome::skipped(reason));
+    }
+
+    // The RDF format is what the routing rule says it is, or else it follows from
+    // the content type of the object or its extension. S3 event notifications do
+    // not carry the content type, so it takes a HeadObject, which is pointless for
+    // an object that has been removed.
+    let format = match routing_rule.and_then(|routing_rule| routing_rule.format.clone()) {
+        Some(format) => Some(format),
+        None if event_name.is_object_removed() => ekg_aws_util::neptune::resolve_format(key, None),
+        None => {
+            let content_type = clients
+                .aws_s3_client
+                .content_type(s3_event_record.s3.bucket.name.as_str(), key)
+                .await
+                .map_err(RecordError::Transient)?;
+            ekg_aws_util::neptune::resolve_format(key, content_type.as_deref())
+        },
+    };
+
+    if event_name.is_object_removed() {
+        return handle_s3_object_removed(
+            &s3_event_record,
+            routed_pipeline_id,
+            routing_rule,
+            format.is_some(),
+            identifier_contexts,
+            clients,
+        )
+        .await;
+    }
+
+    let Some(format) = format else {
+        let response = LambdaResponse::unsupported_rdf_format(key);
+        tracing::warn!("Skipping {}: {}", key, response.message);
+        return Ok(RecordOutcome::skipped(response.message));
+    };
+
+    // The Step Function that orchestrates the RDF Load of the routed pipeline
+    let rdf_load_sfn_arn = rdf_load_sfn_arn(&routing_table, pipeline_id, routed_pipeline_id)?;
     // Convert the S3 event record to a Neptune LoadRequest
     let load_request = ekg_aws_util::neptune::LoadRequest::from_s3_event_record(
         &s3_event_record,
+        format,
         &identifier_contexts,
+        routing_rule,
     )?;
     // Wrap that Neptune Load Request into an EKG Load Request adding the pipeline
-    // ID and the ARN of the Step Function that orchestrates the RDF Load
+    // ID and the ARN of the Step Function
     let sfn_input = ekg_lfn_load::Request {
         load_request,
-        pipeline_id: pipeline_id.to_string(),
-        rdf_load_sfn_arn: mandatory_env_var("rdf_load_sfn_arn", None)?,
+        pipeline_id: routed_pipeline_id.to_string(),
+        rdf_load_sfn_arn: rdf_load_sfn_arn.clone(),
+        load_output: None,
     };
     tracing::trace!("{:#?}", sfn_input);
 
     // Kick the Step Function off to start the RDF Load
-    StateMachine::new(aws_sfn_client)
+    let execution_name = s3_event_record.execution_name();
+    StateMachine::new(clients.aws_sfn_client)
         .start_execution(
-            mandatory_env_var("rdf_load_sfn_arn", None)?.as_str(),
+            rdf_load_sfn_arn.as_str(),
+            execution_name.as_str(),
             serde_json::to_value(sfn_input)?,
         )
-        .await?;
+        .await
+        .map_err(RecordError::Transient)?;
 
-    Ok(())
+    Ok(RecordOutcome::Started { execution_name })
+}
+
+/// The ARN of the Step Function of the given pipeline: our own one, or one of
+/// the `pipelines` of the routing table
+fn rdf_load_sfn_arn(
+    routing_table: &RoutingTable,
+    pipeline_id: &str,
+    routed_pipeline_id: &str,
+) -> Result<String, Error> {
+    if routed_pipeline_id == pipeline_id {
+        return mandatory_env_var("rdf_load_sfn_arn", None);
+    }
+    routing_table
+        .state_machine_arn(routed_pipeline_id)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            Error::ServiceError(format!(
+                "The routing table has no state machine for pipeline {}",
+                routed_pipeline_id
+            ))
+        })
+}
+
+/// Drop the named graph of an S3 object that has been deleted from the source
+/// bucket, remove the triples that only it contributed from the target graphs
+/// it was merged into and mark its `dataops:Dataset` as retired in the
+/// load-requests graph.
+///
+/// Deleting a specific version of an object in a versioning-enabled bucket
+/// deletes that version permanently, which may well be a noncurrent one. So
+/// unless the event created a delete marker, an object that still exists is
+/// left alone.
+///
+/// An object whose RDF format does not follow from its key (`is_rdf_key` is
+/// false) may have been loaded because of its content type, which is gone
+/// along with the object. Such an object is only retired if a load request
+/// recorded its graph, otherwise it is not an RDF file at all.
+async fn handle_s3_object_removed<G: GraphStore, S: ObjectStore, C: CertificateProvider>(
+    s3_event_record: &S3EventRecord,
+    pipeline_id: &str,
+    routing_rule: Option<&RoutingRule>,
+    is_rdf_key: bool,
+    identifier_contexts: &EkgIdentifierContexts,
+    clients: Clients<G, S, C>,
+) -> Result<RecordOutcome, RecordError> {
+    let object = &s3_event_record.s3.object;
+    if !s3_event_record.event_name.is_delete_marker_created() &&
+        object.version_id.is_some() &&
+        clients
+            .aws_s3_client
+            .object_exists(
+                s3_event_record.s3.bucket.name.as_str(),
+                object.decoded_key().as_str(),
+            )
+            .await
+            .map_err(RecordError::Transient)?
+    {
+        let reason = format!(
+            "Skipping {}: only its version {} has been deleted, the object still exists",
+            s3_event_record.s3_uri(),
+            object.version()
+        );
+        tracing::info!("{}", reason);
+        return Ok(RecordOutcome::skipped(reason));
+    }
+
+    let s3_uri = Iri::new(s3_event_record.s3_iri())?;
+    let graph_load_requests = Iri::new(ekg_aws_util::lambda::load_requests_graph_iri(
+        identifier_contexts,
+        pipeline_id,
+    ))?;
+    let mut graphs = recorded_graphs(&s3_uri, &graph_load_requests, &clients).await?;
+    if graphs.is_empty() && !is_rdf_key {
+        let response = LambdaResponse::unsupported_rdf_format(s3_uri.as_str());
+        tracing::warn!("Skipping {}: {}", s3_uri, response.message);
+        return Ok(RecordOutcome::skipped(response.message));
+    }
+    if graphs.is_empty() {
+        // Loaded before the load requests recorded their graph (if at all), name
+        // the graph the same way as LoadRequest::from_s3_event_record() does
+        graphs.push(Iri::new(
+            GraphNamingStrategy::for_routing_rule(routing_rule)?
+                .graph_iri(s3_event_record, identifier_contexts)?,
+        )?);
+    }
+
+    tracing::info!(
+        "S3 object {} has been removed ({}), dropping its named graphs {}",
+        s3_uri,
+        s3_event_record.event_name,
+        graphs
+            .iter()
+            .map(Iri::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let drop_graphs = graphs
+        .iter()
+        .map(|graph| {
+            formatdoc! {
+                r#"
+                    DROP SILENT GRAPH {graph} ;
+                    {prune} ;
+                    DELETE WHERE {{
+                        GRAPH {graph_load_requests} {{
+                            ?target <{prov}wasDerivedFrom> {graph} .
+                        }}
+                    }} ;
+                "#,
+                graph = graph,
+                graph_load_requests = graph_load_requests,
+                prune = prune_merge_targets(graph, &graph_load_requests),
+                prov = NS_PROV,
+            }
+        })
+        .collect::<String>();
+    let sparql = formatdoc! {
+        r#"
+            {drop_graphs}
+            INSERT {{
+                GRAPH {graph_load_requests} {{
+                    {s3_uri} a dataops:RetiredDataset ;
+                        dataops:retiredAt ?now .
+                }}
+            }}
+            WHERE {{
+                BIND(NOW() AS ?now)
+            }}
+        "#,
+        drop_graphs = drop_graphs,
+        graph_load_requests = graph_load_requests,
+        s3_uri = s3_uri,
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    clients
+        .sparql_client
+        .execute(&statement)
+        .await
+        .map_err(RecordError::Transient)?;
+
+    Ok(RecordOutcome::Retired)
+}
+
+/// The named graphs that the load requests of the given S3 object have
+/// recorded as its `dataops:graph`, so that the graph that is dropped is the
+/// one that was loaded, even if the graph naming has changed since.
+async fn recorded_graphs<G: GraphStore, S: ObjectStore, C: CertificateProvider>(
+    s3_uri: &Iri,
+    graph_load_requests: &Iri,
+    clients: &Clients<G, S, C>,
+) -> Result<Vec<Iri>, RecordError> {
+    let sparql = formatdoc! {
+        r#"
+            SELECT DISTINCT ?graph
+            WHERE {{
+                GRAPH {graph_load_requests} {{
+                    ?loadRequest dataops:source {s3_uri} ;
+                        dataops:graph ?graph .
+                }}
+            }}
+        "#,
+        graph_load_requests = graph_load_requests,
+        s3_uri = s3_uri,
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    Ok(clients
+        .sparql_client
+        .select(&statement)
+        .await
+        .map_err(RecordError::Transient)?
+        .into_iter()
+        .filter_map(|mut solution| solution.remove("graph"))
+        .map(Iri::new)
+        .collect::<Result<_, _>>()?)
 }
@@ -0,0 +1,142 @@
+use {
+    ekg_aws_util::SqsBatchItemFailure,
+    ekg_error::Error,
+    serde::Serialize,
+    std::fmt::{Display, Formatter},
+};
+
+/// What happened to one S3 event record of the request
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "outcome", rename_all = "camelCase")]
+pub enum RecordOutcome {
+    /// An execution of the RDF load state machine has been started (or
+    /// already existed, see [`crate::sfn_state_machine::StateMachine`])
+    #[serde(rename_all = "camelCase")]
+    Started { execution_name: String },
+    /// The S3 object has been removed and its named graph has been dropped
+    Retired,
+    /// Nothing to do for this record, such as an event type that we ignore or
+    /// a file that isn't RDF
+    Skipped { reason: String },
+    /// The record could not be processed. Only a `retryable` failure makes the
+    /// lambda function fail so that SNS delivers the event again, retrying a
+    /// record that can't be parsed doesn't help.
+    Failed { reason: String, retryable: bool },
+}
+
+impl RecordOutcome {
+    pub fn skipped(reason: impl Into<String>) -> Self { Self::Skipped { reason: reason.into() } }
+
+    pub fn failed(error: &Error, retryable: bool) -> Self {
+        Self::Failed { reason: error.to_string(), retryable }
+    }
+
+    pub fn is_retryable_failure(&self) -> bool {
+        matches!(self, Self::Failed { retryable: true, .. })
+    }
+}
+
+/// Why an S3 event record could not be processed. Any [`Error`] converts into
+/// an [`RecordError::Invalid`] one, the calls that go over the network have to
+/// say that their errors are [`RecordError::Transient`].
+#[derive(Debug)]
+pub enum RecordError {
+    /// The record, or the configuration of the lambda function, is wrong, such
+    /// as an invalid IRI or a pipeline without a state machine: processing the
+    /// record again fails in the same way
+    Invalid(Error),
+    /// Talking to S3, Step Functions or the SPARQL endpoint failed, which may
+    /// well work when the record is delivered again
+    Transient(Error),
+}
+
+impl RecordError {
+    pub fn is_retryable(&self) -> bool { matches!(self, Self::Transient(_)) }
+
+    pub fn error(&self) -> &Error {
+        match self {
+            Self::Invalid(error) | Self::Transient(error) => error,
+        }
+    }
+}
+
+impl From<Error> for RecordError {
+    fn from(error: Error) -> Self { Self::Invalid(error) }
+}
+
+impl From<serde_json::Error> for RecordError {
+    fn from(error: serde_json::Error) -> Self { Self::Invalid(error.into()) }
+}
+
+impl From<RecordError> for RecordOutcome {
+    fn from(error: RecordError) -> Self { Self::failed(error.error(), error.is_retryable()) }
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { self.error().fmt(f) }
+}
+
+/// The outcome of one S3 event record, with where it came from
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordResult {
+    /// The ID of the message that carried the S3 event record, see
+    /// [`crate::Message::id`]
+    pub message_id: String,
+    /// The S3 URI of the object, unless the message couldn't be parsed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_uri:     Option<String>,
+    #[serde(flatten)]
+    pub outcome:    RecordOutcome,
+}
+
+/// The response of the invoke lambda function: the outcome of every S3 event
+/// record in all of the messages of the request, in order
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InvokeResponse {
+    pub status_code:         u16,
+    pub records:             Vec<RecordResult>,
+    /// Only for a batch of SQS messages, see
+    /// [`InvokeResponse::with_batch_item_failures`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_item_failures: Option<Vec<SqsBatchItemFailure>>,
+}
+
+impl InvokeResponse {
+    pub fn new(records: Vec<RecordResult>) -> Self {
+        Self {
+            status_code: 200,
+            records,
+            batch_item_failures: None,
+        }
+    }
+
+    /// Report the SQS messages with a retryable failure as batch item
+    /// failures, so that only those are received again rather than the whole
+    /// batch. Messages that failed permanently are not reported, receiving
+    /// them again won't help.
+    pub fn with_batch_item_failures(self) -> Self {
+        let mut batch_item_failures: Vec<SqsBatchItemFailure> = Vec::new();
+        for record in self.retryable_failures() {
+            if !batch_item_failures
+                .iter()
+                .any(|failure| failure.item_identifier == record.message_id)
+            {
+                batch_item_failures
+                    .push(SqsBatchItemFailure { item_identifier: record.message_id.clone() });
+            }
+        }
+        Self {
+            batch_item_failures: Some(batch_item_failures),
+            ..self
+        }
+    }
+
+    /// The records that failed in a way that a retry of the event could fix
+    pub fn retryable_failures(&self) -> impl Iterator<Item = &RecordResult> {
+        self.records
+            .iter()
+            .filter(|record| record.outcome.is_retryable_failure())
+    }
+}
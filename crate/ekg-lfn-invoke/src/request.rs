@@ -1,7 +1,26 @@
-use {ekg_aws_util::sns::SnsEventRecord, serde::Deserialize};
+use {
+    ekg_aws_util::{
+        sns::{CertificateProvider, SnsSignatureVerifier},
+        EventBridgeEvent,
+        S3EventRecord,
+        S3EventRecords,
+        SnsEventRecord,
+        SnsRecord,
+        SqsEventRecord,
+    },
+    ekg_error::Error,
+    serde::{de, Deserialize, Deserializer},
+    serde_json::Value,
+};
 
-/// The incoming request structure.
-/// For example:
+/// The incoming request, its source is detected by the shape of the event:
+///
+/// - S3 event notifications published to SNS, the set-up of this module
+/// - S3 event notifications sent to the lambda function directly
+/// - An S3 event delivered by EventBridge (`Object Created`, `Object Deleted`)
+/// - A batch of SQS messages wrapping any of these
+///
+/// An SNS event for example:
 /// {
 ///   "Records": [
 ///     {
@@ -43,8 +62,198 @@ use {ekg_aws_util::sns::SnsEventRecord, serde::Deserialize};
 ///     }
 ///   ]
 /// }
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "PascalCase")]
-pub struct Request {
-    pub records: Vec<SnsEventRecord>,
+#[derive(Debug)]
+pub enum Request {
+    Sns(Vec<SnsEventRecord>),
+    S3(Vec<S3EventRecord>),
+    EventBridge(EventBridgeEvent),
+    Sqs(Vec<SqsEventRecord>),
+}
+
+/// One message of a request, carrying zero or more S3 event records
+#[derive(Debug)]
+pub struct Message {
+    /// The ID of the message: the SNS message ID, the SQS message ID (which is
+    /// what SQS batch item failures refer to), the EventBridge event ID or,
+    /// for an S3 event notification sent directly, the S3 request ID
+    pub id:               String,
+    /// The S3 event records of the message, or why they could not be
+    /// extracted from it
+    pub s3_event_records: Result<Vec<S3EventRecord>, Error>,
+    /// Whether delivering the message again could help when its S3 event
+    /// records could not be extracted, which is only the case when the
+    /// certificate to verify its signature could not be fetched
+    pub is_retryable:     bool,
+}
+
+impl Message {
+    fn new(id: String, s3_event_records: Result<Vec<S3EventRecord>, Error>) -> Self {
+        Self { id, s3_event_records, is_retryable: false }
+    }
+
+    /// The message with the given JSON, an SNS notification (of which the
+    /// signature is verified first, given a verifier) or anything that
+    /// [`s3_event_records_from_json`] supports
+    async fn from_json<P: CertificateProvider>(
+        id: String,
+        json: &str,
+        sns_signature_verifier: Option<&SnsSignatureVerifier<P>>,
+    ) -> Self {
+        if let Some(sns_signature_verifier) = sns_signature_verifier {
+            let sns_notification = serde_json::from_str::<Value>(json)
+                .ok()
+                .filter(|value| value.get("Type").and_then(Value::as_str) == Some("Notification"))
+                .and_then(|value| serde_json::from_value::<SnsRecord>(value).ok());
+            if let Some(sns_notification) = sns_notification {
+                if let Err(error) = sns_signature_verifier.verify(&sns_notification).await {
+                    return Self {
+                        id,
+                        is_retryable: error.is_retryable(),
+                        s3_event_records: Err(error.into()),
+                    };
+                }
+            }
+        }
+        Self::new(id, s3_event_records_from_json(json))
+    }
+}
+
+impl Request {
+    pub fn from_value(mut value: Value) -> Result<Self, Error> {
+        if let Some(records) = value.get_mut("Records").map(Value::take) {
+            let event_source = records
+                .get(0)
+                .and_then(|record| {
+                    record
+                        .get("EventSource")
+                        .or_else(|| record.get("eventSource"))
+                })
+                .and_then(Value::as_str)
+                .unwrap_or("aws:sns")
+                .to_string();
+            return match event_source.as_str() {
+                "aws:sns" => Ok(Self::Sns(serde_json::from_value(records)?)),
+                "aws:s3" => Ok(Self::S3(serde_json::from_value(records)?)),
+                "aws:sqs" => Ok(Self::Sqs(serde_json::from_value(records)?)),
+                other => {
+                    Err(Error::ServiceError(format!(
+                        "Unsupported event source {}",
+                        other
+                    )))
+                },
+            };
+        }
+        if value.get("detail-type").is_some() {
+            return Ok(Self::EventBridge(serde_json::from_value(value)?));
+        }
+        Err(Error::ServiceError(format!(
+            "Unsupported event: {}",
+            value
+        )))
+    }
+
+    /// Whether the lambda function should respond with the SQS batch item
+    /// failures rather than fail
+    pub fn is_sqs(&self) -> bool { matches!(self, Self::Sqs(_)) }
+
+    /// The messages of the request. Given a verifier, the SNS notifications,
+    /// also the ones that SQS messages wrap, must be signed by SNS: the S3
+    /// event records of the other ones are not extracted.
+    pub async fn into_messages<P: CertificateProvider>(
+        self,
+        sns_signature_verifier: Option<&SnsSignatureVerifier<P>>,
+    ) -> Vec<Message> {
+        match self {
+            Self::Sns(records) => {
+                let mut messages = Vec::with_capacity(records.len());
+                for record in records {
+                    if let Some(sns_signature_verifier) = sns_signature_verifier {
+                        if let Err(error) = sns_signature_verifier.verify(&record.sns).await {
+                            messages.push(Message {
+                                id:               record.sns.message_id,
+                                is_retryable:     error.is_retryable(),
+                                s3_event_records: Err(error.into()),
+                            });
+                            continue;
+                        }
+                    }
+                    messages.push(Message::new(
+                        record.sns.message_id,
+                        s3_event_records_from_json(record.sns.message.as_str()),
+                    ));
+                }
+                messages
+            },
+            Self::S3(records) => {
+                records
+                    .into_iter()
+                    .map(|record| {
+                        Message::new(
+                            record.response_elements.x_amz_request_id.clone(),
+                            Ok(vec![record]),
+                        )
+                    })
+                    .collect()
+            },
+            Self::EventBridge(event) => {
+                let s3_event_records = event.s3_event_record().map(|record| vec![record]);
+                vec![Message::new(event.id, s3_event_records)]
+            },
+            Self::Sqs(records) => {
+                let mut messages = Vec::with_capacity(records.len());
+                for record in records {
+                    messages.push(
+                        Message::from_json(
+                            record.message_id,
+                            record.body.as_str(),
+                            sns_signature_verifier,
+                        )
+                        .await,
+                    );
+                }
+                messages
+            },
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Request {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_value(Value::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+/// The S3 event records in the JSON of an SNS message or an SQS message body:
+/// an S3 event notification, an SNS notification wrapping one (as SQS
+/// receives it from a topic without raw message delivery) or an S3 event of
+/// EventBridge.
+fn s3_event_records_from_json(json: &str) -> Result<Vec<S3EventRecord>, Error> {
+    tracing::trace!("Message: {:#?}", json);
+    // Convert to serde Value first, not straight to S3EventRecords to get better
+    // errors
+    let value = serde_json::from_str::<Value>(json)?;
+    if value.get("Records").is_some() {
+        let s3_event_records = serde_json::from_value::<S3EventRecords>(value)?;
+        if s3_event_records.records.is_empty() {
+            return Err(Error::NoInputRecords);
+        }
+        return Ok(s3_event_records.records);
+    }
+    if value.get("Type").and_then(Value::as_str) == Some("Notification") {
+        let sns = serde_json::from_value::<SnsRecord>(value)?;
+        return s3_event_records_from_json(sns.message.as_str());
+    }
+    if value.get("detail-type").is_some() {
+        return serde_json::from_value::<EventBridgeEvent>(value)?
+            .s3_event_record()
+            .map(|record| vec![record]);
+    }
+    // S3 sends a test event when a notification configuration is created
+    if value.get("Event").and_then(Value::as_str) == Some("s3:TestEvent") {
+        return Ok(Vec::new());
+    }
+    Err(Error::ServiceError(format!(
+        "Unsupported message: {}",
+        json
+    )))
 }
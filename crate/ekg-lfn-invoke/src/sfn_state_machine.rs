@@ -7,24 +7,45 @@ pub struct StateMachine {
 impl StateMachine {
     pub fn new(aws_sfn_client: aws_sdk_sfn::Client) -> Self { Self { aws_sfn_client } }
 
+    /// Start an execution with the given name. Execution names are unique per
+    /// state machine so starting a second one with the same name (for
+    /// instance when the same S3 event is delivered twice) is not an error,
+    /// the execution that already exists does the work.
     pub async fn start_execution(
         &self,
         state_machine_arn: &str,
+        name: &str,
         input: serde_json::Value,
     ) -> Result<(), Error> {
-        let output = self
+        let result = self
             .aws_sfn_client
             .start_execution()
             .state_machine_arn(state_machine_arn)
+            .name(name)
             .input(serde_json::to_string(&input)?)
             .send()
-            .await
-            .map_err(|err| {
+            .await;
+        match result {
+            Ok(output) => {
+                tracing::info!("Step function started: {:}", output.execution_arn);
+                Ok(())
+            },
+            Err(err)
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_execution_already_exists()) =>
+            {
+                tracing::info!(
+                    "Step function execution {} already exists, not starting it again",
+                    name
+                );
+                Ok(())
+            },
+            Err(err) => {
                 let msg = format!("Error starting step function: {:}", err);
                 tracing::error!(msg);
-                Error::ServiceError(msg)
-            })?;
-        tracing::info!("Step function started: {:}", output.execution_arn);
-        Ok(())
+                Err(Error::ServiceError(msg))
+            },
+        }
     }
 }
@@ -1,28 +1,26 @@
 #![cfg(test)]
 
-use {ekg_error::Error, ekg_identifier::EkgIdentifierContexts};
+use {
+    crate::clients::Clients,
+    ekg_aws_util::{
+        graph_store::{GraphStore, OxigraphStore},
+        lambda::{load_requests_graph_iri, LambdaDetailStatus},
+        neptune::InMemoryBulkLoader,
+        object_store::{InMemoryObjectStore, ObjectStore},
+        placeholder::DEFAULT_STAGING_PREFIX,
+        routing::RoutingTable,
+        sns::{HttpsCertificateProvider, InMemoryCertificateProvider, SnsSignatureVerifier},
+    },
+    ekg_error::Error,
+    ekg_identifier::{EkgIdentifierContexts, NS_DATAOPS},
+    ekg_mock_neptune_loader::{MockLoader, MockLoaderHandle},
+    ekg_sparql::Prefixes,
+    indoc::indoc,
+    std::{ops::Deref, sync::Arc},
+};
 
-#[ignore]
-#[test_log::test(tokio::test)]
-async fn test_invoke_01() -> Result<(), Error> {
-    tracing::info!("test_invoke_01");
-    let aws_config = aws_config::load_from_env().await;
-    let aws_sfn_client = aws_sdk_sfn::Client::new(&aws_config);
-
-    EkgIdentifierContexts::default_test();
-    std::env::set_var("AWS_REGION", "antartica-01");
-    std::env::set_var(
-        "rdf_load_sfn_arn",
-        "arn:aws:states:antartica-01:123456789012:stateMachine:rdf_load",
-    );
-    // We have to set the AWS_NEPTUNE_LOAD_IAM_ROLE_ARN environment variable here
-    // because the invoke lambda function will pass it on via the LoadRequest to
-    // the load lambda function via the step-functions state machine.
-    std::env::set_var(
-        "AWS_NEPTUNE_LOAD_IAM_ROLE_ARN",
-        "arn:aws:iam::12345:role/ekgf-dt-dev-neptune-load",
-    );
-    let event = r#"{
+/// An SNS notification wrapping the S3 event of an uploaded Turtle file
+const EVENT: &str = r#"{
           "Records": [
             {
               "EventSource": "aws:sns",
@@ -44,13 +42,191 @@ async fn test_invoke_01() -> Result<(), Error> {
             }
           ]
         }"#;
-    let request_as_value: serde_json::Value = serde_json::from_str(event).unwrap();
+
+/// The tests point the SPARQL client to their own mock loader via environment
+/// variables, so they must not run at the same time
+static ENV_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// The given query or update with the prefixes that the lambda functions use
+fn sparql(query: &str) -> Result<String, Error> {
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(query),
+    )?;
+    Ok(statement.to_string())
+}
+
+/// The clients that the tests use, with a bucket that has no objects at all
+type TestClients = Clients<ekg_sparql::SPARQLClient, InMemoryObjectStore>;
+
+/// The signatures of SNS notifications are not verified unless
+/// `EKG_VERIFY_SNS_SIGNATURES` is `true`
+const NO_SNS_SIGNATURE_VERIFIER: Option<&SnsSignatureVerifier<HttpsCertificateProvider>> = None;
+
+/// The certificate URL of the SNS notification in [`EVENT`]
+const SIGNING_CERT_URL: &str =
+    "https://sns.antartica-01.amazonaws.com/SimpleNotificationService-01d088a6f77103d0fe307c0069e40ed6.pem";
+
+async fn clients(handle: &MockLoaderHandle) -> Result<TestClients, Error> {
+    clients_with_sparql_endpoint(handle.sparql_endpoint().as_str()).await
+}
+
+async fn clients_with_sparql_endpoint(sparql_endpoint: &str) -> Result<TestClients, Error> {
+    std::env::set_var("EKG_SPARQL_QUERY_ENDPOINT", sparql_endpoint);
+    std::env::set_var("EKG_SPARQL_UPDATE_ENDPOINT", sparql_endpoint);
+    Ok(clients_with(
+        ekg_sparql::SPARQLClient::from_env().await?,
+        InMemoryObjectStore::new(),
+    )
+    .await)
+}
+
+async fn clients_with<G: GraphStore, S: ObjectStore>(
+    sparql_client: G,
+    aws_s3_client: S,
+) -> Clients<G, S> {
+    let aws_config = aws_config::load_from_env().await;
+    Clients {
+        aws_sfn_client: aws_sdk_sfn::Client::new(&aws_config),
+        sparql_client,
+        aws_s3_client,
+        routing_table: Default::default(),
+        staging_prefix: DEFAULT_STAGING_PREFIX.to_string(),
+        sns_signature_verifier: None,
+    }
+}
+
+/// The given clients, verifying the signatures of SNS notifications with the
+/// certificates of the given provider
+fn with_sns_signature_verifier<G, S>(
+    clients: Clients<G, S>,
+    certificate_provider: InMemoryCertificateProvider,
+) -> Clients<G, S, InMemoryCertificateProvider> {
+    Clients {
+        aws_sfn_client:         clients.aws_sfn_client,
+        sparql_client:          clients.sparql_client,
+        aws_s3_client:          clients.aws_s3_client,
+        routing_table:          clients.routing_table,
+        staging_prefix:         clients.staging_prefix,
+        sns_signature_verifier: Some(SnsSignatureVerifier::new(certificate_provider)),
+    }
+}
+
+/// The S3 event notification embedded in the SNS message of [`EVENT`]
+fn s3_event_message() -> String {
+    let event: serde_json::Value = serde_json::from_str(EVENT).unwrap();
+    event["Records"][0]["Sns"]["Message"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// The EventBridge version of the S3 event in [`EVENT`]
+fn event_bridge_event(detail_type: &str) -> serde_json::Value {
+    serde_json::json!({
+        "version": "0",
+        "id": "17793124-05d4-b198-2fde-7ededc63b103",
+        "detail-type": detail_type,
+        "source": "aws.s3",
+        "account": "123456789012",
+        "time": "2023-09-18T10:03:15Z",
+        "region": "antartica-01",
+        "resources": ["arn:aws:s3:::ekgf-dt-dev-metadata"],
+        "detail": {
+            "version": "0",
+            "bucket": { "name": "ekgf-dt-dev-metadata" },
+            "object": {
+                "key": "static-dataset/personas/ekgf-group-internal-auditor.ttl",
+                "size": 1206,
+                "etag": "455c556f7d1b7f8587ecabe2dd8184af",
+                "version-id": "LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2",
+                "sequencer": "0065082063F0F5766D"
+            },
+            "request-id": "JJ807NMA5B2VMJ0D",
+            "requester": "123456789012",
+            "source-ip-address": "193.237.90.75",
+            "reason": "PutObject"
+        }
+    })
+}
+
+/// The SNS notification of [`EVENT`] the way SQS receives it from a topic
+/// without raw message delivery
+fn sns_notification_message() -> String {
+    let event: serde_json::Value = serde_json::from_str(EVENT).unwrap();
+    let mut sns_notification = event["Records"][0]["Sns"].clone();
+    let sns_notification = sns_notification.as_object_mut().unwrap();
+    let signing_cert_url = sns_notification.remove("SigningCertUrl").unwrap();
+    sns_notification.insert("SigningCertURL".to_string(), signing_cert_url);
+    let unsubscribe_url = sns_notification.remove("UnsubscribeUrl").unwrap();
+    sns_notification.insert("UnsubscribeURL".to_string(), unsubscribe_url);
+    serde_json::to_string(&sns_notification).unwrap()
+}
+
+/// An SQS message with the given body
+fn sqs_record(message_id: &str, body: String) -> serde_json::Value {
+    serde_json::json!({
+        "messageId": message_id,
+        "receiptHandle": "AQEBwJnKyrHigUMZj6rYigCgxlaS3SLy0a",
+        "body": body,
+        "attributes": {
+            "ApproximateReceiveCount": "1",
+            "SentTimestamp": "1695031396801"
+        },
+        "messageAttributes": {},
+        "md5OfBody": "e4e68fb7bd0e697a0ae8f1bb342846b3",
+        "eventSource": "aws:sqs",
+        "eventSourceARN": "arn:aws:sqs:antartica-01:123456789012:rdf_load",
+        "awsRegion": "antartica-01"
+    })
+}
+
+/// The SNS record of [`EVENT`] with the given replacements in its embedded S3
+/// event message
+fn sns_record(message_id: &str, replacements: &[(&str, &str)]) -> serde_json::Value {
+    let mut event: serde_json::Value = serde_json::from_str(EVENT).unwrap();
+    let mut record = event["Records"][0].take();
+    let mut message = record["Sns"]["Message"].as_str().unwrap().to_string();
+    for (from, to) in replacements {
+        message = message.replace(from, to);
+    }
+    record["Sns"]["Message"] = message.into();
+    record["Sns"]["MessageId"] = message_id.into();
+    record
+}
+
+#[ignore]
+#[test_log::test(tokio::test)]
+async fn test_invoke_01() -> Result<(), Error> {
+    tracing::info!("test_invoke_01");
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    std::env::set_var(
+        "rdf_load_sfn_arn",
+        "arn:aws:states:antartica-01:123456789012:stateMachine:rdf_load",
+    );
+    // We have to set the AWS_NEPTUNE_LOAD_IAM_ROLE_ARN environment variable here
+    // because the invoke lambda function will pass it on via the LoadRequest to
+    // the load lambda function via the step-functions state machine.
+    std::env::set_var(
+        "AWS_NEPTUNE_LOAD_IAM_ROLE_ARN",
+        "arn:aws:iam::12345:role/ekgf-dt-dev-neptune-load",
+    );
+    let clients = clients(&handle).await?;
+    let request_as_value: serde_json::Value = serde_json::from_str(EVENT).unwrap();
     println!("result: {:#?}", request_as_value);
-    let lambda_output = crate::handle_lambda_payload(request_as_value, aws_sfn_client).await?;
+    let lambda_output = crate::handle_lambda_payload(request_as_value, "test", clients).await?;
     println!("result: {:#?}", lambda_output);
     if let serde_json::Value::Object(map) = lambda_output {
-        assert_eq!(map.len(), 1);
+        assert_eq!(map.len(), 2);
         assert!(map.contains_key("statusCode"));
+        assert_eq!(map["records"][0]["outcome"], "started");
         if let serde_json::Value::Number(result) = map.get("statusCode").unwrap() {
             assert_eq!(result.as_u64(), Some(200u64));
         } else {
@@ -61,3 +237,733 @@ async fn test_invoke_01() -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[test_log::test(tokio::test)]
+async fn test_invoke_object_removed() -> Result<(), Error> {
+    tracing::info!("test_invoke_object_removed");
+    let _env = ENV_LOCK.lock().await;
+
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    let clients = clients(&handle).await?;
+
+    let event = EVENT.replace("ObjectCreated:Put", "ObjectRemoved:Delete");
+    let request = serde_json::from_str::<crate::Request>(event.as_str())?;
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    assert_eq!(lambda_output["statusCode"], 200);
+    assert_eq!(lambda_output["records"][0]["outcome"], "retired");
+
+    // No load has been started but the named graph has been dropped
+    assert!(mock_loader.jobs().is_empty());
+    let sparql_updates = mock_loader.sparql_updates();
+    assert_eq!(sparql_updates.len(), 1);
+    assert!(sparql_updates[0].contains(
+        "DROP SILENT GRAPH \
+         <s3://ekgf-dt-dev-metadata/static-dataset/personas/ekgf-group-internal-auditor.ttl>"
+    ));
+    assert!(sparql_updates[0].contains("dataops:RetiredDataset"));
+    // The triples that only this object contributed leave the merged graphs
+    assert!(sparql_updates[0].contains(
+        "?target <http://www.w3.org/ns/prov#wasDerivedFrom> \
+         <s3://ekgf-dt-dev-metadata/static-dataset/personas/ekgf-group-internal-auditor.ttl>"
+    ));
+    assert!(sparql_updates[0].contains("DELETE WHERE"));
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invoke_object_removed_but_not_gone() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    let mut clients = clients(&handle).await?;
+    clients.aws_s3_client = InMemoryObjectStore::new().with_object(
+        "ekgf-dt-dev-metadata",
+        "static-dataset/personas/ekgf-group-internal-auditor.ttl",
+        "",
+    );
+
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            // A noncurrent version has been deleted permanently
+            sns_record("message-1", &[("ObjectCreated:Put", "ObjectRemoved:Delete")]),
+            // A delete marker makes the object disappear, whatever HeadObject says
+            sns_record("message-2", &[(
+                "ObjectCreated:Put",
+                "ObjectRemoved:DeleteMarkerCreated",
+            )]),
+            // A file that is not an RDF file has never been loaded
+            sns_record("message-3", &[
+                ("ObjectCreated:Put", "ObjectRemoved:DeleteMarkerCreated"),
+                ("ekgf-group-internal-auditor.ttl", "ekgf-group-internal-auditor.csv"),
+            ]),
+        ]
+    }))?;
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    let records = lambda_output["records"].as_array().unwrap();
+    assert_eq!(records[0]["outcome"], "skipped");
+    assert!(records[0]["reason"]
+        .as_str()
+        .unwrap()
+        .contains("the object still exists"));
+    assert_eq!(records[1]["outcome"], "retired");
+    assert_eq!(records[2]["outcome"], "skipped");
+
+    let sparql_updates = mock_loader.sparql_updates();
+    assert_eq!(sparql_updates.len(), 1);
+    assert!(!sparql_updates[0].contains(".csv"));
+
+    Ok(())
+}
+
+/// The graph that is dropped is the one that the load request recorded, not
+/// the one that the current graph naming strategy would name
+#[test_log::test(tokio::test)]
+async fn test_invoke_object_removed_recorded_graph() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    let graph_load_requests = load_requests_graph_iri(&EkgIdentifierContexts::from_env()?, "test");
+    let dataset =
+        "s3://ekgf-dt-dev-metadata/static-dataset/personas/ekgf-group-internal-auditor.ttl";
+    let graph = "https://kg.example.com/graph/recorded";
+    let target_graph = "https://kg.example.com/graph/merged";
+
+    let store = OxigraphStore::new()?;
+    store.update(
+        sparql(
+            format!(
+                r#"
+                INSERT DATA {{
+                    GRAPH <{graph_load_requests}> {{
+                        <urn:uuid:1> dataops:source <{dataset}> ;
+                            dataops:graph <{graph}> .
+                        <{target_graph}> <http://www.w3.org/ns/prov#wasDerivedFrom> <{graph}> .
+                    }}
+                    GRAPH <{graph}> {{ <urn:s> <urn:p> <urn:o> }}
+                    GRAPH <{target_graph}> {{ <urn:s> <urn:p> <urn:o> }}
+                }}
+                "#
+            )
+            .as_str(),
+        )?
+        .as_str(),
+    )?;
+
+    let clients = clients_with(store.clone(), InMemoryObjectStore::new()).await;
+    let event = EVENT.replace("ObjectCreated:Put", "ObjectRemoved:Delete");
+    let request = serde_json::from_str::<crate::Request>(event.as_str())?;
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    assert_eq!(lambda_output["records"][0]["outcome"], "retired");
+
+    // The recorded graph is gone, and so are its triples in the merged graph
+    // and the lineage that says the merged graph was derived from it
+    assert!(!store.ask(format!("ASK {{ GRAPH <{graph}> {{ ?s ?p ?o }} }}").as_str())?);
+    assert!(!store.ask(format!("ASK {{ GRAPH <{target_graph}> {{ ?s ?p ?o }} }}").as_str())?);
+    assert!(!store.ask(
+        format!(
+            "ASK {{ GRAPH ?g {{ ?target <http://www.w3.org/ns/prov#wasDerivedFrom> <{graph}> }} }}"
+        )
+        .as_str()
+    )?);
+    assert!(store.ask(
+        sparql(
+            format!(
+                "ASK {{ GRAPH <{graph_load_requests}> {{ <{dataset}> a dataops:RetiredDataset }} \
+                 }}"
+            )
+            .as_str()
+        )?
+        .as_str()
+    )?);
+
+    Ok(())
+}
+
+/// A file that is deleted and then uploaded again is a dataset that is loaded,
+/// not a retired one
+#[test_log::test(tokio::test)]
+async fn test_invoke_object_removed_and_uploaded_again() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    let graph_load_requests = load_requests_graph_iri(&EkgIdentifierContexts::from_env()?, "test");
+    let dataset =
+        "s3://ekgf-dt-dev-metadata/static-dataset/personas/ekgf-group-internal-auditor.ttl";
+
+    let store = OxigraphStore::new()?;
+    let load_clients = ekg_lfn_load::Clients {
+        aws_neptunedata_client: InMemoryBulkLoader::new(),
+        sparql_client:          store.clone(),
+        aws_s3_client:          InMemoryObjectStore::new(),
+    };
+    let mut payload: serde_json::Value =
+        serde_json::from_str(include_str!("../../ekg-lfn-load/event.json"))?;
+    payload["load_request"]["source"] = dataset.into();
+    payload["load_request"]["parserConfiguration"]["namedGraphUri"] = dataset.into();
+    payload["load_request"]["sourceVersion"] = "LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2".into();
+    let is_retired = || -> Result<bool, Error> {
+        store.ask(
+            sparql(
+                format!(
+                    "ASK {{ GRAPH <{graph_load_requests}> {{ <{dataset}> a dataops:RetiredDataset \
+                     }} }}"
+                )
+                .as_str(),
+            )?
+            .as_str(),
+        )
+    };
+
+    ekg_lfn_load::handle_lambda_payload(payload.clone(), "test", load_clients.clone())
+        .await
+        .expect("load request failed");
+    assert!(!is_retired()?);
+
+    // The object is deleted ...
+    let clients = clients_with(store.clone(), InMemoryObjectStore::new()).await;
+    let event = EVENT.replace("ObjectCreated:Put", "ObjectRemoved:Delete");
+    let request = serde_json::from_str::<crate::Request>(event.as_str())?;
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    assert_eq!(lambda_output["records"][0]["outcome"], "retired");
+    assert!(is_retired()?);
+
+    // ... and the same version is uploaded again, which is loaded again
+    let lambda_output =
+        ekg_lfn_load::handle_lambda_payload(payload.clone(), "test", load_clients.clone())
+            .await
+            .expect("load request failed");
+    assert_eq!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::LoaderJobInQueue
+    );
+    assert_eq!(
+        load_clients.aws_neptunedata_client.jobs().len(),
+        2
+    );
+    assert!(!is_retired()?);
+    assert!(store.ask(
+        sparql(
+            format!(
+                "ASK {{ GRAPH <{graph_load_requests}> {{ <{dataset}> a dataops:Dataset ; \
+                 dataops:retiredAt ?retiredAt }} }}"
+            )
+            .as_str()
+        )?
+        .as_str()
+    )?);
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invoke_per_record_outcomes() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    let clients = clients(&handle).await?;
+
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            sns_record("message-1", &[("ObjectCreated:Put", "ObjectRemoved:Delete")]),
+            sns_record(
+                "message-2",
+                &[("ekgf-group-internal-auditor.ttl", "ekgf-group-internal-auditor.xlsx")]
+            ),
+            sns_record("message-3", &[("{\"Records\"", "not JSON")]),
+        ]
+    }))?;
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    assert_eq!(lambda_output["statusCode"], 200);
+    let records = lambda_output["records"].as_array().unwrap();
+    assert_eq!(records.len(), 3);
+
+    assert_eq!(records[0]["messageId"], "message-1");
+    assert_eq!(records[0]["outcome"], "retired");
+    assert_eq!(
+        records[0]["s3Uri"],
+        "s3://ekgf-dt-dev-metadata/static-dataset/personas/ekgf-group-internal-auditor.ttl"
+    );
+
+    assert_eq!(records[1]["messageId"], "message-2");
+    assert_eq!(records[1]["outcome"], "skipped");
+    assert!(records[1]["reason"]
+        .as_str()
+        .unwrap()
+        .contains("Unsupported RDF file format"));
+
+    // A message that can't be parsed fails, but retrying it wouldn't help so
+    // it doesn't fail the invocation
+    assert_eq!(records[2]["messageId"], "message-3");
+    assert_eq!(records[2]["outcome"], "failed");
+    assert_eq!(records[2]["retryable"], false);
+    assert!(records[2].get("s3Uri").is_none());
+
+    assert!(mock_loader.jobs().is_empty());
+    assert_eq!(mock_loader.sparql_updates().len(), 1);
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invoke_routing() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    let mut clients = clients(&handle).await?;
+    clients.routing_table = Arc::new(RoutingTable::parse(indoc! {r#"
+        [[rule]]
+        glob = "**/*-internal-auditor.ttl"
+        skip = true
+
+        [[rule]]
+        prefix = "static-dataset/"
+        graph = "{graph_base}{prefix}/{stem}"
+    "#})?);
+
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            sns_record("message-1", &[]),
+            sns_record("message-2", &[
+                ("ObjectCreated:Put", "ObjectRemoved:Delete"),
+                ("ekgf-group-internal-auditor.ttl", "ekgf-group-external-auditor.ttl"),
+            ]),
+            sns_record("message-3", &[(
+                "\"key\":\"static-dataset/",
+                "\"key\":\"ekg-staging/LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2/static-dataset/",
+            )]),
+        ]
+    }))?;
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    let records = lambda_output["records"].as_array().unwrap();
+    assert_eq!(records[0]["outcome"], "skipped");
+    assert!(records[0]["reason"]
+        .as_str()
+        .unwrap()
+        .contains("excluded by the routing table"));
+    assert_eq!(records[1]["outcome"], "retired");
+    assert_eq!(records[2]["outcome"], "skipped");
+    assert!(records[2]["reason"]
+        .as_str()
+        .unwrap()
+        .contains("a staged copy of another object"));
+
+    // The named graph of the removed object follows the graph template of the
+    // rule, its dataset is still the S3 object itself
+    let graph_base = EkgIdentifierContexts::from_env()?
+        .internal
+        .ekg_graph_base
+        .as_base_iri();
+    let sparql_updates = mock_loader.sparql_updates();
+    assert_eq!(sparql_updates.len(), 1);
+    assert!(sparql_updates[0].contains(
+        format!(
+            "DROP SILENT GRAPH <{}static-dataset/personas/ekgf-group-external-auditor>",
+            graph_base
+        )
+        .as_str()
+    ));
+    assert!(sparql_updates[0].contains(
+        "<s3://ekgf-dt-dev-metadata/static-dataset/personas/ekgf-group-external-auditor.ttl> a \
+         dataops:RetiredDataset"
+    ));
+
+    Ok(())
+}
+
+/// The content type of an object decides its RDF format when its key does
+/// not, an object that has been removed has no content type anymore
+#[test_log::test(tokio::test)]
+async fn test_invoke_content_type() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    let mut clients = clients(&handle).await?;
+    clients.aws_s3_client = InMemoryObjectStore::new()
+        .with_object(
+            "ekgf-dt-dev-metadata",
+            "static-dataset/personas/ekgf-group-internal-auditor",
+            "",
+        )
+        .with_content_type(
+            "ekgf-dt-dev-metadata",
+            "static-dataset/personas/ekgf-group-internal-auditor",
+            "text/turtle; charset=utf-8",
+        )
+        .with_object(
+            "ekgf-dt-dev-metadata",
+            "static-dataset/personas/ekgf-group-external-auditor",
+            "",
+        );
+    // Loading a file of this pipeline fails before anything is started
+    clients.routing_table = Arc::new(RoutingTable::parse(indoc! {r#"
+        [[rule]]
+        prefix = "static-dataset/"
+        pipeline = "personas"
+    "#})?);
+
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            sns_record("message-1", &[(
+                "ekgf-group-internal-auditor.ttl",
+                "ekgf-group-internal-auditor",
+            )]),
+            sns_record("message-2", &[(
+                "ekgf-group-internal-auditor.ttl",
+                "ekgf-group-external-auditor",
+            )]),
+            sns_record("message-3", &[
+                ("ObjectCreated:Put", "ObjectRemoved:DeleteMarkerCreated"),
+                ("ekgf-group-internal-auditor.ttl", "ekgf-group-internal-auditor"),
+            ]),
+        ]
+    }))?;
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    let records = lambda_output["records"].as_array().unwrap();
+    assert_eq!(records[0]["outcome"], "failed");
+    assert!(records[0]["reason"]
+        .as_str()
+        .unwrap()
+        .contains("no state machine for pipeline personas"));
+    assert_eq!(records[1]["outcome"], "skipped");
+    assert!(records[1]["reason"]
+        .as_str()
+        .unwrap()
+        .contains("Unsupported RDF file format"));
+    // No load request recorded a graph for it, so it was never loaded
+    assert_eq!(records[2]["outcome"], "skipped");
+    assert!(mock_loader.sparql_updates().is_empty());
+
+    Ok(())
+}
+
+/// Only a failure to talk to S3, Step Functions or the SPARQL endpoint is
+/// worth retrying, an invalid record or configuration fails again
+#[test_log::test(tokio::test)]
+async fn test_invoke_failure_retryability() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    std::env::set_var(
+        "rdf_load_sfn_arn",
+        "arn:aws:states:antartica-01:123456789012:stateMachine:rdf_load",
+    );
+    std::env::remove_var("AWS_NEPTUNE_LOAD_IAM_ROLE_ARN");
+    let mut clients =
+        clients_with_sparql_endpoint(format!("{}-unavailable", handle.sparql_endpoint()).as_str())
+            .await?;
+    clients.routing_table = Arc::new(RoutingTable::parse(indoc! {r#"
+        [[rule]]
+        prefix = "static-dataset/ontology/"
+        pipeline = "ontology"
+    "#})?);
+
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            // A pipeline that the routing table has no state machine for
+            sqs_record(
+                "sqs-1",
+                s3_event_message().replace("static-dataset/personas/", "static-dataset/ontology/")
+            ),
+            // No IAM role for the load request
+            sqs_record("sqs-2", s3_event_message()),
+            // The SPARQL endpoint is unavailable
+            sqs_record(
+                "sqs-3",
+                s3_event_message().replace("ObjectCreated:Put", "ObjectRemoved:Delete")
+            ),
+        ]
+    }))?;
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    let records = lambda_output["records"].as_array().unwrap();
+    assert_eq!(records.len(), 3);
+    assert!(records.iter().all(|record| record["outcome"] == "failed"));
+    assert!(records[0]["reason"]
+        .as_str()
+        .unwrap()
+        .contains("no state machine for pipeline ontology"));
+    assert_eq!(records[0]["retryable"], false);
+    assert!(records[1]["reason"]
+        .as_str()
+        .unwrap()
+        .contains("AWS_NEPTUNE_LOAD_IAM_ROLE_ARN"));
+    assert_eq!(records[1]["retryable"], false);
+    assert_eq!(records[2]["retryable"], true);
+    assert_eq!(
+        lambda_output["batchItemFailures"],
+        serde_json::json!([{ "itemIdentifier": "sqs-3" }])
+    );
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invoke_retryable_failure() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    // The mock loader has nothing at this endpoint, so dropping the named graph
+    // fails
+    let clients =
+        clients_with_sparql_endpoint(format!("{}-unavailable", handle.sparql_endpoint()).as_str())
+            .await?;
+
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            sns_record(
+                "message-1",
+                &[("ekgf-group-internal-auditor.ttl", "ekgf-group-internal-auditor.xlsx")]
+            ),
+            sns_record("message-2", &[("ObjectCreated:Put", "ObjectRemoved:Delete")]),
+        ]
+    }))?;
+    let error = crate::handle_lambda_request(request, "test", clients)
+        .await
+        .expect_err("a failed SPARQL update should fail the invocation");
+    let error = error.to_string();
+    assert!(
+        error.contains("1 of 2 records failed"),
+        "{}",
+        error
+    );
+    assert!(
+        error.contains(
+            "s3://ekgf-dt-dev-metadata/static-dataset/personas/ekgf-group-internal-auditor.ttl"
+        ),
+        "{}",
+        error
+    );
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_request_event_sources() -> Result<(), Error> {
+    let key = "s3://ekgf-dt-dev-metadata/static-dataset/personas/ekgf-group-internal-auditor.ttl";
+
+    // S3 event notifications sent to the lambda function directly
+    let request = serde_json::from_str::<crate::Request>(s3_event_message().as_str())?;
+    assert!(matches!(request, crate::Request::S3(_)));
+    let messages = request.into_messages(NO_SNS_SIGNATURE_VERIFIER).await;
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].id, "JJ807NMA5B2VMJ0D");
+    let s3_event_records = messages[0].s3_event_records.as_ref().unwrap();
+    assert_eq!(s3_event_records[0].s3_uri(), key);
+
+    // An EventBridge event
+    let request = serde_json::from_value::<crate::Request>(event_bridge_event("Object Deleted"))?;
+    assert!(matches!(request, crate::Request::EventBridge(_)));
+    let messages = request.into_messages(NO_SNS_SIGNATURE_VERIFIER).await;
+    assert_eq!(messages.len(), 1);
+    assert_eq!(
+        messages[0].id,
+        "17793124-05d4-b198-2fde-7ededc63b103"
+    );
+    let s3_event_records = messages[0].s3_event_records.as_ref().unwrap();
+    assert_eq!(s3_event_records[0].s3_uri(), key);
+    assert!(s3_event_records[0].event_name.is_object_removed());
+
+    // SQS messages wrapping an SNS notification, an S3 event notification, an
+    // EventBridge event, the test event of S3 and something else
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            sqs_record("sqs-1", sns_notification_message()),
+            sqs_record("sqs-2", s3_event_message()),
+            sqs_record("sqs-3", event_bridge_event("Object Created").to_string()),
+            sqs_record(
+                "sqs-4",
+                r#"{"Service":"Amazon S3","Event":"s3:TestEvent","Bucket":"ekgf-dt-dev-metadata"}"#
+                    .to_string()
+            ),
+            sqs_record("sqs-5", "not JSON".to_string()),
+        ]
+    }))?;
+    assert!(request.is_sqs());
+    let messages = request.into_messages(NO_SNS_SIGNATURE_VERIFIER).await;
+    assert_eq!(
+        messages
+            .iter()
+            .map(|message| message.id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["sqs-1", "sqs-2", "sqs-3", "sqs-4", "sqs-5"]
+    );
+    for message in &messages[0..3] {
+        let s3_event_records = message.s3_event_records.as_ref().unwrap();
+        assert_eq!(s3_event_records.len(), 1);
+        assert_eq!(s3_event_records[0].s3_uri(), key);
+        assert!(s3_event_records[0].event_name.is_object_created());
+        // The same S3 event gets the same version whatever the route it took
+        assert_eq!(
+            s3_event_records[0].s3.object.version(),
+            "LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2"
+        );
+    }
+    assert!(messages[3].s3_event_records.as_ref().unwrap().is_empty());
+    assert!(messages[4].s3_event_records.is_err());
+
+    // Anything else is rejected
+    assert!(serde_json::from_str::<crate::Request>(r#"{"hello": "world"}"#).is_err());
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invoke_sqs_batch_item_failures() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    // The mock loader has nothing at this endpoint, so dropping the named graph
+    // fails
+    let clients =
+        clients_with_sparql_endpoint(format!("{}-unavailable", handle.sparql_endpoint()).as_str())
+            .await?;
+
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            sqs_record(
+                "sqs-1",
+                s3_event_message().replace(
+                    "ekgf-group-internal-auditor.ttl",
+                    "ekgf-group-internal-auditor.xlsx"
+                )
+            ),
+            sqs_record(
+                "sqs-2",
+                s3_event_message().replace("ObjectCreated:Put", "ObjectRemoved:Delete")
+            ),
+            sqs_record("sqs-3", "not JSON".to_string()),
+        ]
+    }))?;
+    // An SQS batch doesn't fail, it tells which messages to receive again
+    let lambda_output = crate::handle_lambda_request(request, "test", clients).await?;
+    assert_eq!(
+        lambda_output["batchItemFailures"],
+        serde_json::json!([{ "itemIdentifier": "sqs-2" }])
+    );
+    let records = lambda_output["records"].as_array().unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0]["outcome"], "skipped");
+    assert_eq!(records[1]["outcome"], "failed");
+    assert_eq!(records[1]["retryable"], true);
+    assert_eq!(records[2]["outcome"], "failed");
+    assert_eq!(records[2]["retryable"], false);
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invoke_sns_signature_rejected() -> Result<(), Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("AWS_REGION", "antartica-01");
+    // The certificate of the test data is not the one that signed EVENT
+    let certificate_provider = InMemoryCertificateProvider::new().with_certificate(
+        SIGNING_CERT_URL,
+        include_str!("../../ekg-aws-util/test-data/sns/signing-cert.pem"),
+    );
+    let verifying_clients =
+        with_sns_signature_verifier(clients(&handle).await?, certificate_provider);
+
+    // An SNS notification that is not signed by SNS is not processed, and
+    // delivering it again won't help
+    let request = serde_json::from_str::<crate::Request>(EVENT)?;
+    let lambda_output =
+        crate::handle_lambda_request(request, "test", verifying_clients.clone()).await?;
+    let records = lambda_output["records"].as_array().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["outcome"], "failed");
+    assert_eq!(records[0]["retryable"], false);
+    assert!(mock_loader.sparql_queries().is_empty());
+
+    // The same goes for an SNS notification in an SQS message, an S3 event
+    // notification without any signature is processed as usual
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [
+            sqs_record("sqs-1", sns_notification_message()),
+            sqs_record(
+                "sqs-2",
+                s3_event_message().replace(
+                    "ekgf-group-internal-auditor.ttl",
+                    "ekgf-group-internal-auditor.xlsx"
+                )
+            ),
+        ]
+    }))?;
+    let lambda_output = crate::handle_lambda_request(request, "test", verifying_clients).await?;
+    assert_eq!(
+        lambda_output["batchItemFailures"],
+        serde_json::json!([])
+    );
+    let records = lambda_output["records"].as_array().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["outcome"], "failed");
+    assert_eq!(records[0]["retryable"], false);
+    assert_eq!(records[1]["outcome"], "skipped");
+
+    // When the certificate cannot be fetched, the message is received again
+    let verifying_clients = with_sns_signature_verifier(
+        clients(&handle).await?,
+        InMemoryCertificateProvider::new(),
+    );
+    let request = serde_json::from_value::<crate::Request>(serde_json::json!({
+        "Records": [sqs_record("sqs-1", sns_notification_message())]
+    }))?;
+    let lambda_output = crate::handle_lambda_request(request, "test", verifying_clients).await?;
+    assert_eq!(
+        lambda_output["batchItemFailures"],
+        serde_json::json!([{ "itemIdentifier": "sqs-1" }])
+    );
+
+    // Without a verifier, the same SNS notification is processed as usual
+    let request = serde_json::from_str::<crate::Request>(EVENT)?;
+    let messages = request.into_messages(NO_SNS_SIGNATURE_VERIFIER).await;
+    assert!(messages[0].s3_event_records.is_ok());
+
+    Ok(())
+}
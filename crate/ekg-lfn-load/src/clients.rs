@@ -1,5 +1,12 @@
+/// The clients that the lambda function uses, the real ones by default, see
+/// the `fake` feature of `ekg-aws-util` for in-memory ones.
 #[derive(Clone)]
-pub struct Clients {
-    pub aws_neptunedata_client: aws_sdk_neptunedata::Client,
-    pub sparql_client:          ekg_sparql::SPARQLClient,
+pub struct Clients<
+    L = aws_sdk_neptunedata::Client,
+    G = ekg_sparql::SPARQLClient,
+    S = aws_sdk_s3::Client,
+> {
+    pub aws_neptunedata_client: L,
+    pub sparql_client:          G,
+    pub aws_s3_client:          S,
 }
@@ -1,3 +1,413 @@
-pub use request::Request;
+//! The handler of the load lambda function, in a library so that it can be
+//! invoked in-process as well (see the `ekg-sfn-local` crate).
+use {
+    chrono::{Duration, SecondsFormat, Utc},
+    ekg_aws_util::{
+        graph_store::GraphStore,
+        lambda::{
+            load_request_iri,
+            LambdaDetailStatus::{AlreadyLoaded, LoaderJobInQueue},
+            LambdaResponse,
+            RetryPolicy,
+        },
+        neptune::{is_rdf_format, BulkLoader, LoadRequest},
+        object_store::ObjectStore,
+        placeholder::{
+            stage,
+            staged_s3_uri,
+            staging_prefix_from_env,
+            PlaceholderRewriter,
+            StagingError,
+        },
+        sparql::{Iri, Literal},
+    },
+    ekg_identifier::{EkgIdentifierContexts, NS_DATAOPS, NS_RDFS},
+    ekg_sparql::Prefixes,
+    indoc::formatdoc,
+    serde_json::Value,
+    std::ops::Deref,
+};
+pub use {
+    clients::Clients,
+    lambda_runtime::{Error as LambdaError, LambdaEvent},
+    request::Request,
+};
 
 mod request;
+
+mod clients;
+#[cfg(test)]
+mod tests;
+
+// noinspection DuplicatedCode
+/// The actual handler of the Lambda request.
+pub async fn handle_lambda_event<L: BulkLoader, G: GraphStore, S: ObjectStore>(
+    event: LambdaEvent<Value>,
+    pipeline_id: &'static str,
+    clients: Clients<L, G, S>,
+) -> Result<LambdaResponse, LambdaError> {
+    tracing::trace!("Event {:#?}\n\n", event.clone());
+
+    let (payload, _ctx) = event.into_parts();
+
+    handle_lambda_payload(payload, pipeline_id, clients).await
+}
+
+pub async fn handle_lambda_payload<L: BulkLoader, G: GraphStore, S: ObjectStore>(
+    payload: Value,
+    pipeline_id: &'static str,
+    clients: Clients<L, G, S>,
+) -> Result<LambdaResponse, LambdaError> {
+    tracing::trace!(
+        "Payload {}",
+        serde_json::to_string_pretty(&payload)?
+    );
+
+    let request = serde_json::from_value::<Request>(payload).map_err(|e| {
+        tracing::error!("Error parsing request: {}", e);
+        e
+    })?;
+
+    let retry_policy = RetryPolicy::from_env()?;
+
+    match handle_lambda_request(&request, pipeline_id, clients).await {
+        Ok(response) => {
+            let response = response.with_retry_policy(&retry_policy, request.load_output.as_ref());
+            tracing::info!("Response: {:}", serde_json::to_string(&response)?);
+            Ok(response)
+        },
+        Err(error) => {
+            tracing::error!("Error handling request: {:?}", error);
+            Err(error.into())
+        },
+    }
+}
+
+pub async fn handle_lambda_request<L: BulkLoader, G: GraphStore, S: ObjectStore>(
+    request: &crate::Request,
+    pipeline_id: &'static str,
+    clients: Clients<L, G, S>,
+) -> Result<LambdaResponse, LambdaError> {
+    let identifier_contexts = EkgIdentifierContexts::from_env()?;
+    let load_request = &request.load_request;
+    if request.pipeline_id != pipeline_id {
+        return Ok(LambdaResponse::pipeline_id_not_matching(
+            request.pipeline_id.as_str(),
+            pipeline_id,
+        ));
+    }
+
+    if !is_rdf_format(&load_request.format) {
+        return Ok(LambdaResponse::unsupported_rdf_format(
+            load_request.source.as_str(),
+        ));
+    }
+
+    // S3 and SNS deliver events at least once, so skip a version of the source
+    // that has been loaded (or is being loaded) already
+    if let Some(source_version) = load_request
+        .source_version
+        .as_ref()
+        .filter(|source_version| !source_version.is_empty())
+    {
+        if is_already_loaded(
+            load_request,
+            source_version,
+            pipeline_id,
+            &identifier_contexts,
+            clients.clone(),
+        )
+        .await?
+        {
+            return Ok(LambdaResponse::ok(
+                AlreadyLoaded,
+                Some(
+                    format!(
+                        "{} (version {})",
+                        load_request.source, source_version
+                    )
+                    .as_str(),
+                ),
+            ));
+        }
+    }
+
+    // Build the terms of the registration before anything else happens, an
+    // invalid IRI should not leave a loader job behind that is not registered
+    let registration = Registration::new(load_request, pipeline_id, &identifier_contexts)?;
+
+    // Rewrite the placeholder IRIs in the source, the bulk loader then loads
+    // the staged copy instead of the source itself
+    let rewriter = PlaceholderRewriter::from_env();
+    let load_request = &match stage_source(load_request, &rewriter, clients.clone()).await {
+        Ok(load_request) => load_request,
+        Err(error) => {
+            return Ok(LambdaResponse::staging_failed(
+                load_request.source.as_str(),
+                &error,
+            ))
+        },
+    };
+
+    // First, initiate the load request using the NeptuneData API which gives us
+    // a load request ID
+    let result = handle_load_request(load_request, pipeline_id, clients.clone()).await?;
+    if let Some(result_identifier) = &result.result_identifier {
+        tracing::info!("Load request ID: {:?}", result_identifier);
+        // First, register the load request in the database itself using SPARQL
+        handle_load_request_registration(
+            &registration,
+            result_identifier.as_str(),
+            &identifier_contexts,
+            clients.clone(),
+        )
+        .await?;
+    }
+    Ok(result)
+}
+
+/// The IRIs and literals of the registration of a load request, see
+/// [`handle_load_request_registration`]. Building them validates them, which
+/// is done before the loader job is started.
+struct Registration {
+    graph_load_requests: Iri,
+    pipeline_iri:        Iri,
+    pipeline_label:      Literal,
+    s3_iri:              Iri,
+    s3_label:            Literal,
+    graph_iri:           Iri,
+    load_request_label:  Literal,
+    source_version:      Option<Literal>,
+}
+
+impl Registration {
+    fn new(
+        load_request: &LoadRequest,
+        pipeline_id: &str,
+        ekg_identifier_contexts: &EkgIdentifierContexts,
+    ) -> Result<Self, ekg_error::Error> {
+        // The IRI of the load request itself is this one followed by the UUID
+        // that the loader job gets
+        Iri::new(load_request_iri(ekg_identifier_contexts, ""))?;
+        Ok(Self {
+            graph_load_requests: Iri::new(ekg_aws_util::lambda::load_requests_graph_iri(
+                ekg_identifier_contexts,
+                pipeline_id,
+            ))?,
+            pipeline_iri:        Iri::new(format!(
+                "{}dataops-pipeline-{}",
+                ekg_identifier_contexts.internal.ekg_id_base.as_base_iri(),
+                pipeline_id
+            ))?,
+            pipeline_label:      Literal::string(format!("Pipeline {}", pipeline_id)),
+            s3_iri:              Iri::new(load_request.source_iri())?,
+            s3_label:            Literal::string(format!("S3 file {}", load_request.source)),
+            graph_iri:           Iri::new(load_request.target_graph_uri())?,
+            load_request_label:  Literal::string(format!(
+                "Queued load request for {}",
+                load_request.source
+            )),
+            source_version:      load_request
+                .source_version
+                .as_deref()
+                .filter(|source_version| !source_version.is_empty())
+                .map(Literal::string),
+        })
+    }
+}
+
+/// Handle the registration (using SPARQL) of the load request in the database
+/// itself.
+async fn handle_load_request_registration<L: BulkLoader, G: GraphStore, S: ObjectStore>(
+    registration: &Registration,
+    load_request_id: &str,
+    ekg_identifier_contexts: &EkgIdentifierContexts,
+    clients: Clients<L, G, S>,
+) -> Result<LambdaResponse, LambdaError> {
+    tracing::info!(
+        "Load request registration for RDF file {} in graph {}",
+        registration.s3_iri.as_str(),
+        registration.graph_load_requests.as_str()
+    );
+    let load_request_iri = Iri::new(load_request_iri(
+        ekg_identifier_contexts,
+        load_request_id,
+    ))?;
+
+    // A source that was deleted before is no longer retired once it is loaded
+    // again, its dataops:retiredAt stays so that the load requests from before
+    // it was retired do not count as having loaded it (see is_already_loaded)
+    let sparql = formatdoc! {
+        r#"
+            DELETE WHERE {{
+                GRAPH {graph_load_requests} {{
+                    {s3_iri} a dataops:RetiredDataset .
+                }}
+            }} ;
+            INSERT DATA {{
+                GRAPH {graph_load_requests} {{
+                    {pipeline_iri} a dataops:Pipeline ;
+                        rdfs:label {pipeline_label} .
+                    {load_request_iri} a dataops:LoadRequest ; a dataops:QueuedLoadRequest ;
+                        rdfs:label {load_request_label} ;
+                        dataops:inPipeline {pipeline_iri} ;
+                        dataops:source {s3_iri} ;
+                        dataops:graph {graph_iri} .
+                    {source_version}
+                    {s3_iri} a dataops:Dataset ; a dataops:SingleGraphDataset ;
+                        rdfs:label {s3_label} ;
+                        dataops:loadedByLoadRequest {load_request_iri} .
+                }}
+            }} ;
+            INSERT {{
+                GRAPH {graph_load_requests} {{
+                    {load_request_iri} dataops:latestStatusChange ?statusChange .
+                    ?statusChange a dataops:LoadRequestStatusChange ;
+                        dataops:loadRequest {load_request_iri} ;
+                        dataops:toStatus dataops:QueuedLoadRequest ;
+                        dataops:changedAt ?now .
+                }}
+            }}
+            WHERE {{
+                BIND(NOW() AS ?now)
+                BIND(IRI(CONCAT(STR({load_request_iri}), "-status-", STRUUID())) AS ?statusChange)
+            }}
+        "#,
+        pipeline_label = registration.pipeline_label,
+        pipeline_iri = registration.pipeline_iri,
+        graph_load_requests = registration.graph_load_requests,
+        load_request_iri = load_request_iri,
+        load_request_label = registration.load_request_label,
+        s3_iri = registration.s3_iri,
+        graph_iri = registration.graph_iri,
+        s3_label = registration.s3_label,
+        source_version = match &registration.source_version {
+            Some(source_version) => {
+                format!("{} dataops:sourceVersion {} .", load_request_iri, source_version)
+            },
+            None => String::new(),
+        },
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder()
+            .declare(NS_DATAOPS.deref())
+            .declare(NS_RDFS.deref())
+            .build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    clients.sparql_client.execute(&statement).await?;
+
+    Ok(LambdaResponse::ok(
+        LoaderJobInQueue,
+        Some("Load request registered successfully"),
+    ))
+}
+
+/// Whether the given version of the source of the load request has been loaded
+/// before, or is being loaded, by a load request that did not fail. Load
+/// requests that were queued before the source was last retired (deleted from
+/// S3) do not count, the source has to be loaded again. Neither do load
+/// requests that did not finish while the state machine could still be
+/// working on them (see [`RetryPolicy::max_duration_seconds`]), their
+/// execution gave up or died.
+async fn is_already_loaded<L: BulkLoader, G: GraphStore, S: ObjectStore>(
+    load_request: &LoadRequest,
+    source_version: &str,
+    pipeline_id: &str,
+    ekg_identifier_contexts: &EkgIdentifierContexts,
+    clients: Clients<L, G, S>,
+) -> Result<bool, LambdaError> {
+    let graph_load_requests =
+        ekg_aws_util::lambda::load_requests_graph_iri(ekg_identifier_contexts, pipeline_id);
+    let max_duration_seconds = RetryPolicy::from_env()?.max_duration_seconds();
+    let stale_before = Utc::now() - Duration::seconds(max_duration_seconds as i64);
+
+    let sparql = formatdoc! {
+        r#"
+            ASK {{
+                GRAPH {graph_load_requests} {{
+                    {s3_iri} dataops:loadedByLoadRequest ?loadRequest .
+                    ?loadRequest dataops:sourceVersion {source_version} ;
+                        dataops:latestStatusChange/dataops:changedAt ?changedAt .
+                    ?queued dataops:loadRequest ?loadRequest ;
+                        dataops:toStatus dataops:QueuedLoadRequest ;
+                        dataops:changedAt ?queuedAt .
+                    FILTER NOT EXISTS {{ ?loadRequest a dataops:FailedLoadRequest }}
+                    FILTER NOT EXISTS {{
+                        {s3_iri} dataops:retiredAt ?retiredAt .
+                        FILTER(?retiredAt >= ?queuedAt)
+                    }}
+                    FILTER(
+                        EXISTS {{ ?loadRequest a dataops:FinishedLoadRequest }} ||
+                        ?changedAt >= {stale_before}
+                    )
+                }}
+            }}
+        "#,
+        graph_load_requests = Iri::new(graph_load_requests)?,
+        s3_iri = Iri::new(load_request.source_iri())?,
+        source_version = Literal::string(source_version),
+        stale_before = Literal::date_time(stale_before.to_rfc3339_opts(SecondsFormat::Secs, true)),
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    Ok(clients.sparql_client.ask(&statement).await?)
+}
+
+/// Write a copy of the source with its placeholder IRIs replaced by the given
+/// rewriter (see [`PlaceholderRewriter::from_env`]) to the staging prefix and
+/// return the load request for that copy. If there are no placeholders to
+/// rewrite, the load request is returned as is.
+async fn stage_source<L: BulkLoader, G: GraphStore, S: ObjectStore>(
+    load_request: &LoadRequest,
+    rewriter: &PlaceholderRewriter,
+    clients: Clients<L, G, S>,
+) -> Result<LoadRequest, StagingError> {
+    if rewriter.is_empty() {
+        return Ok(load_request.clone());
+    }
+    let staged_source = staged_s3_uri(
+        load_request.source.as_str(),
+        staging_prefix_from_env().as_str(),
+        load_request.source_version.as_deref(),
+    )
+    .map_err(StagingError::Permanent)?;
+    stage(
+        &clients.aws_s3_client,
+        rewriter,
+        load_request.source.as_str(),
+        staged_source.as_str(),
+    )
+    .await?;
+    Ok(LoadRequest {
+        staged_source: Some(staged_source),
+        ..load_request.clone()
+    })
+}
+
+/// Initiate the load request using the NeptuneData API.
+async fn handle_load_request<L: BulkLoader, G: GraphStore, S: ObjectStore>(
+    load_request: &LoadRequest,
+    pipeline_id: &str,
+    clients: Clients<L, G, S>,
+) -> Result<LambdaResponse, LambdaError> {
+    tracing::info!(
+        "Load request for RDF file {:} (pipeline {:})",
+        load_request.loader_source(),
+        pipeline_id
+    );
+
+    let result = clients
+        .aws_neptunedata_client
+        .start_load(load_request)
+        .await;
+
+    match result {
+        Ok(ref loader_job_output) => Ok(LambdaResponse::from(loader_job_output)),
+        Err(error) => Ok(error.into()),
+    }
+}
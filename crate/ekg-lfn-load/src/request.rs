@@ -1,5 +1,5 @@
 use {
-    ekg_aws_util::{neptune::LoadRequest, ARN},
+    ekg_aws_util::{lambda::LambdaResponse, neptune::LoadRequest, ARN},
     serde::{Deserialize, Serialize},
 };
 
@@ -9,4 +9,12 @@ pub struct Request {
     pub load_request:     LoadRequest,
     pub pipeline_id:      String,
     pub rdf_load_sfn_arn: ARN,
+    /// The output of the previous attempt to instruct the loader, if the state
+    /// machine is retrying
+    #[serde(
+        default,
+        rename = "LoadOutput",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub load_output:      Option<LambdaResponse>,
 }
@@ -1,28 +1,74 @@
 #![cfg(test)]
 
-// #[ignore]
+use {
+    aws_sdk_neptunedata::{
+        operation::start_loader_job::StartLoaderJobError,
+        types::error::BadRequestException,
+    },
+    ekg_aws_util::{
+        graph_store::{InMemoryGraphStore, OxigraphStore},
+        lambda::{
+            load_request_iri,
+            load_requests_graph_iri,
+            LambdaDetailStatus,
+            LambdaResponse,
+            Retryability,
+        },
+        neptune::InMemoryBulkLoader,
+        object_store::InMemoryObjectStore,
+        placeholder::{PlaceholderRewriter, StagingError},
+    },
+    ekg_identifier::{EkgIdentifierContexts, NS_DATAOPS, NS_RDFS},
+    ekg_mock_neptune_loader::MockLoader,
+    ekg_sparql::Prefixes,
+    flate2::{read::GzDecoder, write::GzEncoder},
+    std::{
+        io::{Read, Write},
+        ops::Deref,
+    },
+};
+
+/// The given query with the prefixes that the lambda functions use
+fn sparql(query: &str) -> Result<String, ekg_error::Error> {
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder()
+            .declare(NS_DATAOPS.deref())
+            .declare(NS_RDFS.deref())
+            .build()?,
+        std::borrow::Cow::Borrowed(query),
+    )?;
+    Ok(statement.to_string())
+}
+
 #[test_log::test(tokio::test)]
 async fn test_load_01() -> Result<(), ekg_error::Error> {
     tracing::info!("test_load_01");
 
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
     ekg_identifier::EkgIdentifierContexts::default_test();
     std::env::set_var(
         "EKG_SPARQL_LOADER_ENDPOINT",
-        "http://localhost:8787/loader",
+        handle.loader_endpoint(),
     );
     std::env::set_var(
         "EKG_SPARQL_QUERY_ENDPOINT",
-        "http://localhost:8787/sparql",
+        handle.sparql_endpoint(),
     );
     std::env::set_var(
         "EKG_SPARQL_UPDATE_ENDPOINT",
-        "http://localhost:8787/sparql",
+        handle.sparql_endpoint(),
     );
     std::env::set_var(
         "AWS_NEPTUNE_LOAD_IAM_ROLE_ARN",
         "arn:aws:iam::12345:role/ekgf-dt-dev-neptune-load",
     );
     std::env::set_var("AWS_REGION", "antartica-01");
+    std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDTEST");
+    std::env::set_var("AWS_SECRET_ACCESS_KEY", "SECRET");
     let aws_config = aws_config::load_from_env().await;
     let clients = crate::Clients {
         // Create the NeptuneData client
@@ -30,18 +76,517 @@ async fn test_load_01() -> Result<(), ekg_error::Error> {
         // Create the HTTP SPARQL client (which strangely enough is not part of the
         // aws_sdk_neptunedata or aws_sdk_neptune crates, we had to build one ourselves)
         sparql_client:          ekg_sparql::SPARQLClient::from_env().await?,
+        aws_s3_client:          aws_sdk_s3::Client::new(&aws_config),
     };
     let event = include_str!("../event.json");
     let request_as_value: serde_json::Value = serde_json::from_str(event).unwrap();
     println!("result: {:#?}", request_as_value);
     let request = serde_json::from_value::<crate::Request>(request_as_value.clone())?;
     println!("result: {:#?}", request);
-    let lambda_output = crate::handle_lambda_request(&request, clients).await?;
+    let lambda_output = crate::handle_lambda_request(&request, "test", clients)
+        .await
+        .expect("load request failed");
     println!("result: {:#?}", lambda_output);
-    assert_eq!(lambda_output.status_code, 500);
+    assert_eq!(lambda_output.status_code, 200);
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::LoaderJobInQueue
+    ));
+
+    // The mock loader should have received the load request ...
+    let jobs = mock_loader.jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(
+        lambda_output.result_identifier.as_deref(),
+        Some(jobs[0].load_id.as_str())
+    );
+    assert_eq!(jobs[0].source, request.load_request.source);
+    // ... and the registration of the load request in the load-requests graph
+    let sparql_updates = mock_loader.sparql_updates();
+    assert_eq!(sparql_updates.len(), 1);
+    // ... including the first entry of its status history
+    assert!(sparql_updates[0].contains("dataops:LoadRequestStatusChange"));
+    assert!(sparql_updates[0].contains("dataops:toStatus dataops:QueuedLoadRequest"));
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_load_in_memory() -> Result<(), ekg_error::Error> {
+    ekg_identifier::EkgIdentifierContexts::default_test();
+    let clients = crate::Clients {
+        aws_neptunedata_client: InMemoryBulkLoader::new(),
+        sparql_client:          InMemoryGraphStore::new(),
+        aws_s3_client:          InMemoryObjectStore::new(),
+    };
+    let exc = BadRequestException::builder()
+        .code("BadRequestException")
+        .request_id("7a3f9f0e")
+        .detailed_message("Max load task queue size limit breached. Limit is 64")
+        .message("Max load task queue size limit breached. Limit is 64")
+        .build()
+        .unwrap();
+    clients
+        .aws_neptunedata_client
+        .push_start_error(StartLoaderJobError::BadRequestException(exc));
+    let mut payload: serde_json::Value = serde_json::from_str(include_str!("../event.json"))?;
+
+    // The first attempt finds the loader queue full ...
+    let lambda_output = crate::handle_lambda_payload(payload.clone(), "test", clients.clone())
+        .await
+        .expect("load request failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::MaxLoadTaskQueueSizeLimitBreached
+    ));
+    assert_eq!(
+        lambda_output.retryability,
+        Retryability::Transient
+    );
+    assert_eq!(lambda_output.attempt, 1);
+    assert!(clients.aws_neptunedata_client.jobs().is_empty());
+    assert!(clients.sparql_client.statements().is_empty());
+
+    // ... the retry gets the job accepted and registered
+    payload["LoadOutput"] = serde_json::to_value(&lambda_output)?;
+    let lambda_output = crate::handle_lambda_payload(payload, "test", clients.clone())
+        .await
+        .expect("load request failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::LoaderJobInQueue
+    ));
+    assert_eq!(lambda_output.attempt, 2);
+    let jobs = clients.aws_neptunedata_client.jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(
+        lambda_output.result_identifier.as_deref(),
+        Some(jobs[0].load_id.as_str())
+    );
+    let statements = clients.sparql_client.statements();
+    assert_eq!(statements.len(), 1);
+    assert!(statements[0].contains("dataops:toStatus dataops:QueuedLoadRequest"));
+
+    Ok(())
+}
+
+/// A load request that cannot be registered because of an invalid IRI fails
+/// before the loader job is started
+#[test_log::test(tokio::test)]
+async fn test_load_invalid_iri() -> Result<(), ekg_error::Error> {
+    EkgIdentifierContexts::default_test();
+    let clients = crate::Clients {
+        aws_neptunedata_client: InMemoryBulkLoader::new(),
+        sparql_client:          InMemoryGraphStore::new(),
+        aws_s3_client:          InMemoryObjectStore::new(),
+    };
+    let mut request = serde_json::from_str::<crate::Request>(include_str!("../event.json"))?;
+    request.load_request.parser_configuration.named_graph_uri =
+        "https://kg.example.com/graph/get use cases".to_string();
+
+    let result = crate::handle_lambda_request(&request, "test", clients.clone()).await;
+    assert!(result.is_err());
+    assert!(clients.aws_neptunedata_client.jobs().is_empty());
+    assert!(clients.sparql_client.statements().is_empty());
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_load_request_registration() -> Result<(), ekg_error::Error> {
+    EkgIdentifierContexts::default_test();
+    let ekg_identifier_contexts = EkgIdentifierContexts::from_env()?;
+    let store = OxigraphStore::new()?;
+    let clients = crate::Clients {
+        aws_neptunedata_client: InMemoryBulkLoader::new(),
+        sparql_client:          store.clone(),
+        aws_s3_client:          InMemoryObjectStore::new(),
+    };
+    let request = serde_json::from_str::<crate::Request>(include_str!("../event.json"))?;
+
+    let registration = crate::Registration::new(
+        &request.load_request,
+        "test",
+        &ekg_identifier_contexts,
+    )?;
+    crate::handle_load_request_registration(
+        &registration,
+        "in-memory-1",
+        &ekg_identifier_contexts,
+        clients,
+    )
+    .await
+    .expect("registration failed");
+
+    let solutions = store.select(&sparql(
+        r#"
+        SELECT ?graph ?loadRequest ?status ?pipeline ?dataset
+        WHERE {
+            GRAPH ?graph {
+                ?loadRequest a dataops:LoadRequest ;
+                    dataops:inPipeline ?pipeline ;
+                    dataops:latestStatusChange ?statusChange .
+                ?statusChange dataops:toStatus ?status .
+                ?dataset dataops:loadedByLoadRequest ?loadRequest .
+            }
+        }
+        "#,
+    )?)?;
+    assert_eq!(solutions.len(), 1);
+    let solution = &solutions[0];
+    assert_eq!(
+        solution["graph"],
+        load_requests_graph_iri(&ekg_identifier_contexts, "test")
+    );
+    assert_eq!(
+        solution["loadRequest"],
+        load_request_iri(&ekg_identifier_contexts, "in-memory-1")
+    );
+    assert!(solution["status"].ends_with("QueuedLoadRequest"));
+    assert!(solution["pipeline"].ends_with("dataops-pipeline-test"));
+    assert_eq!(solution["dataset"], request.load_request.source);
+
+    // The source is labelled as an S3 file
+    assert!(store.ask(&sparql(
+        r#"
+        ASK {
+            GRAPH ?graph {
+                ?dataset a dataops:Dataset ;
+                    rdfs:label ?label .
+                FILTER(STRSTARTS(?label, "S3 file s3://"))
+            }
+        }
+        "#,
+    )?)?);
+
+    // The load request records its source and its named graph for lineage
+    let solutions = store.select(&sparql(
+        r#"
+        SELECT ?source ?graph
+        WHERE {
+            GRAPH ?g {
+                ?loadRequest a dataops:LoadRequest ;
+                    dataops:source ?source ;
+                    dataops:graph ?graph .
+            }
+        }
+        "#,
+    )?)?;
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(
+        solutions[0]["source"],
+        request.load_request.source
+    );
+    assert_eq!(
+        solutions[0]["graph"],
+        request.load_request.target_graph_uri()
+    );
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_load_already_loaded() -> Result<(), ekg_error::Error> {
+    EkgIdentifierContexts::default_test();
+    let ekg_identifier_contexts = EkgIdentifierContexts::from_env()?;
+    let store = OxigraphStore::new()?;
+    let clients = crate::Clients {
+        aws_neptunedata_client: InMemoryBulkLoader::new(),
+        sparql_client:          store.clone(),
+        aws_s3_client:          InMemoryObjectStore::new(),
+    };
+    let mut payload: serde_json::Value = serde_json::from_str(include_str!("../event.json"))?;
+    payload["load_request"]["sourceVersion"] = "LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2".into();
+
+    // The first delivery of the S3 event starts a loader job ...
+    let lambda_output = crate::handle_lambda_payload(payload.clone(), "test", clients.clone())
+        .await
+        .expect("load request failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::LoaderJobInQueue
+    ));
+    assert_eq!(clients.aws_neptunedata_client.jobs().len(), 1);
+    assert!(store.ask(&sparql(
+        r#"
+        ASK {
+            GRAPH ?graph {
+                ?loadRequest dataops:sourceVersion "LBK4atYjFZR7h5v_.bUVAuWLbYpwCeB2" .
+            }
+        }
+        "#,
+    )?)?);
+
+    // ... a redelivery of the same event does not
+    let lambda_output = crate::handle_lambda_payload(payload.clone(), "test", clients.clone())
+        .await
+        .expect("load request failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::AlreadyLoaded
+    ));
+    assert_eq!(lambda_output.status_code, 200);
+    assert_eq!(
+        lambda_output.retryability,
+        Retryability::Succeeded
+    );
+    assert_eq!(clients.aws_neptunedata_client.jobs().len(), 1);
+
+    // A new version of the same S3 object is loaded
+    payload["load_request"]["sourceVersion"] = "3HL4kqtJlcpXroDTDmJ".into();
+    let lambda_output = crate::handle_lambda_payload(payload.clone(), "test", clients.clone())
+        .await
+        .expect("load request failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::LoaderJobInQueue
+    ));
+    assert_eq!(clients.aws_neptunedata_client.jobs().len(), 2);
+
+    // A failed load of a version can be retried by redelivering its event
+    store.update(
+        sparql(
+            format!(
+                r#"
+                INSERT DATA {{
+                    GRAPH <{}> {{
+                        <{}> a dataops:FailedLoadRequest .
+                    }}
+                }}
+                "#,
+                load_requests_graph_iri(&ekg_identifier_contexts, "test"),
+                load_request_iri(
+                    &ekg_identifier_contexts,
+                    lambda_output.result_identifier.as_deref().unwrap()
+                ),
+            )
+            .as_str(),
+        )?
+        .as_str(),
+    )?;
+    let lambda_output = crate::handle_lambda_payload(payload, "test", clients.clone())
+        .await
+        .expect("load request failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::LoaderJobInQueue
+    ));
+    assert_eq!(clients.aws_neptunedata_client.jobs().len(), 3);
+
+    Ok(())
+}
+
+/// Load the given payload twice with a fresh store, applying the given SPARQL
+/// update to that store in between, and return the number of loader jobs
+async fn load_twice(payload: &serde_json::Value, update: &str) -> Result<usize, ekg_error::Error> {
+    let store = OxigraphStore::new()?;
+    let clients = crate::Clients {
+        aws_neptunedata_client: InMemoryBulkLoader::new(),
+        sparql_client:          store.clone(),
+        aws_s3_client:          InMemoryObjectStore::new(),
+    };
+    for _ in 0..2 {
+        crate::handle_lambda_payload(payload.clone(), "test", clients.clone())
+            .await
+            .expect("load request failed");
+        if !update.is_empty() {
+            store.update(sparql(update)?.as_str())?;
+        }
+    }
+    Ok(clients.aws_neptunedata_client.jobs().len())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_load_already_loaded_but_retired_or_stale() -> Result<(), ekg_error::Error> {
+    EkgIdentifierContexts::default_test();
+    let mut payload: serde_json::Value = serde_json::from_str(include_str!("../event.json"))?;
+    payload["load_request"]["sourceVersion"] = "455c556f7d1b7f8587ecabe2dd8184af".into();
+    assert_eq!(load_twice(&payload, "").await?, 1);
+
+    // Deleting the object and uploading the same bytes again (the same eTag
+    // in a bucket that is not versioning-enabled) loads it again
+    let retire = r#"
+        INSERT {
+            GRAPH ?graph { ?dataset dataops:retiredAt ?now }
+        }
+        WHERE {
+            GRAPH ?graph { ?dataset a dataops:Dataset }
+            BIND(NOW() AS ?now)
+        }
+    "#;
+    assert_eq!(load_twice(&payload, retire).await?, 2);
+
+    // A load request that never got further than the queue, because its
+    // execution died, does not block its version forever
+    let age = r#"
+        DELETE {
+            GRAPH ?graph { ?statusChange dataops:changedAt ?changedAt }
+        }
+        INSERT {
+            GRAPH ?graph {
+                ?statusChange dataops:changedAt
+                    "2000-01-01T00:00:00Z"^^<http://www.w3.org/2001/XMLSchema#dateTime>
+            }
+        }
+        WHERE {
+            GRAPH ?graph { ?statusChange dataops:changedAt ?changedAt }
+        }
+    "#;
+    assert_eq!(load_twice(&payload, age).await?, 2);
+
+    // ... unless it finished
+    let finish = format!(
+        "INSERT {{ GRAPH ?graph {{ ?loadRequest a dataops:FinishedLoadRequest }} }} WHERE {{ \
+         GRAPH ?graph {{ ?loadRequest a dataops:LoadRequest }} }} ; {}",
+        age
+    );
+    assert_eq!(load_twice(&payload, finish.as_str()).await?, 1);
+
+    // An object without a version ID or an eTag has no version at all
+    payload["load_request"]["sourceVersion"] = "".into();
+    assert_eq!(load_twice(&payload, "").await?, 2);
+
+    Ok(())
+}
+
+/// A Turtle file that is written against the placeholder bases
+const PLACEHOLDER_TURTLE: &str = concat!(
+    "@prefix ex: <https://placeholder.kg/ontology/> .\n",
+    "<https://placeholder.kg/id/person-1> a ex:Person ;\n",
+    "    ex:homepage \"https://placeholder.kg/id/person-1\" .\n",
+);
+
+#[test_log::test(tokio::test)]
+async fn test_load_staged_source() -> Result<(), ekg_error::Error> {
+    ekg_identifier::EkgIdentifierContexts::default_test();
+    let request = serde_json::from_str::<crate::Request>(include_str!("../event.json"))?;
+    let bucket = "ekgf-dt-dev-metadata";
+    let key = "use-case/studio/stories/get-use-cases/tests.ttl";
+    let clients = crate::Clients {
+        aws_neptunedata_client: InMemoryBulkLoader::new(),
+        sparql_client:          InMemoryGraphStore::new(),
+        aws_s3_client:          InMemoryObjectStore::new().with_object(
+            bucket,
+            key,
+            PLACEHOLDER_TURTLE,
+        ),
+    };
+    let rewriter = PlaceholderRewriter::new([
+        ("https://placeholder.kg", "https://kg.example.com"),
+        (
+            "https://placeholder.kg/id",
+            "https://kg.example.com/id/",
+        ),
+    ]);
+
+    // The placeholders are rewritten into a copy under the staging prefix ...
+    let load_request =
+        crate::stage_source(&request.load_request, &rewriter, clients.clone()).await?;
+    let staged_key = format!("ekg-staging/{}", key);
+    let staged_source = format!("s3://{}/{}", bucket, staged_key);
+    assert_eq!(load_request.source, request.load_request.source);
+    assert_eq!(
+        load_request.staged_source.as_deref(),
+        Some(staged_source.as_str())
+    );
+    let staged = clients
+        .aws_s3_client
+        .object(bucket, staged_key.as_str())
+        .expect("missing staged copy");
+    assert_eq!(
+        String::from_utf8(staged).unwrap(),
+        PLACEHOLDER_TURTLE
+            .replace(
+                "https://placeholder.kg/id/",
+                "https://kg.example.com/id/"
+            )
+            .replace(
+                "https://placeholder.kg/",
+                "https://kg.example.com/"
+            )
+    );
+
+    // ... which is what the bulk loader loads
+    crate::handle_load_request(&load_request, "test", clients.clone())
+        .await
+        .expect("load request failed");
+    assert_eq!(
+        clients.aws_neptunedata_client.jobs()[0].source,
+        staged_source
+    );
+
+    // Without placeholders to rewrite, the bulk loader loads the source itself
+    let load_request = crate::stage_source(
+        &request.load_request,
+        &PlaceholderRewriter::default(),
+        clients.clone(),
+    )
+    .await?;
+    assert_eq!(load_request.staged_source, None);
     assert_eq!(
-        lambda_output.message,
-        "Dispatch failure: io error"
+        load_request.loader_source(),
+        request.load_request.source
+    );
+
+    // A compressed source is compressed again after rewriting
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(PLACEHOLDER_TURTLE.as_bytes()).unwrap();
+    let _ = clients.aws_s3_client.clone().with_object(
+        bucket,
+        format!("{}.gz", key).as_str(),
+        encoder.finish().unwrap(),
     );
+    let mut gzip_request = request.load_request.clone();
+    gzip_request.source = format!("{}.gz", request.load_request.source);
+    let load_request = crate::stage_source(&gzip_request, &rewriter, clients.clone()).await?;
+    assert_eq!(
+        load_request.staged_source,
+        Some(format!("{}.gz", staged_source))
+    );
+    let staged = clients
+        .aws_s3_client
+        .object(bucket, format!("{}.gz", staged_key).as_str())
+        .expect("missing staged copy");
+    let mut content = String::new();
+    GzDecoder::new(staged.as_slice())
+        .read_to_string(&mut content)
+        .unwrap();
+    assert!(content.contains("<https://kg.example.com/id/person-1>"));
+    assert!(!content.contains("<https://placeholder.kg/"));
+
+    // A source that is not there cannot be staged, for now
+    let mut missing_request = request.load_request.clone();
+    missing_request.source = format!("s3://{}/missing.ttl", bucket);
+    let error = crate::stage_source(&missing_request, &rewriter, clients.clone())
+        .await
+        .expect_err("a missing source should not be staged");
+    assert!(error.is_transient());
+
+    // A corrupt compressed source will never be staged
+    let _ = clients.aws_s3_client.clone().with_object(
+        bucket,
+        "corrupt.ttl.gz",
+        PLACEHOLDER_TURTLE.as_bytes().to_vec(),
+    );
+    let mut corrupt_request = request.load_request.clone();
+    corrupt_request.source = format!("s3://{}/corrupt.ttl.gz", bucket);
+    let error = crate::stage_source(&corrupt_request, &rewriter, clients.clone())
+        .await
+        .expect_err("a corrupt source should not be staged");
+    assert!(!error.is_transient());
+    let response = LambdaResponse::staging_failed(corrupt_request.source.as_str(), &error);
+    assert_eq!(response.retryability, Retryability::Permanent);
+    let response = LambdaResponse::staging_failed(
+        missing_request.source.as_str(),
+        &StagingError::Transient(ekg_error::Error::ServiceError(
+            "timeout".to_string(),
+        )),
+    );
+    assert_eq!(
+        response.detail_status,
+        LambdaDetailStatus::IOError
+    );
+    assert_eq!(response.retryability, Retryability::Transient);
+
     Ok(())
 }
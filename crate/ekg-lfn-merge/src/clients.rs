@@ -0,0 +1,6 @@
+/// The clients that the lambda function uses, the real one by default, see
+/// the `fake` feature of `ekg-aws-util` for an in-memory one.
+#[derive(Clone)]
+pub struct Clients<G = ekg_sparql::SPARQLClient> {
+    pub sparql_client: G,
+}
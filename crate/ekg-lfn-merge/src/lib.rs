@@ -0,0 +1,183 @@
+//! The handler of the merge lambda function, in a library so that it can be
+//! invoked in-process as well (see the `ekg-sfn-local` crate).
+pub use {
+    clients::Clients,
+    lambda_runtime::{Error as LambdaError, LambdaEvent},
+    request::Request,
+};
+use {
+    ekg_aws_util::{
+        graph_store::GraphStore,
+        lambda::{
+            default_merge_target_graph_iri,
+            load_request_iri,
+            load_requests_graph_iri,
+            prune_merge_targets,
+            LambdaDetailStatus,
+            LambdaResponse,
+            NS_PROV,
+        },
+        sparql::Iri,
+    },
+    ekg_identifier::{EkgIdentifierContexts, NS_DATAOPS},
+    ekg_sparql::Prefixes,
+    indoc::formatdoc,
+    serde_json::Value,
+    std::ops::Deref,
+};
+
+mod clients;
+mod request;
+#[cfg(test)]
+mod tests;
+
+/// The environment variable that holds the IRI of the graph into which the
+/// named graphs of the loaded files are merged.
+const ENV_MERGE_TARGET_GRAPH: &str = "EKG_MERGE_TARGET_GRAPH";
+
+// noinspection DuplicatedCode
+/// The actual handler of the Lambda request.
+pub async fn handle_lambda_event<G: GraphStore>(
+    event: LambdaEvent<Value>,
+    pipeline_id: &'static str,
+    clients: Clients<G>,
+) -> Result<LambdaResponse, LambdaError> {
+    tracing::trace!("Event {:#?}\n\n", event.clone());
+
+    let (payload, _ctx) = event.into_parts();
+
+    handle_lambda_payload(payload, pipeline_id, clients).await
+}
+
+pub async fn handle_lambda_payload<G: GraphStore>(
+    payload: Value,
+    pipeline_id: &'static str,
+    clients: Clients<G>,
+) -> Result<LambdaResponse, LambdaError> {
+    tracing::trace!(
+        "Payload {}",
+        serde_json::to_string_pretty(&payload)?
+    );
+
+    let request = serde_json::from_value::<Request>(payload).map_err(|e| {
+        tracing::error!("Error parsing request: {}", e);
+        e
+    })?;
+
+    match handle_lambda_request(&request, pipeline_id, clients).await {
+        Ok(response) => {
+            tracing::info!("Response: {:}", serde_json::to_string(&response)?);
+            Ok(response)
+        },
+        Err(error) => {
+            tracing::error!("Error handling request: {:?}", error);
+            Err(error)
+        },
+    }
+}
+
+pub async fn handle_lambda_request<G: GraphStore>(
+    request: &Request,
+    pipeline_id: &'static str,
+    clients: Clients<G>,
+) -> Result<LambdaResponse, LambdaError> {
+    if request.pipeline_id != pipeline_id {
+        return Ok(LambdaResponse::pipeline_id_not_matching(
+            request.pipeline_id.as_str(),
+            pipeline_id,
+        ));
+    }
+    let load_request_id =
+        request
+            .load_output
+            .result_identifier
+            .as_deref()
+            .ok_or(LambdaError::from(
+                "Missing resultIdentifier in LoadOutput",
+            ))?;
+    let identifier_contexts = EkgIdentifierContexts::from_env()?;
+    let target_graph = match std::env::var(ENV_MERGE_TARGET_GRAPH) {
+        Ok(target_graph) if !target_graph.trim().is_empty() => target_graph,
+        _ => default_merge_target_graph_iri(&identifier_contexts, pipeline_id),
+    };
+
+    handle_merge(
+        request.load_request.source_iri().as_str(),
+        request.load_request.target_graph_uri(),
+        target_graph.as_str(),
+        load_request_id,
+        pipeline_id,
+        &identifier_contexts,
+        clients,
+    )
+    .await
+}
+
+/// Add the triples of the named graph of the given source to the target graph
+/// and record the lineage in the load-requests graph using PROV-O:
+///
+/// - the target graph `prov:wasDerivedFrom` the named graph of the source
+/// - the named graph of the source `prov:wasGeneratedBy` the load request
+/// - the load request is a `prov:Activity` that `prov:used` the source
+///
+/// Before the triples are added, the triples that the named graph of the
+/// source no longer contains (because a new version of the source replaced it)
+/// are removed from the target graph, unless another named graph that the
+/// target graph was derived from contains them as well, see
+/// [`prune_merge_targets`].
+async fn handle_merge<G: GraphStore>(
+    source: &str,
+    source_graph: &str,
+    target_graph: &str,
+    load_request_id: &str,
+    pipeline_id: &str,
+    identifier_contexts: &EkgIdentifierContexts,
+    clients: Clients<G>,
+) -> Result<LambdaResponse, LambdaError> {
+    let graph_load_requests = load_requests_graph_iri(identifier_contexts, pipeline_id);
+
+    tracing::info!(
+        "Merging graph {} into graph {} (pipeline {})",
+        source_graph,
+        target_graph,
+        pipeline_id
+    );
+
+    let source_graph = Iri::new(source_graph)?;
+    let graph_load_requests = Iri::new(graph_load_requests)?;
+    let sparql = formatdoc! {
+        r#"
+            {prune} ;
+            ADD SILENT GRAPH {source_graph} TO GRAPH {target_graph} ;
+            INSERT DATA {{
+                GRAPH {graph_load_requests} {{
+                    {target_graph} <{prov}wasDerivedFrom> {source_graph} .
+                    {source_graph} <{prov}wasGeneratedBy> {load_request_iri} .
+                    {load_request_iri} a <{prov}Activity> ;
+                        <{prov}used> {source} .
+                }}
+            }}
+        "#,
+        prune = prune_merge_targets(&source_graph, &graph_load_requests),
+        prov = NS_PROV,
+        source = Iri::new(source)?,
+        target_graph = Iri::new(target_graph)?,
+        load_request_iri = Iri::new(load_request_iri(identifier_contexts, load_request_id))?,
+    };
+    let statement = ekg_sparql::Statement::new(
+        Prefixes::builder().declare(NS_DATAOPS.deref()).build()?,
+        std::borrow::Cow::Borrowed(sparql.as_str()),
+    )?;
+
+    clients.sparql_client.execute(&statement).await?;
+
+    let message = format!(
+        "Merged graph {} into graph {}",
+        source_graph.as_str(),
+        target_graph
+    );
+    Ok(LambdaResponse::ok(
+        LambdaDetailStatus::GraphMerged,
+        Some(message.as_str()),
+    ))
+}
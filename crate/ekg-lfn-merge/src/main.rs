@@ -0,0 +1,23 @@
+/// See https://github.com/awslabs/aws-lambda-rust-runtime for more info on Rust runtime for AWS Lambda
+use {
+    ekg_lfn_merge::{handle_lambda_event, Clients, LambdaError},
+    ekg_util::env::mandatory_env_var_static,
+    lambda_runtime::service_fn,
+};
+
+#[tokio::main]
+async fn main() -> Result<(), LambdaError> {
+    ekg_util::tracing::aws_lfn_init();
+
+    let clients = Clients {
+        // Create the HTTP SPARQL client (which strangely enough is not part of the
+        // aws_sdk_neptunedata or aws_sdk_neptune crates, we had to build one ourselves)
+        sparql_client: ekg_sparql::SPARQLClient::from_env().await?,
+    };
+    let pipeline_id = mandatory_env_var_static("EKG_PIPELINE_ID", None)?;
+
+    // Call the actual handler of the request
+    let func = service_fn(move |req| handle_lambda_event(req, pipeline_id, clients.clone()));
+    lambda_runtime::run(func).await?;
+    Ok(())
+}
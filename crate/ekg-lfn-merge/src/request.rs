@@ -0,0 +1,17 @@
+use {
+    ekg_aws_util::{lambda::LambdaResponse, neptune::LoadRequest},
+    serde::{Deserialize, Serialize},
+};
+
+/// Merge the named graph of a successfully loaded S3 based RDF file into the
+/// target graph. This is the state of the step function after the check
+/// lambda function found the loader job completed.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Request {
+    pub load_request: LoadRequest,
+    pub pipeline_id:  String,
+    /// The output of the load lambda function, its `result_identifier` is the
+    /// ID of the loader job
+    #[serde(rename = "LoadOutput")]
+    pub load_output:  LambdaResponse,
+}
@@ -0,0 +1,120 @@
+#![cfg(test)]
+
+use {
+    ekg_aws_util::{
+        graph_store::OxigraphStore,
+        lambda::{load_requests_graph_iri, LambdaDetailStatus},
+    },
+    ekg_identifier::EkgIdentifierContexts,
+    ekg_mock_neptune_loader::MockLoader,
+};
+
+const TARGET_GRAPH: &str = "https://placeholder.kg/graph/merged-test";
+const SOURCE_GRAPH: &str = "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl";
+const OTHER_SOURCE_GRAPH: &str = "s3://ekgf-dt-dev-metadata/ontology/cdmc-other.ttl";
+
+#[test_log::test(tokio::test)]
+async fn test_merge_01() -> Result<(), ekg_error::Error> {
+    tracing::info!("test_merge_01");
+
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+
+    ekg_identifier::EkgIdentifierContexts::default_test();
+    std::env::set_var(
+        "EKG_SPARQL_QUERY_ENDPOINT",
+        handle.sparql_endpoint(),
+    );
+    std::env::set_var(
+        "EKG_SPARQL_UPDATE_ENDPOINT",
+        handle.sparql_endpoint(),
+    );
+    std::env::set_var("EKG_MERGE_TARGET_GRAPH", TARGET_GRAPH);
+    let clients = crate::Clients {
+        sparql_client: ekg_sparql::SPARQLClient::from_env().await?,
+    };
+    let event = include_str!("../event.json");
+    let request = serde_json::from_str::<crate::Request>(event)?;
+    let lambda_output = crate::handle_lambda_request(&request, "test", clients)
+        .await
+        .expect("merge request failed");
+    tracing::info!("result: {:#?}", lambda_output);
+    assert_eq!(lambda_output.status_code, 200);
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::GraphMerged
+    ));
+
+    let sparql_updates = mock_loader.sparql_updates();
+    assert_eq!(sparql_updates.len(), 1);
+    assert!(sparql_updates[0].contains(
+        "ADD SILENT GRAPH <s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl> TO GRAPH \
+         <https://placeholder.kg/graph/merged-test>"
+    ));
+    assert!(sparql_updates[0].contains("http://www.w3.org/ns/prov#wasGeneratedBy"));
+    assert!(sparql_updates[0].contains("uuid:9d6e8f2a-6f4c-4c1e-9f1a-1d2b3c4d5e6f"));
+
+    Ok(())
+}
+
+/// A new version of a file replaced its named graph: the triples that are no
+/// longer in it leave the target graph, unless another file contributes them
+#[test_log::test(tokio::test)]
+async fn test_merge_replaced_version() -> Result<(), ekg_error::Error> {
+    EkgIdentifierContexts::default_test();
+    std::env::set_var("EKG_MERGE_TARGET_GRAPH", TARGET_GRAPH);
+    let graph_load_requests = load_requests_graph_iri(&EkgIdentifierContexts::from_env()?, "test");
+
+    // The previous version of the file contributed ex:kept and ex:dropped,
+    // another file contributed ex:shared as well
+    let store = OxigraphStore::new()?;
+    store.update(
+        format!(
+            r#"
+            PREFIX ex: <https://example.com/>
+            PREFIX prov: <http://www.w3.org/ns/prov#>
+            INSERT DATA {{
+                GRAPH <{SOURCE_GRAPH}> {{ ex:s ex:p ex:kept , ex:shared . }}
+                GRAPH <{OTHER_SOURCE_GRAPH}> {{ ex:s ex:p ex:shared . }}
+                GRAPH <{TARGET_GRAPH}> {{ ex:s ex:p ex:kept , ex:dropped , ex:shared . }}
+                GRAPH <{graph_load_requests}> {{
+                    <{TARGET_GRAPH}> prov:wasDerivedFrom <{SOURCE_GRAPH}> , <{OTHER_SOURCE_GRAPH}> .
+                }}
+            }}
+            "#
+        )
+        .as_str(),
+    )?;
+
+    let clients = crate::Clients { sparql_client: store.clone() };
+    let request = serde_json::from_str::<crate::Request>(include_str!("../event.json"))?;
+    let lambda_output = crate::handle_lambda_request(&request, "test", clients)
+        .await
+        .expect("merge request failed");
+    assert!(matches!(
+        lambda_output.detail_status,
+        LambdaDetailStatus::GraphMerged
+    ));
+
+    let objects = store
+        .select(
+            format!(
+                "SELECT ?o WHERE {{ GRAPH <{TARGET_GRAPH}> {{ <https://example.com/s> ?p ?o }} }}"
+            )
+            .as_str(),
+        )?
+        .into_iter()
+        .map(|solution| solution["o"].clone())
+        .collect::<std::collections::BTreeSet<_>>();
+    assert_eq!(
+        objects,
+        ["https://example.com/kept", "https://example.com/shared"]
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    );
+
+    Ok(())
+}
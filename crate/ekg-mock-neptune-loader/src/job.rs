@@ -0,0 +1,206 @@
+use {
+    serde_json::{json, Value},
+    std::time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const LOAD_IN_QUEUE: &str = "LOAD_IN_QUEUE";
+pub const LOAD_NOT_STARTED: &str = "LOAD_NOT_STARTED";
+pub const LOAD_IN_PROGRESS: &str = "LOAD_IN_PROGRESS";
+pub const LOAD_COMPLETED: &str = "LOAD_COMPLETED";
+pub const LOAD_CANCELLED_BY_USER: &str = "LOAD_CANCELLED_BY_USER";
+pub const LOAD_FAILED: &str = "LOAD_FAILED";
+
+/// One entry of the `errors.errorLogs` list that the Neptune loader returns
+/// when the status is requested with `errors=true`.
+#[derive(Clone, Debug)]
+pub struct LoaderErrorLog {
+    pub error_code:    String,
+    pub error_message: String,
+    pub file_name:     String,
+    pub record_num:    u64,
+}
+
+/// The scripted lifecycle of a loader job.
+///
+/// Every status request for a job returns the next status in the list, the
+/// last status is returned for all subsequent requests. So the default
+/// lifecycle `LOAD_IN_QUEUE` → `LOAD_IN_PROGRESS` → `LOAD_COMPLETED` means
+/// that the third (and every later) status check finds the job completed.
+#[derive(Clone, Debug)]
+pub struct JobLifecycle {
+    pub statuses:      Vec<String>,
+    pub total_records: u64,
+    pub errors:        Vec<LoaderErrorLog>,
+}
+
+impl Default for JobLifecycle {
+    fn default() -> Self { Self::completed() }
+}
+
+impl JobLifecycle {
+    pub fn new<S: Into<String>>(statuses: impl IntoIterator<Item = S>) -> Self {
+        let statuses: Vec<String> = statuses.into_iter().map(Into::into).collect();
+        assert!(
+            !statuses.is_empty(),
+            "a job lifecycle needs at least one status"
+        );
+        Self { statuses, total_records: 0, errors: vec![] }
+    }
+
+    /// Queue → in progress → completed
+    pub fn completed() -> Self { Self::new([LOAD_IN_QUEUE, LOAD_IN_PROGRESS, LOAD_COMPLETED]) }
+
+    /// Queue → in progress → failed
+    pub fn failed() -> Self { Self::new([LOAD_IN_QUEUE, LOAD_IN_PROGRESS, LOAD_FAILED]) }
+
+    pub fn with_total_records(self, total_records: u64) -> Self { Self { total_records, ..self } }
+
+    pub fn with_error(
+        mut self,
+        error_code: &str,
+        error_message: &str,
+        file_name: &str,
+        record_num: u64,
+    ) -> Self {
+        self.errors.push(LoaderErrorLog {
+            error_code: error_code.to_string(),
+            error_message: error_message.to_string(),
+            file_name: file_name.to_string(),
+            record_num,
+        });
+        self
+    }
+}
+
+/// What the mock loader should do with the next start request
+#[derive(Clone, Debug)]
+pub enum StartOutcome {
+    /// Accept the load request, the job follows the given lifecycle
+    Accept(JobLifecycle),
+    /// Reject with a 400 "Max load task queue size limit breached"
+    QueueFull,
+    /// Reject with a 400 "Max concurrent load limit breached"
+    ConcurrentLimit,
+}
+
+/// A loader job as registered by the mock loader
+#[derive(Clone, Debug)]
+pub struct LoaderJob {
+    pub load_id:    String,
+    pub source:     String,
+    pub request:    Value,
+    pub lifecycle:  JobLifecycle,
+    pub start_time: u64,
+    /// Number of status checks done so far
+    pub checks:     usize,
+    pub cancelled:  bool,
+}
+
+impl LoaderJob {
+    pub fn new(request: Value, lifecycle: JobLifecycle) -> Self {
+        Self {
+            load_id: new_load_id(),
+            source: request["source"].as_str().unwrap_or_default().to_string(),
+            request,
+            lifecycle,
+            start_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            checks: 0,
+            cancelled: false,
+        }
+    }
+
+    /// The status that the job currently has, without advancing the lifecycle
+    pub fn status(&self) -> &str {
+        if self.cancelled {
+            return LOAD_CANCELLED_BY_USER;
+        }
+        let statuses = &self.lifecycle.statuses;
+        statuses[self.checks.min(statuses.len() - 1)].as_str()
+    }
+
+    /// Return the status payload for the current step of the lifecycle and
+    /// move on to the next step.
+    pub fn check(&mut self, errors: bool, page: usize, errors_per_page: usize) -> Value {
+        let payload = self.status_payload(errors, page, errors_per_page);
+        self.checks += 1;
+        payload
+    }
+
+    fn status_payload(&self, errors: bool, page: usize, errors_per_page: usize) -> Value {
+        let status = self.status();
+        let finished =
+            status != LOAD_IN_QUEUE && status != LOAD_NOT_STARTED && status != LOAD_IN_PROGRESS;
+        let error_count = if finished {
+            self.lifecycle.errors.len() as u64
+        } else {
+            0
+        };
+        let total_records = if finished {
+            self.lifecycle.total_records
+        } else {
+            0
+        };
+        let overall_status = json!({
+            "fullUri": self.source,
+            "runNumber": 1,
+            "retryNumber": 0,
+            "status": status,
+            "totalTimeSpent": self.checks,
+            "startTime": self.start_time,
+            "totalRecords": total_records,
+            "totalDuplicates": 0,
+            "parsingErrors": error_count,
+            "datatypeMismatchErrors": 0,
+            "insertErrors": 0,
+        });
+        let mut payload = json!({
+            "feedCount": [{ status: 1 }],
+            "overallStatus": overall_status,
+        });
+        if finished && status != LOAD_COMPLETED {
+            payload["failedFeeds"] = json!([overall_status]);
+        }
+        if errors && error_count > 0 {
+            let start = page.saturating_sub(1) * errors_per_page;
+            let error_logs: Vec<Value> = self
+                .lifecycle
+                .errors
+                .iter()
+                .skip(start)
+                .take(errors_per_page)
+                .map(|error| {
+                    json!({
+                        "errorCode": error.error_code,
+                        "errorMessage": error.error_message,
+                        "fileName": error.file_name,
+                        "recordNum": error.record_num,
+                    })
+                })
+                .collect();
+            payload["errors"] = json!({
+                "startIndex": start + 1,
+                "endIndex": start + error_logs.len(),
+                "loadId": self.load_id,
+                "errorLogs": error_logs,
+            });
+        }
+        payload
+    }
+}
+
+/// Neptune load IDs are UUIDs
+fn new_load_id() -> String {
+    let id = rand::random::<u128>();
+    let hex = format!("{:032x}", id);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
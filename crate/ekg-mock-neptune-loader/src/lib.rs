@@ -0,0 +1,23 @@
+//! A local mock of the Amazon Neptune bulk loader HTTP API so that the
+//! `ekg-lfn-load` and `ekg-lfn-check` lambda functions (and the NeptuneData
+//! client that they use) can be tested without a Neptune cluster.
+pub use {
+    job::{
+        JobLifecycle,
+        LoaderErrorLog,
+        LoaderJob,
+        StartOutcome,
+        LOAD_CANCELLED_BY_USER,
+        LOAD_COMPLETED,
+        LOAD_FAILED,
+        LOAD_IN_PROGRESS,
+        LOAD_IN_QUEUE,
+        LOAD_NOT_STARTED,
+    },
+    server::{MockLoader, MockLoaderHandle},
+};
+
+mod job;
+mod server;
+#[cfg(test)]
+mod tests;
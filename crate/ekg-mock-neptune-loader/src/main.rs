@@ -0,0 +1,20 @@
+use {ekg_mock_neptune_loader::MockLoader, std::net::SocketAddr, tracing_subscriber::EnvFilter};
+
+/// Run the mock Neptune loader on `EKG_MOCK_LOADER_ADDRESS` (default
+/// `127.0.0.1:8787`), point `EKG_SPARQL_LOADER_ENDPOINT` to
+/// `http://127.0.0.1:8787/loader` to use it.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let address = std::env::var("EKG_MOCK_LOADER_ADDRESS")
+        .unwrap_or("127.0.0.1:8787".to_string())
+        .parse::<SocketAddr>()?;
+
+    let handle = MockLoader::new().serve(address)?;
+    tokio::signal::ctrl_c().await?;
+    handle.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,415 @@
+use {
+    crate::{JobLifecycle, LoaderJob, StartOutcome},
+    hyper::{
+        header::CONTENT_TYPE,
+        server::conn::AddrIncoming,
+        service::{make_service_fn, service_fn},
+        Body,
+        Method,
+        Request,
+        Response,
+        Server,
+        StatusCode,
+    },
+    serde_json::{json, Value},
+    std::{
+        collections::{HashMap, VecDeque},
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    },
+    tokio::{sync::oneshot, task::JoinHandle},
+};
+
+const DEFAULT_ERRORS_PER_PAGE: usize = 10;
+const DEFAULT_LIST_LIMIT: usize = 100;
+
+#[derive(Default)]
+struct State {
+    default_lifecycle: JobLifecycle,
+    start_outcomes:    VecDeque<StartOutcome>,
+    jobs:              Vec<LoaderJob>,
+    failing_page:      Option<usize>,
+    sparql_updates:    Vec<String>,
+    sparql_queries:    Vec<String>,
+}
+
+/// A mock of the Neptune bulk loader HTTP API.
+///
+/// Serves:
+///
+/// - `POST /loader` to start a loader job
+/// - `GET /loader/{loadId}?errors=true&page=n&errorsPerPage=n` to get the
+///   status of a loader job
+/// - `GET /loader?limit=n` to list the loader jobs
+/// - `DELETE /loader/{loadId}` to cancel a loader job
+/// - `POST /sparql` which simply records the SPARQL updates and queries that
+///   the lambda functions send so that tests can inspect them, every ASK query
+///   is answered with `false` and every other query without any solutions
+///
+/// See https://docs.aws.amazon.com/neptune/latest/userguide/load-api-reference.html
+#[derive(Clone, Default)]
+pub struct MockLoader {
+    state: Arc<Mutex<State>>,
+}
+
+/// A running mock loader, stops when [`MockLoaderHandle::shutdown`] is called
+/// or when the handle is dropped.
+pub struct MockLoaderHandle {
+    pub local_addr: SocketAddr,
+    shutdown:       Option<oneshot::Sender<()>>,
+    join_handle:    JoinHandle<Result<(), hyper::Error>>,
+}
+
+impl MockLoader {
+    pub fn new() -> Self { Self::default() }
+
+    /// The lifecycle of every accepted job for which no explicit
+    /// [`StartOutcome`] has been pushed.
+    pub fn with_default_lifecycle(self, lifecycle: JobLifecycle) -> Self {
+        self.state.lock().unwrap().default_lifecycle = lifecycle;
+        self
+    }
+
+    /// Script the outcome of the next start request, outcomes are used in the
+    /// order in which they were pushed.
+    pub fn push_start_outcome(&self, outcome: StartOutcome) {
+        self.state.lock().unwrap().start_outcomes.push_back(outcome);
+    }
+
+    /// Answer every status request for the given page of errors, or a later
+    /// one, with an internal failure
+    pub fn fail_error_pages_from(&self, page: usize) {
+        self.state.lock().unwrap().failing_page = Some(page);
+    }
+
+    /// All jobs that have been accepted so far, oldest first
+    pub fn jobs(&self) -> Vec<LoaderJob> { self.state.lock().unwrap().jobs.clone() }
+
+    /// All SPARQL updates that have been posted to `/sparql` so far
+    pub fn sparql_updates(&self) -> Vec<String> {
+        self.state.lock().unwrap().sparql_updates.clone()
+    }
+
+    /// All SPARQL queries that have been posted to `/sparql` so far
+    pub fn sparql_queries(&self) -> Vec<String> {
+        self.state.lock().unwrap().sparql_queries.clone()
+    }
+
+    /// Start serving on the given address, use port 0 to let the OS pick a
+    /// free port, see [`MockLoaderHandle::local_addr`].
+    pub fn serve(&self, addr: SocketAddr) -> Result<MockLoaderHandle, hyper::Error> {
+        let incoming = AddrIncoming::bind(&addr)?;
+        let local_addr = incoming.local_addr();
+        let state = self.state.clone();
+        let make_service = make_service_fn(move |_conn| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle_request(request, state.clone())
+                }))
+            }
+        });
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        let server = Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                shutdown_signal.await.ok();
+            });
+        tracing::info!("Mock Neptune loader listening on {}", local_addr);
+        Ok(MockLoaderHandle {
+            local_addr,
+            shutdown: Some(shutdown),
+            join_handle: tokio::spawn(server),
+        })
+    }
+}
+
+impl MockLoaderHandle {
+    /// The value for the `EKG_SPARQL_LOADER_ENDPOINT` environment variable
+    pub fn loader_endpoint(&self) -> String { format!("http://{}/loader", self.local_addr) }
+
+    /// The value for the `EKG_SPARQL_UPDATE_ENDPOINT` environment variable
+    pub fn sparql_endpoint(&self) -> String { format!("http://{}/sparql", self.local_addr) }
+
+    pub async fn shutdown(mut self) -> Result<(), hyper::Error> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        (&mut self.join_handle).await.unwrap_or_else(|_| Ok(()))
+    }
+}
+
+impl Drop for MockLoaderHandle {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    state: Arc<Mutex<State>>,
+) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().trim_end_matches('/').to_string();
+    let query = request
+        .uri()
+        .query()
+        .and_then(|query| serde_urlencoded::from_str::<HashMap<String, String>>(query).ok())
+        .unwrap_or_default();
+    let form_encoded = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(|content_type| content_type.starts_with("application/x-www-form-urlencoded"))
+        .unwrap_or(false);
+    let body = hyper::body::to_bytes(request.into_body())
+        .await
+        .unwrap_or_default();
+
+    tracing::info!("{} {}", method, path);
+
+    let mut state = state.lock().unwrap();
+    let response = match (&method, path.as_str()) {
+        (&Method::POST, "/loader") => start_loader_job(&mut state, &body),
+        (&Method::GET, "/loader") => list_loader_jobs(&state, &query),
+        (&Method::GET, path) if path.starts_with("/loader/") => {
+            get_loader_job_status(&mut state, &path["/loader/".len()..], &query)
+        },
+        (&Method::DELETE, path) if path.starts_with("/loader/") => {
+            cancel_loader_job(&mut state, &path["/loader/".len()..])
+        },
+        (&Method::POST, "/sparql") => {
+            match sparql_operation(&body, form_encoded) {
+                SparqlOperation::Query(query) => {
+                    let result = if first_keyword(query.as_str()) == Some("ASK".to_string()) {
+                        json!({"head": {}, "boolean": false})
+                    } else {
+                        json!({"head": {"vars": []}, "results": {"bindings": []}})
+                    };
+                    state.sparql_queries.push(query);
+                    json_response(StatusCode::OK, result)
+                },
+                SparqlOperation::Update(update) => {
+                    state.sparql_updates.push(update);
+                    json_response(
+                        StatusCode::OK,
+                        json!({"type": "UPDATE", "result": []}),
+                    )
+                },
+            }
+        },
+        _ => {
+            error_response(
+                StatusCode::NOT_FOUND,
+                "UnsupportedOperationException",
+                &format!("No mock for {} {}", method, path),
+            )
+        },
+    };
+    Ok(response)
+}
+
+fn start_loader_job(state: &mut State, body: &[u8]) -> Response<Body> {
+    let request = match serde_json::from_slice::<Value>(body) {
+        Ok(request) if request["source"].is_string() => request,
+        _ => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "MissingParameterException",
+                "Missing required parameter: source",
+            )
+        },
+    };
+    let source = request["source"].as_str().unwrap_or_default().to_string();
+    let outcome = state
+        .start_outcomes
+        .pop_front()
+        .unwrap_or_else(|| StartOutcome::Accept(state.default_lifecycle.clone()));
+    match outcome {
+        StartOutcome::Accept(lifecycle) => {
+            let job = LoaderJob::new(request, lifecycle);
+            let load_id = job.load_id.clone();
+            tracing::info!("Accepted loader job {} for {}", load_id, source);
+            state.jobs.push(job);
+            json_response(
+                StatusCode::OK,
+                json!({"status": "200 OK", "payload": {"loadId": load_id}}),
+            )
+        },
+        StartOutcome::QueueFull => {
+            error_response(
+                StatusCode::BAD_REQUEST,
+                "BadRequestException",
+                &format!(
+                    "Failed to start new load for the source {source}. Max load task queue size \
+                     limit breached. Limit is 64"
+                ),
+            )
+        },
+        StartOutcome::ConcurrentLimit => {
+            error_response(
+                StatusCode::BAD_REQUEST,
+                "BadRequestException",
+                &format!(
+                    "Failed to start new load for the source {source}. Max concurrent load limit \
+                     breached. Limit is 1"
+                ),
+            )
+        },
+    }
+}
+
+fn get_loader_job_status(
+    state: &mut State,
+    load_id: &str,
+    query: &HashMap<String, String>,
+) -> Response<Body> {
+    let errors = query
+        .get("errors")
+        .map(|errors| errors.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    let page = query
+        .get("page")
+        .and_then(|page| page.parse().ok())
+        .unwrap_or(1);
+    let errors_per_page = query
+        .get("errorsPerPage")
+        .and_then(|errors_per_page| errors_per_page.parse().ok())
+        .unwrap_or(DEFAULT_ERRORS_PER_PAGE);
+    if errors &&
+        state
+            .failing_page
+            .is_some_and(|failing_page| page >= failing_page)
+    {
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "InternalFailureException",
+            &format!("Failed to get page {page} of the errors of load {load_id}"),
+        );
+    }
+    match state.jobs.iter_mut().find(|job| job.load_id == load_id) {
+        Some(job) => {
+            json_response(
+                StatusCode::OK,
+                json!({"status": "200 OK", "payload": job.check(errors, page, errors_per_page)}),
+            )
+        },
+        None => load_id_not_found(load_id),
+    }
+}
+
+fn list_loader_jobs(state: &State, query: &HashMap<String, String>) -> Response<Body> {
+    let limit = query
+        .get("limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_LIST_LIMIT);
+    let load_ids: Vec<&str> = state
+        .jobs
+        .iter()
+        .rev()
+        .take(limit)
+        .map(|job| job.load_id.as_str())
+        .collect();
+    json_response(
+        StatusCode::OK,
+        json!({"status": "200 OK", "payload": {"loadIds": load_ids}}),
+    )
+}
+
+fn cancel_loader_job(state: &mut State, load_id: &str) -> Response<Body> {
+    match state.jobs.iter_mut().find(|job| job.load_id == load_id) {
+        Some(job) => {
+            job.cancelled = true;
+            json_response(StatusCode::OK, json!({"status": "200 OK"}))
+        },
+        None => load_id_not_found(load_id),
+    }
+}
+
+fn load_id_not_found(load_id: &str) -> Response<Body> {
+    error_response(
+        StatusCode::NOT_FOUND,
+        "BulkLoadIdNotFoundException",
+        &format!("The load with id = {load_id} does not exist or not active"),
+    )
+}
+
+enum SparqlOperation {
+    Query(String),
+    Update(String),
+}
+
+/// The SPARQL query or update is either posted directly
+/// (`application/sparql-query` or `application/sparql-update`) or as the
+/// `query` or `update` parameter of a form, see
+/// https://www.w3.org/TR/sparql11-protocol/#query-operation and
+/// https://www.w3.org/TR/sparql11-protocol/#update-operation
+fn sparql_operation(body: &[u8], form_encoded: bool) -> SparqlOperation {
+    if form_encoded {
+        if let Ok(mut form) = serde_urlencoded::from_bytes::<HashMap<String, String>>(body) {
+            if let Some(query) = form.remove("query") {
+                return SparqlOperation::Query(query);
+            }
+            if let Some(update) = form.remove("update") {
+                return SparqlOperation::Update(update);
+            }
+        }
+    }
+    let sparql = String::from_utf8_lossy(body).to_string();
+    if is_query(sparql.as_str()) {
+        SparqlOperation::Query(sparql)
+    } else {
+        SparqlOperation::Update(sparql)
+    }
+}
+
+/// Whether the given SPARQL (posted directly) is a query rather than an
+/// update, judging by its first keyword after the prologue
+fn is_query(sparql: &str) -> bool {
+    first_keyword(sparql)
+        .map(|keyword| ["ASK", "SELECT", "CONSTRUCT", "DESCRIBE"].contains(&keyword.as_str()))
+        .unwrap_or(false)
+}
+
+/// The first keyword of the given SPARQL after the prologue, in upper case
+fn first_keyword(sparql: &str) -> Option<String> {
+    sparql
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .find(|line| {
+            let keyword = line.split_whitespace().next().unwrap_or_default();
+            !keyword.eq_ignore_ascii_case("PREFIX") && !keyword.eq_ignore_ascii_case("BASE")
+        })
+        .and_then(|line| line.split(|c: char| !c.is_ascii_alphabetic()).next())
+        .map(str::to_ascii_uppercase)
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// Neptune returns errors as a JSON object with a `code`, a `requestId` and
+/// a `detailedMessage`, the error type is also passed in the
+/// `x-amzn-errortype` header which is what the AWS SDK looks at first.
+fn error_response(status: StatusCode, code: &str, message: &str) -> Response<Body> {
+    let body = json!({
+        "code": code,
+        "requestId": format!("{:032x}", rand::random::<u128>()),
+        "message": message,
+        "detailedMessage": message,
+    });
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .header("x-amzn-errortype", code)
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
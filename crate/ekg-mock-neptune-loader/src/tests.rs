@@ -0,0 +1,203 @@
+#![cfg(test)]
+
+use {
+    crate::{JobLifecycle, MockLoader, MockLoaderHandle, StartOutcome},
+    aws_sdk_neptunedata::{
+        config::{BehaviorVersion, Credentials, Region},
+        types::{Format, S3BucketRegion},
+    },
+    ekg_aws_util::{
+        lambda::{LambdaDetailStatus, LambdaResponse},
+        neptune::LoaderJobStatus,
+    },
+};
+
+fn neptunedata_client(handle: &MockLoaderHandle) -> aws_sdk_neptunedata::Client {
+    let config = aws_sdk_neptunedata::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("antartica-01"))
+        .credentials_provider(Credentials::new(
+            "AKIDTEST", "SECRET", None, None, "test",
+        ))
+        .endpoint_url(handle.loader_endpoint().strip_suffix("/loader").unwrap())
+        .build();
+    aws_sdk_neptunedata::Client::from_conf(config)
+}
+
+async fn start_loader_job(
+    client: &aws_sdk_neptunedata::Client,
+) -> Result<
+    aws_sdk_neptunedata::operation::start_loader_job::StartLoaderJobOutput,
+    aws_sdk_neptunedata::error::SdkError<
+        aws_sdk_neptunedata::operation::start_loader_job::StartLoaderJobError,
+        aws_smithy_runtime_api::http::Response,
+    >,
+> {
+    client
+        .start_loader_job()
+        .source("s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl")
+        .format(Format::Turtle)
+        .iam_role_arn("arn:aws:iam::123456789012:role/ekgf-dt-dev-neptune-load")
+        .s3_bucket_region(S3BucketRegion::from("eu-west-2"))
+        .send()
+        .await
+}
+
+async fn loader_job_status(
+    client: &aws_sdk_neptunedata::Client,
+    load_id: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let output = client
+        .get_loader_job_status()
+        .load_id(load_id)
+        .errors(true)
+        .send()
+        .await?;
+    let status = output
+        .payload()
+        .as_object()
+        .and_then(|payload| payload.get("overallStatus"))
+        .and_then(|overall_status| overall_status.as_object())
+        .and_then(|overall_status| overall_status.get("status"))
+        .and_then(|status| status.as_string())
+        .ok_or("missing overallStatus.status")?;
+    Ok(status.to_string())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_job_lifecycle() -> Result<(), Box<dyn std::error::Error>> {
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader.serve(([127, 0, 0, 1], 0).into())?;
+    let client = neptunedata_client(&handle);
+
+    let output = start_loader_job(&client).await?;
+    let load_id = output.payload.get("loadId").ok_or("missing loadId")?;
+
+    assert_eq!(
+        loader_job_status(&client, load_id).await?,
+        "LOAD_IN_QUEUE"
+    );
+    assert_eq!(
+        loader_job_status(&client, load_id).await?,
+        "LOAD_IN_PROGRESS"
+    );
+    assert_eq!(
+        loader_job_status(&client, load_id).await?,
+        "LOAD_COMPLETED"
+    );
+    assert_eq!(
+        loader_job_status(&client, load_id).await?,
+        "LOAD_COMPLETED"
+    );
+    assert_eq!(mock_loader.jobs().len(), 1);
+
+    handle.shutdown().await?;
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_failed_job_with_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let mock_loader = MockLoader::new();
+    mock_loader.push_start_outcome(StartOutcome::Accept(
+        JobLifecycle::new(["LOAD_FAILED"]).with_error(
+            "PARSING_ERROR",
+            "Expected '.', found '<'",
+            "s3://ekgf-dt-dev-metadata/ontology/cdmc-data-use.ttl",
+            12,
+        ),
+    ));
+    let handle = mock_loader.serve(([127, 0, 0, 1], 0).into())?;
+    let client = neptunedata_client(&handle);
+
+    let output = start_loader_job(&client).await?;
+    let load_id = output.payload.get("loadId").ok_or("missing loadId")?;
+
+    let output = client
+        .get_loader_job_status()
+        .load_id(load_id)
+        .errors(true)
+        .send()
+        .await?;
+    let status = LoaderJobStatus::try_from(output.payload())?;
+    assert!(matches!(
+        status.detail_status(),
+        LambdaDetailStatus::LoaderJobFailed
+    ));
+    assert_eq!(status.overall_status.parsing_errors, 1);
+    assert_eq!(status.failed_feeds.len(), 1);
+    let errors = status.errors.ok_or("no errors")?;
+    assert_eq!(errors.load_id.as_str(), load_id);
+    assert_eq!(errors.error_logs.len(), 1);
+    assert_eq!(errors.error_logs[0].error_code, "PARSING_ERROR");
+    assert_eq!(errors.error_logs[0].record_num, 12);
+
+    handle.shutdown().await?;
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_queue_full_and_concurrent_limit() -> Result<(), Box<dyn std::error::Error>> {
+    let mock_loader = MockLoader::new();
+    mock_loader.push_start_outcome(StartOutcome::QueueFull);
+    mock_loader.push_start_outcome(StartOutcome::ConcurrentLimit);
+    let handle = mock_loader.serve(([127, 0, 0, 1], 0).into())?;
+    let client = neptunedata_client(&handle);
+
+    let response: LambdaResponse = start_loader_job(&client)
+        .await
+        .expect_err("queue should be full")
+        .into();
+    assert_eq!(response.status_code, 400);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::MaxLoadTaskQueueSizeLimitBreached
+    ));
+
+    let response: LambdaResponse = start_loader_job(&client)
+        .await
+        .expect_err("concurrent load limit should be breached")
+        .into();
+    assert_eq!(response.status_code, 400);
+    assert!(matches!(
+        response.detail_status,
+        LambdaDetailStatus::MaxConcurrentLoadLimitBreached
+    ));
+
+    // The script is exhausted, so the next one is accepted again
+    assert!(start_loader_job(&client).await.is_ok());
+
+    handle.shutdown().await?;
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_list_and_cancel() -> Result<(), Box<dyn std::error::Error>> {
+    let mock_loader = MockLoader::new();
+    let handle = mock_loader.serve(([127, 0, 0, 1], 0).into())?;
+    let client = neptunedata_client(&handle);
+
+    let first = start_loader_job(&client).await?;
+    let second = start_loader_job(&client).await?;
+
+    let output = client.list_loader_jobs().send().await?;
+    let load_ids = output.payload().load_ids();
+    assert_eq!(load_ids.len(), 2);
+    assert_eq!(Some(&load_ids[0]), second.payload.get("loadId"));
+
+    let load_id = first.payload.get("loadId").ok_or("missing loadId")?;
+    client.cancel_loader_job().load_id(load_id).send().await?;
+    assert_eq!(
+        loader_job_status(&client, load_id).await?,
+        "LOAD_CANCELLED_BY_USER"
+    );
+
+    assert!(client
+        .cancel_loader_job()
+        .load_id("does-not-exist")
+        .send()
+        .await
+        .is_err());
+
+    handle.shutdown().await?;
+    Ok(())
+}
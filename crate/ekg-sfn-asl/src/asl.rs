@@ -0,0 +1,202 @@
+//! The subset of the Amazon States Language that our state machines use, see
+//! https://states-language.net/spec.html
+use {
+    serde::{
+        de::{MapAccess, Visitor},
+        ser::SerializeMap,
+        Deserialize,
+        Deserializer,
+        Serialize,
+        Serializer,
+    },
+    serde_json::Value,
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct StateMachine {
+    pub comment:  String,
+    pub start_at: String,
+    pub states:   States,
+}
+
+impl StateMachine {
+    pub fn state(&self, name: &str) -> Option<&State> { self.states.get(name) }
+
+    /// The JSON definition as it is passed to Step Functions
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self).map(|json| json + "\n")
+    }
+}
+
+/// The states of a state machine by name, in the order in which they were
+/// added (which is also the order in which they are written to JSON).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct States(Vec<(String, State)>);
+
+impl States {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn add(mut self, name: &str, state: State) -> Self {
+        self.0.push((name.to_string(), state));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&State> {
+        self.0
+            .iter()
+            .find(|(state_name, _)| state_name == name)
+            .map(|(_, state)| state)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &State)> {
+        self.0.iter().map(|(name, state)| (name.as_str(), state))
+    }
+}
+
+impl Serialize for States {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, state) in self.0.iter() {
+            map.serialize_entry(name, state)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for States {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StatesVisitor;
+
+        impl<'de> Visitor<'de> for StatesVisitor {
+            type Value = States;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a map of state names to states")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<States, A::Error> {
+                let mut states = Vec::new();
+                while let Some((name, state)) = access.next_entry::<String, State>()? {
+                    states.push((name, state));
+                }
+                Ok(States(states))
+            }
+        }
+
+        deserializer.deserialize_map(StatesVisitor)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "Type")]
+pub enum State {
+    Task(Task),
+    Choice(Choice),
+    Wait(Wait),
+    Succeed(Terminal),
+    Fail(Terminal),
+}
+
+/// Invoke a lambda function with (part of) the state as its input and put its
+/// output back into the state at the result path
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Task {
+    pub comment:         String,
+    pub resource:        String,
+    pub input_path:      String,
+    pub timeout_seconds: u32,
+    pub result_path:     String,
+    pub next:            String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Choice {
+    pub comment: String,
+    pub choices: Vec<ChoiceRule>,
+    pub default: String,
+}
+
+impl Choice {
+    /// The name of the next state for the given input, the first rule that
+    /// matches wins
+    pub fn next(&self, input: &Value) -> &str {
+        self.choices
+            .iter()
+            .find(|rule| rule.condition.matches(input))
+            .map(|rule| rule.next.as_str())
+            .unwrap_or(self.default.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChoiceRule {
+    #[serde(flatten)]
+    pub condition: Condition,
+    pub next:      String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum Condition {
+    StringEquals {
+        #[serde(rename = "Variable")]
+        variable:      String,
+        #[serde(rename = "StringEquals")]
+        string_equals: String,
+    },
+    NumericEquals {
+        #[serde(rename = "Variable")]
+        variable:       String,
+        #[serde(rename = "NumericEquals")]
+        numeric_equals: i64,
+    },
+    Or {
+        #[serde(rename = "Or")]
+        or: Vec<Condition>,
+    },
+}
+
+impl Condition {
+    pub fn matches(&self, input: &Value) -> bool {
+        match self {
+            Self::StringEquals { variable, string_equals } => {
+                select(input, variable).and_then(Value::as_str) == Some(string_equals.as_str())
+            },
+            Self::NumericEquals { variable, numeric_equals } => {
+                select(input, variable).and_then(Value::as_i64) == Some(*numeric_equals)
+            },
+            Self::Or { or } => or.iter().any(|condition| condition.matches(input)),
+        }
+    }
+}
+
+/// Wait the number of seconds found at the given path of the state
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Wait {
+    pub comment:      String,
+    pub seconds_path: String,
+    pub next:         String,
+}
+
+/// A `Succeed` or `Fail` state
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Terminal {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Select the value at the given (simple, dotted) reference path such as
+/// `$.CheckOutput.detailStatus`, see
+/// https://states-language.net/spec.html#ref-paths
+pub fn select<'a>(input: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.strip_prefix('$')?;
+    path.split('.')
+        .filter(|field| !field.is_empty())
+        .try_fold(input, |value, field| value.get(field))
+}
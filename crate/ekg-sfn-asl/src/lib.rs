@@ -0,0 +1,28 @@
+//! Generates the Amazon States Language (ASL) definition of the RDF load state
+//! machine from typed states, so that the detail statuses that its Choice
+//! states route on always match the [`LambdaDetailStatus`] enum of the lambda
+//! functions. Run `cargo run -p ekg-sfn-asl -- aws-sfn-state-machine.asl.json`
+//! after changing that enum, Terraform reads the generated file with
+//! `templatefile`.
+//!
+//! [`LambdaDetailStatus`]: ekg_aws_util::lambda::LambdaDetailStatus
+pub use {
+    asl::{
+        select,
+        Choice,
+        ChoiceRule,
+        Condition,
+        State,
+        StateMachine,
+        States,
+        Task,
+        Terminal,
+        Wait,
+    },
+    rdf_load::state_machine,
+};
+
+mod asl;
+pub mod rdf_load;
+#[cfg(test)]
+mod tests;
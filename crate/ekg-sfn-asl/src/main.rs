@@ -0,0 +1,10 @@
+/// Write the ASL definition of the RDF load state machine to the file given as
+/// the first argument, or to stdout if there is none.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let json = ekg_sfn_asl::state_machine()?.to_json()?;
+    match std::env::args().nth(1) {
+        Some(path) => std::fs::write(path, json)?,
+        None => print!("{}", json),
+    }
+    Ok(())
+}
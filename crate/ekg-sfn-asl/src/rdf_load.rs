@@ -0,0 +1,255 @@
+//! The RDF load state machine: instruct the Neptune bulk loader, poll it until
+//! the loader job has finished and then merge the loaded graph.
+//!
+//! The Choice states route on the `detailStatus` of the output of the lambda
+//! functions. Their branches are derived from [`LambdaDetailStatus::ALL`] and
+//! its retryability so that they can never get out of sync with the enum.
+use {
+    crate::asl::{
+        Choice,
+        ChoiceRule,
+        Condition,
+        State,
+        StateMachine,
+        States,
+        Task,
+        Terminal,
+        Wait,
+    },
+    ekg_aws_util::lambda::{LambdaDetailStatus, Retryability},
+};
+
+pub const INSTRUCT_NEPTUNE_TO_LOAD: &str = "InstructNeptuneToLoad";
+pub const CHECK_IF_INSTRUCTION_GIVEN: &str = "CheckIfInstructionGiven";
+pub const RETRY_LOAD_INSTRUCTION: &str = "RetryLoadInstruction";
+pub const CHECK_LOADER_JOB_STATUS: &str = "CheckLoaderJobStatus";
+pub const CHECK_IF_LOADER_JOB_FINISHED: &str = "CheckIfLoaderJobFinished";
+pub const RETRY_CHECK: &str = "RetryCheck";
+pub const MERGE_INTO_TARGET_GRAPH: &str = "MergeIntoTargetGraph";
+pub const CHECK_IF_MERGED: &str = "CheckIfMerged";
+pub const LOADER_JOB_COMPLETED: &str = "LoaderJobCompleted";
+pub const MERGE_FAILED: &str = "MergeFailed";
+pub const LOAD_INSTRUCTION_FAILED: &str = "LoadInstructionFailed";
+pub const LOADER_JOB_FAILED: &str = "LoaderJobFailed";
+
+/// The Terraform `templatefile` variables that the generated definition
+/// refers to
+pub const TEMPLATE_VAR_LAMBDA_INVOKE_NAME: &str = "${lambda_invoke_name}";
+pub const TEMPLATE_VAR_LAMBDA_LOAD_ARN: &str = "${lambda_load_arn}";
+pub const TEMPLATE_VAR_LAMBDA_CHECK_ARN: &str = "${lambda_check_arn}";
+pub const TEMPLATE_VAR_LAMBDA_MERGE_ARN: &str = "${lambda_merge_arn}";
+
+/// Returns the name of the next state for a given detail status
+pub type Route = fn(&LambdaDetailStatus) -> &'static str;
+
+/// The Choice states of the RDF load state machine, each with the path of the
+/// detail status that it routes on and the function that routes it
+pub const CHOICES: [(&str, &str, Route); 3] = [
+    (
+        CHECK_IF_INSTRUCTION_GIVEN,
+        "$.LoadOutput.detailStatus",
+        route_load_output,
+    ),
+    (
+        CHECK_IF_LOADER_JOB_FINISHED,
+        "$.CheckOutput.detailStatus",
+        route_check_output,
+    ),
+    (
+        CHECK_IF_MERGED,
+        "$.MergeOutput.detailStatus",
+        route_merge_output,
+    ),
+];
+
+/// Where to go after the load lambda function: check the loader job once the
+/// loader accepted it, stop right away if this version of the file was loaded
+/// already, try again after a transient failure (such as a full loader queue),
+/// give up otherwise.
+pub fn route_load_output(status: &LambdaDetailStatus) -> &'static str {
+    match status {
+        LambdaDetailStatus::LoaderJobInQueue => CHECK_LOADER_JOB_STATUS,
+        LambdaDetailStatus::AlreadyLoaded => LOADER_JOB_COMPLETED,
+        status if status.retryability() == Retryability::Transient => RETRY_LOAD_INSTRUCTION,
+        _ => LOAD_INSTRUCTION_FAILED,
+    }
+}
+
+/// Where to go after the check lambda function: merge once the loader job
+/// completed, check again while it is still running or after a transient
+/// failure, give up otherwise.
+pub fn route_check_output(status: &LambdaDetailStatus) -> &'static str {
+    match status {
+        LambdaDetailStatus::LoaderJobCompleted => MERGE_INTO_TARGET_GRAPH,
+        status if status.is_retryable() => RETRY_CHECK,
+        status if status.retryability() == Retryability::Transient => RETRY_CHECK,
+        _ => LOADER_JOB_FAILED,
+    }
+}
+
+pub fn route_merge_output(status: &LambdaDetailStatus) -> &'static str {
+    match status {
+        LambdaDetailStatus::GraphMerged => LOADER_JOB_COMPLETED,
+        _ => MERGE_FAILED,
+    }
+}
+
+pub fn state_machine() -> Result<StateMachine, serde_json::Error> {
+    Ok(StateMachine {
+        comment:  format!(
+            "The RDF Loader State Machine, invoked by the lambda function {}",
+            TEMPLATE_VAR_LAMBDA_INVOKE_NAME
+        ),
+        start_at: INSTRUCT_NEPTUNE_TO_LOAD.to_string(),
+        states:   States::new()
+            .add(
+                INSTRUCT_NEPTUNE_TO_LOAD,
+                State::Task(Task {
+                    comment:         "Instruct the Neptune bulk loader to load the given S3 file"
+                        .to_string(),
+                    resource:        TEMPLATE_VAR_LAMBDA_LOAD_ARN.to_string(),
+                    input_path:      "$".to_string(),
+                    timeout_seconds: 300,
+                    result_path:     "$.LoadOutput".to_string(),
+                    next:            CHECK_IF_INSTRUCTION_GIVEN.to_string(),
+                }),
+            )
+            .add(
+                CHECK_IF_INSTRUCTION_GIVEN,
+                State::Choice(choice(
+                    "Check if the Neptune bulk loader was instructed successfully to load the \
+                     given S3 file",
+                    CHOICES[0].1,
+                    CHOICES[0].2,
+                    LOAD_INSTRUCTION_FAILED,
+                )?),
+            )
+            .add(
+                RETRY_LOAD_INSTRUCTION,
+                State::Wait(Wait {
+                    comment:      "Wait a number of seconds, as suggested by the load lambda \
+                                   function, and then retry the instruction to the Neptune bulk \
+                                   loader"
+                        .to_string(),
+                    seconds_path: "$.LoadOutput.suggestedRetrySeconds".to_string(),
+                    next:         INSTRUCT_NEPTUNE_TO_LOAD.to_string(),
+                }),
+            )
+            .add(
+                CHECK_LOADER_JOB_STATUS,
+                State::Task(Task {
+                    comment:         "Check if the Neptune bulk loader has finished loading the \
+                                      given S3 file"
+                        .to_string(),
+                    resource:        TEMPLATE_VAR_LAMBDA_CHECK_ARN.to_string(),
+                    input_path:      "$".to_string(),
+                    // As long as the check lambda function may run, the check
+                    // that finds the job completed also swaps the staging graph
+                    // into the target graph and registers the load errors
+                    timeout_seconds: 60,
+                    result_path:     "$.CheckOutput".to_string(),
+                    next:            CHECK_IF_LOADER_JOB_FINISHED.to_string(),
+                }),
+            )
+            .add(
+                CHECK_IF_LOADER_JOB_FINISHED,
+                State::Choice(choice(
+                    "Check if the Neptune bulk loader has finished the job successfully, failed \
+                     or is still running",
+                    CHOICES[1].1,
+                    CHOICES[1].2,
+                    LOADER_JOB_FAILED,
+                )?),
+            )
+            .add(
+                RETRY_CHECK,
+                State::Wait(Wait {
+                    comment:      "Wait a number of seconds, as suggested by the check lambda \
+                                   function, and then retry to get the latest status of the \
+                                   Neptune bulk loader"
+                        .to_string(),
+                    seconds_path: "$.CheckOutput.suggestedRetrySeconds".to_string(),
+                    next:         CHECK_LOADER_JOB_STATUS.to_string(),
+                }),
+            )
+            .add(
+                MERGE_INTO_TARGET_GRAPH,
+                State::Task(Task {
+                    comment:         "Merge the named graph of the loaded S3 file into the target \
+                                      graph and record its provenance"
+                        .to_string(),
+                    resource:        TEMPLATE_VAR_LAMBDA_MERGE_ARN.to_string(),
+                    input_path:      "$".to_string(),
+                    timeout_seconds: 60,
+                    result_path:     "$.MergeOutput".to_string(),
+                    next:            CHECK_IF_MERGED.to_string(),
+                }),
+            )
+            .add(
+                CHECK_IF_MERGED,
+                State::Choice(choice(
+                    "Check if the named graph of the loaded S3 file was merged successfully",
+                    CHOICES[2].1,
+                    CHOICES[2].2,
+                    MERGE_FAILED,
+                )?),
+            )
+            .add(
+                LOADER_JOB_COMPLETED,
+                State::Succeed(Terminal::default()),
+            )
+            .add(MERGE_FAILED, State::Fail(Terminal::default()))
+            .add(
+                LOAD_INSTRUCTION_FAILED,
+                State::Fail(Terminal::default()),
+            )
+            .add(
+                LOADER_JOB_FAILED,
+                State::Fail(Terminal::default()),
+            ),
+    })
+}
+
+/// A Choice state with one rule per next state that lists every detail status
+/// routed to it, the default only catches values that the enum doesn't know.
+fn choice(
+    comment: &str,
+    variable: &str,
+    route: Route,
+    default: &str,
+) -> Result<Choice, serde_json::Error> {
+    let mut branches: Vec<(&'static str, Vec<Condition>)> = Vec::new();
+    for status in LambdaDetailStatus::ALL.iter() {
+        let condition = Condition::StringEquals {
+            variable:      variable.to_string(),
+            string_equals: detail_status_name(status)?,
+        };
+        let next = route(status);
+        match branches.iter_mut().find(|(branch, _)| *branch == next) {
+            Some((_, conditions)) => conditions.push(condition),
+            None => branches.push((next, vec![condition])),
+        }
+    }
+    let choices = branches
+        .into_iter()
+        .map(|(next, conditions)| {
+            ChoiceRule {
+                condition: Condition::Or { or: conditions },
+                next:      next.to_string(),
+            }
+        })
+        .collect();
+    Ok(Choice {
+        comment: comment.to_string(),
+        choices,
+        default: default.to_string(),
+    })
+}
+
+/// The name of the given status as the lambda functions serialize it
+pub fn detail_status_name(status: &LambdaDetailStatus) -> Result<String, serde_json::Error> {
+    match serde_json::to_value(status)? {
+        serde_json::Value::String(name) => Ok(name),
+        value => Ok(value.to_string()),
+    }
+}
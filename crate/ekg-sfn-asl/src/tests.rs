@@ -0,0 +1,115 @@
+#![cfg(test)]
+
+use {
+    crate::{
+        rdf_load::{detail_status_name, CHOICES},
+        state_machine,
+        State,
+        StateMachine,
+    },
+    ekg_aws_util::lambda::LambdaDetailStatus,
+    serde_json::{json, Value},
+};
+
+/// The state with the given value at the given path such as
+/// `$.CheckOutput.detailStatus`
+fn input_with(path: &str, value: &str) -> Value {
+    path.trim_start_matches("$.").rsplit('.').fold(
+        json!(value),
+        |value, field| json!({ field: value }),
+    )
+}
+
+#[test_log::test]
+fn test_generated_definition_is_up_to_date() {
+    let generated = state_machine().unwrap().to_json().unwrap();
+    assert_eq!(
+        generated,
+        include_str!("../../../aws-sfn-state-machine.asl.json"),
+        "Run `cargo run -p ekg-sfn-asl -- aws-sfn-state-machine.asl.json` in the root of the \
+         repository"
+    );
+    let parsed: StateMachine = serde_json::from_str(generated.as_str()).unwrap();
+    assert_eq!(parsed, state_machine().unwrap());
+}
+
+#[test_log::test]
+fn test_every_detail_status_is_routed() {
+    let state_machine = state_machine().unwrap();
+    for (choice_name, variable, route) in CHOICES.iter() {
+        let Some(State::Choice(choice)) = state_machine.state(choice_name) else {
+            panic!("{} is not a Choice state", choice_name);
+        };
+        for status in LambdaDetailStatus::ALL.iter() {
+            let name = detail_status_name(status).unwrap();
+            let input = input_with(variable, name.as_str());
+            let matching_rules = choice
+                .choices
+                .iter()
+                .filter(|rule| rule.condition.matches(&input))
+                .count();
+            assert_eq!(
+                matching_rules, 1,
+                "{} is not routed exactly once by {}",
+                name, choice_name
+            );
+            assert_eq!(choice.next(&input), route(status));
+        }
+    }
+}
+
+#[test_log::test]
+fn test_retryable_statuses_are_retried() {
+    let state_machine = state_machine().unwrap();
+    let Some(State::Choice(choice)) = state_machine.state("CheckIfLoaderJobFinished") else {
+        panic!("CheckIfLoaderJobFinished is not a Choice state");
+    };
+    for status in LambdaDetailStatus::ALL
+        .iter()
+        .filter(|status| status.is_retryable())
+    {
+        let input = input_with(
+            "$.CheckOutput.detailStatus",
+            detail_status_name(status).unwrap().as_str(),
+        );
+        assert_eq!(choice.next(&input), "RetryCheck");
+    }
+    let input = input_with(
+        "$.CheckOutput.detailStatus",
+        "RetryBudgetExhausted",
+    );
+    assert_eq!(choice.next(&input), "LoaderJobFailed");
+    let input = input_with("$.CheckOutput.detailStatus", "SomethingNew");
+    assert_eq!(choice.next(&input), "LoaderJobFailed");
+}
+
+#[test_log::test]
+fn test_every_next_state_exists() {
+    let state_machine = state_machine().unwrap();
+    assert!(state_machine
+        .state(state_machine.start_at.as_str())
+        .is_some());
+    for (name, state) in state_machine.states.iter() {
+        let next_states: Vec<&str> = match state {
+            State::Task(task) => vec![task.next.as_str()],
+            State::Wait(wait) => vec![wait.next.as_str()],
+            State::Choice(choice) => {
+                choice
+                    .choices
+                    .iter()
+                    .map(|rule| rule.next.as_str())
+                    .chain([choice.default.as_str()])
+                    .collect()
+            },
+            State::Succeed(_) | State::Fail(_) => vec![],
+        };
+        for next in next_states {
+            assert!(
+                state_machine.state(next).is_some(),
+                "{} refers to unknown state {}",
+                name,
+                next
+            );
+        }
+    }
+}
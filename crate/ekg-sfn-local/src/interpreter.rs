@@ -0,0 +1,216 @@
+use {
+    ekg_error::Error,
+    ekg_sfn_asl::{select, State, StateMachine},
+    lambda_runtime::Error as LambdaError,
+    serde_json::{Map, Value},
+    std::{collections::HashMap, future::Future, pin::Pin},
+};
+
+/// The default maximum number of state transitions of one execution, a
+/// safeguard against a state machine that loops forever
+pub const DEFAULT_MAX_TRANSITIONS: usize = 1_000;
+
+pub type TaskFuture = Pin<Box<dyn Future<Output = Result<Value, LambdaError>> + Send>>;
+
+/// The in-process replacement of a lambda function, it gets the (selected)
+/// input of a Task state and returns the output of the lambda function
+pub type TaskHandler = Box<dyn Fn(Value) -> TaskFuture + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    Succeeded,
+    Failed,
+}
+
+/// The outcome of one execution of a [`LocalStateMachine`]
+#[derive(Clone, Debug)]
+pub struct Execution {
+    pub status:          ExecutionStatus,
+    /// The name of the `Succeed` or `Fail` state (or of the Task state whose
+    /// handler returned an error) at which the execution ended
+    pub final_state:     String,
+    /// The state (the input of the execution plus all task results) at the end
+    pub output:          Value,
+    /// The error returned by a task handler, if that is what ended the
+    /// execution
+    pub error:           Option<String>,
+    /// The names of all states that were entered, in order
+    pub history:         Vec<String>,
+    /// The virtual clock: the total number of seconds that the Wait states
+    /// would have waited (they don't actually wait)
+    pub elapsed_seconds: u64,
+}
+
+impl Execution {
+    pub fn succeeded(&self) -> bool { self.status == ExecutionStatus::Succeeded }
+
+    /// The number of times the given state was entered
+    pub fn visits(&self, state_name: &str) -> usize {
+        self.history
+            .iter()
+            .filter(|name| name.as_str() == state_name)
+            .count()
+    }
+}
+
+/// Interprets the subset of the Amazon States Language that the RDF load state
+/// machine uses (Task, Choice, Wait, Succeed and Fail states with InputPath,
+/// ResultPath and SecondsPath), dispatching Task states to in-process
+/// handlers by their `Resource`.
+pub struct LocalStateMachine {
+    definition:      StateMachine,
+    tasks:           HashMap<String, TaskHandler>,
+    max_transitions: usize,
+}
+
+impl LocalStateMachine {
+    pub fn new(definition: StateMachine) -> Self {
+        Self {
+            definition,
+            tasks: HashMap::new(),
+            max_transitions: DEFAULT_MAX_TRANSITIONS,
+        }
+    }
+
+    /// Handle the Task states with the given `Resource` (such as
+    /// `${lambda_load_arn}`) with the given function
+    pub fn with_task<F, Fut>(mut self, resource: &str, handler: F) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, LambdaError>> + Send + 'static,
+    {
+        self.tasks.insert(
+            resource.to_string(),
+            Box::new(move |input| Box::pin(handler(input))),
+        );
+        self
+    }
+
+    pub fn with_max_transitions(self, max_transitions: usize) -> Self {
+        Self { max_transitions, ..self }
+    }
+
+    pub fn definition(&self) -> &StateMachine { &self.definition }
+
+    /// Run the state machine with the given input until it reaches a
+    /// `Succeed` or `Fail` state, or until a task handler returns an error
+    /// (there are no Retry or Catch clauses so that fails the execution).
+    pub async fn execute(&self, input: Value) -> Result<Execution, Error> {
+        let mut state = input;
+        let mut state_name = self.definition.start_at.clone();
+        let mut history = Vec::new();
+        let mut elapsed_seconds = 0_u64;
+
+        loop {
+            if history.len() >= self.max_transitions {
+                return Err(Error::ServiceError(format!(
+                    "Execution did not finish within {} transitions, last state: {}",
+                    self.max_transitions, state_name
+                )));
+            }
+            history.push(state_name.clone());
+            let Some(definition) = self.definition.state(state_name.as_str()) else {
+                return Err(Error::ServiceError(format!(
+                    "Unknown state {}",
+                    state_name
+                )));
+            };
+            tracing::debug!("Entering state {}", state_name);
+            let next = match definition {
+                State::Task(task) => {
+                    let Some(handler) = self.tasks.get(task.resource.as_str()) else {
+                        return Err(Error::ServiceError(format!(
+                            "No handler for resource {} of task {}",
+                            task.resource, state_name
+                        )));
+                    };
+                    let task_input = select(&state, task.input_path.as_str())
+                        .cloned()
+                        .unwrap_or(Value::Null);
+                    match handler(task_input).await {
+                        Ok(result) => {
+                            set_path(&mut state, task.result_path.as_str(), result)?;
+                            task.next.clone()
+                        },
+                        Err(error) => {
+                            tracing::error!("Task {} failed: {}", state_name, error);
+                            return Ok(Execution {
+                                status: ExecutionStatus::Failed,
+                                final_state: state_name,
+                                output: state,
+                                error: Some(error.to_string()),
+                                history,
+                                elapsed_seconds,
+                            });
+                        },
+                    }
+                },
+                State::Choice(choice) => choice.next(&state).to_string(),
+                State::Wait(wait) => {
+                    let seconds = select(&state, wait.seconds_path.as_str())
+                        .and_then(Value::as_u64)
+                        .ok_or_else(|| {
+                            Error::ServiceError(format!(
+                                "No number of seconds at {} for Wait state {}",
+                                wait.seconds_path, state_name
+                            ))
+                        })?;
+                    elapsed_seconds += seconds;
+                    wait.next.clone()
+                },
+                State::Succeed(_) | State::Fail(_) => {
+                    return Ok(Execution {
+                        status: if matches!(definition, State::Succeed(_)) {
+                            ExecutionStatus::Succeeded
+                        } else {
+                            ExecutionStatus::Failed
+                        },
+                        final_state: state_name,
+                        output: state,
+                        error: None,
+                        history,
+                        elapsed_seconds,
+                    });
+                },
+            };
+            state_name = next;
+        }
+    }
+}
+
+/// Put the given value at the given (simple, dotted) reference path such as
+/// `$.LoadOutput`, creating objects along the way, `$` replaces the whole
+/// state.
+fn set_path(state: &mut Value, path: &str, value: Value) -> Result<(), Error> {
+    let Some(path) = path.strip_prefix('$') else {
+        return Err(Error::ServiceError(format!(
+            "Invalid ResultPath {}",
+            path
+        )));
+    };
+    let fields: Vec<&str> = path.split('.').filter(|field| !field.is_empty()).collect();
+    let Some((last, parents)) = fields.split_last() else {
+        *state = value;
+        return Ok(());
+    };
+    let mut target = state;
+    for field in parents {
+        target = object_mut(target)
+            .entry(*field)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    object_mut(target).insert(last.to_string(), value);
+    Ok(())
+}
+
+/// The given value as an object, replacing it with an empty object if it isn't
+/// one (like Step Functions does when a ResultPath goes through a non-object)
+fn object_mut(value: &mut Value) -> &mut Map<String, Value> {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    match value {
+        Value::Object(object) => object,
+        _ => unreachable!("just replaced by an object"),
+    }
+}
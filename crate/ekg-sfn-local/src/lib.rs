@@ -0,0 +1,23 @@
+//! Runs a Step Functions state machine in-process so that the whole
+//! invoke → load → check → merge pipeline can be tested with `cargo test`,
+//! against the mock Neptune loader (see the `ekg-mock-neptune-loader` crate)
+//! instead of AWS.
+//!
+//! Wait states don't actually wait, the seconds that they would have waited
+//! are added to a virtual clock, see [`Execution::elapsed_seconds`].
+pub use {
+    interpreter::{
+        Execution,
+        ExecutionStatus,
+        LocalStateMachine,
+        TaskFuture,
+        TaskHandler,
+        DEFAULT_MAX_TRANSITIONS,
+    },
+    rdf_load::RdfLoadClients,
+};
+
+mod interpreter;
+mod rdf_load;
+#[cfg(test)]
+mod tests;
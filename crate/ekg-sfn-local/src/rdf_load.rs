@@ -0,0 +1,60 @@
+use {
+    crate::LocalStateMachine,
+    ekg_error::Error,
+    ekg_identifier::EkgIdentifierContexts,
+    ekg_sfn_asl::rdf_load::{
+        TEMPLATE_VAR_LAMBDA_CHECK_ARN,
+        TEMPLATE_VAR_LAMBDA_LOAD_ARN,
+        TEMPLATE_VAR_LAMBDA_MERGE_ARN,
+    },
+};
+
+/// The clients of the three lambda functions of the RDF load state machine,
+/// typically all pointing to a mock loader and a local SPARQL store
+#[derive(Clone)]
+pub struct RdfLoadClients {
+    pub load:  ekg_lfn_load::Clients,
+    pub check: ekg_lfn_check::Clients,
+    pub merge: ekg_lfn_merge::Clients,
+}
+
+impl LocalStateMachine {
+    /// The generated RDF load state machine with its Task states dispatched
+    /// to the `handle_lambda_payload` functions of `ekg-lfn-load`,
+    /// `ekg-lfn-check` and `ekg-lfn-merge`
+    pub fn rdf_load(pipeline_id: &'static str, clients: RdfLoadClients) -> Result<Self, Error> {
+        let definition = ekg_sfn_asl::state_machine()?;
+        let RdfLoadClients { load, check, merge } = clients;
+        Ok(Self::new(definition)
+            .with_task(TEMPLATE_VAR_LAMBDA_LOAD_ARN, move |input| {
+                let clients = load.clone();
+                async move {
+                    let response =
+                        ekg_lfn_load::handle_lambda_payload(input, pipeline_id, clients).await?;
+                    Ok(serde_json::to_value(response)?)
+                }
+            })
+            .with_task(TEMPLATE_VAR_LAMBDA_CHECK_ARN, move |input| {
+                let clients = check.clone();
+                async move {
+                    let ekg_identifier_contexts = EkgIdentifierContexts::from_env()?;
+                    let response = ekg_lfn_check::handle_lambda_payload(
+                        input,
+                        &ekg_identifier_contexts,
+                        pipeline_id,
+                        clients,
+                    )
+                    .await?;
+                    Ok(serde_json::to_value(response)?)
+                }
+            })
+            .with_task(TEMPLATE_VAR_LAMBDA_MERGE_ARN, move |input| {
+                let clients = merge.clone();
+                async move {
+                    let response =
+                        ekg_lfn_merge::handle_lambda_payload(input, pipeline_id, clients).await?;
+                    Ok(serde_json::to_value(response)?)
+                }
+            }))
+    }
+}
@@ -0,0 +1,165 @@
+#![cfg(test)]
+
+use {
+    crate::{LocalStateMachine, RdfLoadClients},
+    ekg_mock_neptune_loader::{JobLifecycle, MockLoader, MockLoaderHandle, StartOutcome},
+    ekg_sfn_asl::rdf_load::{
+        CHECK_LOADER_JOB_STATUS,
+        INSTRUCT_NEPTUNE_TO_LOAD,
+        LOADER_JOB_COMPLETED,
+        LOADER_JOB_FAILED,
+        MERGE_INTO_TARGET_GRAPH,
+        RETRY_LOAD_INSTRUCTION,
+    },
+};
+
+/// The tests point the lambda functions to their own mock loader via
+/// environment variables, so they must not run at the same time
+static ENV_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+async fn rdf_load_state_machine(
+    handle: &MockLoaderHandle,
+) -> Result<LocalStateMachine, ekg_error::Error> {
+    ekg_identifier::EkgIdentifierContexts::default_test();
+    std::env::set_var(
+        "EKG_SPARQL_LOADER_ENDPOINT",
+        handle.loader_endpoint(),
+    );
+    std::env::set_var(
+        "EKG_SPARQL_QUERY_ENDPOINT",
+        handle.sparql_endpoint(),
+    );
+    std::env::set_var(
+        "EKG_SPARQL_UPDATE_ENDPOINT",
+        handle.sparql_endpoint(),
+    );
+    std::env::set_var(
+        "AWS_NEPTUNE_LOAD_IAM_ROLE_ARN",
+        "arn:aws:iam::12345:role/ekgf-dt-dev-neptune-load",
+    );
+    std::env::set_var(
+        "EKG_MERGE_TARGET_GRAPH",
+        "https://placeholder.kg/graph/test",
+    );
+    // No jitter so that the waits on the virtual clock are predictable
+    std::env::set_var("EKG_RETRY_JITTER", "none");
+    std::env::set_var("AWS_REGION", "antartica-01");
+    std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDTEST");
+    std::env::set_var("AWS_SECRET_ACCESS_KEY", "SECRET");
+    let aws_config = aws_config::load_from_env().await;
+    let aws_neptunedata_client = ekg_aws_util::neptune::get_neptunedata_client(&aws_config)?;
+    let sparql_client = ekg_sparql::SPARQLClient::from_env().await?;
+    LocalStateMachine::rdf_load("test", RdfLoadClients {
+        load:  ekg_lfn_load::Clients {
+            aws_neptunedata_client: aws_neptunedata_client.clone(),
+            sparql_client:          sparql_client.clone(),
+            aws_s3_client:          aws_sdk_s3::Client::new(&aws_config),
+        },
+        check: ekg_lfn_check::Clients {
+            aws_neptunedata_client,
+            sparql_client: sparql_client.clone(),
+        },
+        merge: ekg_lfn_merge::Clients { sparql_client },
+    })
+}
+
+fn input() -> serde_json::Value {
+    serde_json::from_str(include_str!("../../ekg-lfn-load/event.json")).unwrap()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_rdf_load_completed() -> Result<(), ekg_error::Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    mock_loader.push_start_outcome(StartOutcome::Accept(JobLifecycle::completed()));
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+    let state_machine = rdf_load_state_machine(&handle).await?;
+
+    let execution = state_machine.execute(input()).await?;
+    tracing::info!("history: {:?}", execution.history);
+
+    assert!(execution.succeeded(), "{:#?}", execution);
+    assert_eq!(execution.final_state, LOADER_JOB_COMPLETED);
+    // LOAD_IN_QUEUE, LOAD_IN_PROGRESS and then LOAD_COMPLETED
+    assert_eq!(execution.visits(CHECK_LOADER_JOB_STATUS), 3);
+    assert_eq!(execution.visits(MERGE_INTO_TARGET_GRAPH), 1);
+    // The two retries of the check were waited for on the virtual clock
+    assert!(execution.elapsed_seconds > 0);
+    assert_eq!(
+        execution.output["MergeOutput"]["detailStatus"],
+        "GraphMerged"
+    );
+
+    assert_eq!(mock_loader.jobs().len(), 1);
+    let sparql_updates = mock_loader.sparql_updates();
+    assert!(sparql_updates
+        .iter()
+        .any(|update| update.contains("ADD SILENT GRAPH")));
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_rdf_load_failed() -> Result<(), ekg_error::Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    mock_loader.push_start_outcome(StartOutcome::Accept(JobLifecycle::failed()));
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+    let state_machine = rdf_load_state_machine(&handle).await?;
+
+    let execution = state_machine.execute(input()).await?;
+
+    assert!(!execution.succeeded());
+    assert_eq!(execution.final_state, LOADER_JOB_FAILED);
+    assert_eq!(execution.visits(CHECK_LOADER_JOB_STATUS), 3);
+    assert_eq!(execution.visits(MERGE_INTO_TARGET_GRAPH), 0);
+    assert!(!mock_loader
+        .sparql_updates()
+        .iter()
+        .any(|update| update.contains("ADD SILENT GRAPH")));
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_rdf_load_queue_full() -> Result<(), ekg_error::Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    mock_loader.push_start_outcome(StartOutcome::QueueFull);
+    mock_loader.push_start_outcome(StartOutcome::Accept(JobLifecycle::completed()));
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+    let state_machine = rdf_load_state_machine(&handle).await?;
+
+    let execution = state_machine.execute(input()).await?;
+
+    assert!(execution.succeeded(), "{:#?}", execution);
+    assert_eq!(execution.visits(INSTRUCT_NEPTUNE_TO_LOAD), 2);
+    assert_eq!(execution.visits(RETRY_LOAD_INSTRUCTION), 1);
+    assert_eq!(execution.output["LoadOutput"]["attempt"], 2);
+    assert_eq!(mock_loader.jobs().len(), 1);
+
+    Ok(())
+}
+
+#[test_log::test(tokio::test)]
+async fn test_max_transitions() -> Result<(), ekg_error::Error> {
+    let _env = ENV_LOCK.lock().await;
+    let mock_loader = MockLoader::new();
+    mock_loader.push_start_outcome(StartOutcome::Accept(JobLifecycle::completed()));
+    let handle = mock_loader
+        .serve(([127, 0, 0, 1], 0).into())
+        .expect("could not start the mock loader");
+    let state_machine = rdf_load_state_machine(&handle)
+        .await?
+        .with_max_transitions(3);
+
+    assert!(state_machine.execute(input()).await.is_err());
+
+    Ok(())
+}
@@ -12,6 +12,7 @@ locals {
   lfn_role_invoke = "${local.full_name}-lfn-invoke"
   lfn_role_load   = "${local.full_name}-lfn-load"
   lfn_role_check  = "${local.full_name}-lfn-check"
+  lfn_role_merge  = "${local.full_name}-lfn-merge"
 
   default_tags = {
     org_short   = var.org_short
@@ -42,4 +43,11 @@ locals {
   lambda_check_crate_path   = "${path.module}/crate/${local.lambda_check_crate}"
   lambda_check_package_path = "${path.module}/target/lambda/${local.lambda_check_crate}"
   lambda_check_zip          = "${path.module}/target/lambda/tf-artifact-${local.lambda_check_crate}-${var.name}.zip"
+
+  // The lambda function "merge" which is used to merge the named graph of a loaded file into the target graph
+  lambda_merge_name         = "${local.full_name}-merge"
+  lambda_merge_crate        = "ekg-lfn-merge"
+  lambda_merge_crate_path   = "${path.module}/crate/${local.lambda_merge_crate}"
+  lambda_merge_package_path = "${path.module}/target/lambda/${local.lambda_merge_crate}"
+  lambda_merge_zip          = "${path.module}/target/lambda/tf-artifact-${local.lambda_merge_crate}-${var.name}.zip"
 }
@@ -6,6 +6,10 @@ output "lambda_load_arn" {
   value = aws_lambda_function.load.qualified_arn
 }
 
+output "lambda_merge_arn" {
+  value = aws_lambda_function.merge.qualified_arn
+}
+
 output "sns_topic_rdf_load_arn" {
   value = aws_sns_topic.rdf_load.arn
 }
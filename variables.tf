@@ -105,6 +105,17 @@ variable "ekg_graph_base_placeholder" {
   default     = "https://placeholder.kg/graph"
 }
 
+variable "staging_prefix" {
+  description = "The prefix of the keys under which the load lambda function writes the copies of the source files with their placeholders replaced by the internal bases, in the source bucket"
+  type        = string
+  default     = "ekg-staging/"
+
+  validation {
+    condition     = endswith(var.staging_prefix, "/")
+    error_message = "The staging_prefix must end with a '/'."
+  }
+}
+
 variable "ekg_base_internal" {
   description = "The placeholder for the EKG base URL"
   type        = string
@@ -180,6 +191,81 @@ variable "ekg_sparql_update_endpoint" {
   type        = string
 }
 
+variable "load_strategy" {
+  description = "What happens when a file is uploaded again: 'append' loads the new version into the existing named graph, 'replace' loads it into a staging graph first and then replaces the named graph with it"
+  type        = string
+  default     = "append"
+
+  validation {
+    condition     = contains(["append", "replace"], var.load_strategy)
+    error_message = "The load_strategy must be either 'append' or 'replace'."
+  }
+}
+
+variable "graph_naming" {
+  description = "How the named graph of a file is named: 's3-uri' (its S3 URI), 'hash' (<ekg_graph_base_internal> followed by a hash of its key) or a template such as '{graph_base}{prefix}/{stem}', see crate/ekg-aws-util/src/neptune/graph_naming.rs"
+  type        = string
+  default     = "s3-uri"
+
+  validation {
+    condition     = contains(["s3-uri", "hash"], var.graph_naming) || can(regex("[{]", var.graph_naming))
+    error_message = "The graph_naming must be 's3-uri', 'hash' or a template with placeholders such as {graph_base}."
+  }
+}
+
+variable "merge_target_graph" {
+  description = "IRI of the graph into which the named graph of every loaded file is merged, defaults to <ekg_graph_base_internal>merged-<name>"
+  type        = string
+  default     = ""
+}
+
+variable "retry_policy" {
+  description = <<-EOT
+    How long the state machine waits before it instructs the loader again or checks the status of a loader job
+    again (exponential backoff with 'none', 'full' or 'decorrelated' jitter) and after how many attempts it gives up
+  EOT
+  type        = object({
+    base_delay_seconds = optional(number, 10)
+    multiplier         = optional(number, 2)
+    max_delay_seconds  = optional(number, 300)
+    jitter             = optional(string, "full")
+    max_attempts       = optional(number, 100)
+  })
+  default     = {}
+
+  validation {
+    condition     = contains(["none", "full", "decorrelated"], var.retry_policy.jitter)
+    error_message = "The retry_policy jitter must be either 'none', 'full' or 'decorrelated'."
+  }
+}
+
+variable "routing_table" {
+  description = <<-EOT
+    Routing table (TOML or JSON) that decides per key prefix or glob which pipeline loads a file, into which named
+    graph, with which base URI and format, or whether it is loaded at all, see crate/ekg-aws-util/src/routing.rs
+  EOT
+  type        = string
+  default     = ""
+}
+
+variable "routing_table_s3_uri" {
+  description = "S3 URI of the routing table, when it is not given in the routing_table variable itself"
+  type        = string
+  default     = ""
+}
+
+variable "verify_sns_signatures" {
+  description = "Whether the invoke lambda function rejects the SNS notifications (also the ones wrapped in SQS messages) that are not signed by SNS, it then downloads the signing certificates from SNS"
+  type        = bool
+  default     = false
+}
+
+variable "routed_state_machine_arns" {
+  description = "ARNs of the RDF load state machines of the other pipelines that the routing table routes files to"
+  type        = list(string)
+  default     = []
+}
+
 variable "python_bin" {
   description = "The path to the python binary"
   type        = string